@@ -0,0 +1,1775 @@
+/*
+    Custom iterator adapters
+
+    An adapter is an iterator that wraps another iterator and changes
+    what comes out of it (.map(), .filter(), .take() are all adapters).
+
+    The recipe is the same as for SongIterator:
+    - a dedicated struct holding the inner iterator plus any state
+    - an `impl Iterator` for that struct
+    - a function (or method) which constructs the struct
+*/
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::iter::Peekable;
+use std::ops::Add;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::vec;
+
+/*
+    Running fold (prefix sums)
+
+    Like .fold(), but instead of only returning the final value we yield
+    the accumulator after every element:
+
+        running_sum(vec![1, 2, 3, 4]) -> 1, 3, 6, 10
+
+    The closure is stored in the struct, so it can be called again on every
+    call to .next(). It needs to be FnMut (not Fn) so that callers can pass
+    closures which update their own state.
+*/
+
+pub struct RunningFold<I, B, F> {
+    iter: I,
+    acc: B,
+    f: F,
+}
+
+impl<I, B, F> Iterator for RunningFold<I, B, F>
+where
+    I: Iterator,
+    B: Clone,
+    F: FnMut(&B, I::Item) -> B,
+{
+    type Item = B;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The ? operator returns None as soon as the inner iterator is done
+        let item = self.iter.next()?;
+        self.acc = (self.f)(&self.acc, item);
+        Some(self.acc.clone())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Exactly one output per input
+        self.iter.size_hint()
+    }
+}
+
+pub fn running_fold<I, B, F>(
+    iter: I,
+    init: B,
+    f: F,
+) -> RunningFold<I::IntoIter, B, F>
+where
+    I: IntoIterator,
+    B: Clone,
+    F: FnMut(&B, I::Item) -> B,
+{
+    RunningFold { iter: iter.into_iter(), acc: init, f }
+}
+
+// running_sum is just a running_fold starting from zero (Default).
+// A closure that captures nothing can be used as a plain fn pointer,
+// which lets us name the return type (needed by MyIteratorExt below).
+pub type RunningSum<I, T> = RunningFold<I, T, fn(&T, T) -> T>;
+
+pub fn running_sum<I, T>(iter: I) -> RunningSum<I::IntoIter, T>
+where
+    I: IntoIterator<Item = T>,
+    T: Add<Output = T> + Copy + Default,
+{
+    running_fold(iter, T::default(), |&acc, x| acc + x)
+}
+
+#[test]
+fn test_running_sum() {
+    let sums: Vec<usize> = running_sum(vec![1, 2, 3, 4]).collect();
+    assert_eq!(sums, vec![1, 3, 6, 10]);
+
+    let empty: Vec<i32> = running_sum(Vec::new()).collect();
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn test_running_fold() {
+    // Running maximum
+    let maxes: Vec<i32> =
+        running_fold(vec![3, 1, 4, 1, 5], i32::MIN, |&m, x| m.max(x)).collect();
+    assert_eq!(maxes, vec![3, 3, 4, 4, 5]);
+
+    // Works on infinite iterators as long as we .take()
+    let evens: Vec<usize> =
+        running_fold(0.., 0, |&acc, x| acc + 2 * x).take(4).collect();
+    assert_eq!(evens, vec![0, 2, 6, 12]);
+
+    // Building up a String
+    let prefixes: Vec<String> =
+        running_fold("abc".chars(), String::new(), |s, c| {
+            format!("{}{}", s, c)
+        })
+        .collect();
+    assert_eq!(prefixes, vec!["a", "ab", "abc"]);
+}
+
+/*
+    Moving average
+
+    Yields the mean of the last `window` items. We keep the last `window`
+    items in a VecDeque (used as a ring buffer) together with their sum, so
+    each step is O(1): add the new item, subtract the one falling off.
+
+    Edge cases:
+    - Nothing is emitted until the window is full, so an input shorter than
+      `window` produces an empty iterator.
+    - A window of 0 makes no sense (mean of nothing), so we panic, just like
+      std's .chunks(0) and .windows(0).
+*/
+
+pub struct MovingAverage<I> {
+    iter: I,
+    window: usize,
+    buf: VecDeque<f64>,
+    sum: f64,
+}
+
+impl<I> Iterator for MovingAverage<I>
+where
+    I: Iterator<Item = f64>,
+{
+    type Item = f64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Fill up the window first (only loops more than once on the
+        // first call)
+        loop {
+            let x = self.iter.next()?;
+            self.buf.push_back(x);
+            self.sum += x;
+            if self.buf.len() > self.window {
+                self.sum -= self.buf.pop_front().unwrap();
+            }
+            if self.buf.len() == self.window {
+                return Some(self.sum / self.window as f64);
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // The first output needs `window` items; after that it's one output
+        // per input.
+        let (lo, hi) = self.iter.size_hint();
+        let missing = self.window - self.buf.len();
+        // Saturating: an unbounded inner iterator reports usize::MAX
+        let adjust = |n: usize| n.saturating_sub(missing.saturating_sub(1));
+        (adjust(lo), hi.map(adjust))
+    }
+}
+
+pub fn moving_average<I>(iter: I, window: usize) -> MovingAverage<I::IntoIter>
+where
+    I: IntoIterator<Item = f64>,
+{
+    assert!(window > 0, "moving_average: window must be positive");
+    MovingAverage {
+        iter: iter.into_iter(),
+        window,
+        buf: VecDeque::with_capacity(window + 1),
+        sum: 0.0,
+    }
+}
+
+#[test]
+fn test_moving_average() {
+    let avgs: Vec<f64> =
+        moving_average(vec![1.0, 2.0, 3.0, 4.0, 5.0], 2).collect();
+    assert_eq!(avgs, vec![1.5, 2.5, 3.5, 4.5]);
+
+    // Window of 1 is the identity
+    let same: Vec<f64> = moving_average(vec![1.0, 5.0, 2.0], 1).collect();
+    assert_eq!(same, vec![1.0, 5.0, 2.0]);
+
+    // Window equal to the length: exactly one output
+    let one: Vec<f64> = moving_average(vec![1.0, 2.0, 3.0], 3).collect();
+    assert_eq!(one, vec![2.0]);
+
+    // Input shorter than the window: nothing is emitted
+    let none: Vec<f64> = moving_average(vec![1.0, 2.0], 3).collect();
+    assert!(none.is_empty());
+    assert_eq!(moving_average(Vec::new(), 3).count(), 0);
+
+    // size_hint agrees with the actual count
+    let it = moving_average(vec![0.0; 10], 4);
+    assert_eq!(it.size_hint(), (7, Some(7)));
+    assert_eq!(it.count(), 7);
+}
+
+#[test]
+#[should_panic]
+fn test_moving_average_zero_window() {
+    moving_average(vec![1.0], 0);
+}
+
+#[test]
+fn test_moving_average_infinite() {
+    let it = moving_average(std::iter::repeat(1.0), 3);
+    assert_eq!(it.size_hint(), (usize::MAX - 2, None));
+    let avgs: Vec<f64> = it.take(10).collect();
+    assert_eq!(avgs, vec![1.0; 10]);
+}
+
+/*
+    Exponentially weighted moving average (EWMA)
+
+    Instead of a fixed window, every item contributes, but older items are
+    weighted less and less:
+
+        s_0 = x_0
+        s_t = alpha * x_t + (1 - alpha) * s_(t-1)
+
+    alpha close to 1 follows the input closely; alpha close to 0 is very
+    smooth. Unlike moving_average this needs only one f64 of state, and it
+    emits a value for every input.
+*/
+
+pub struct Ewma<I> {
+    iter: I,
+    alpha: f64,
+    current: Option<f64>,
+}
+
+impl<I> Iterator for Ewma<I>
+where
+    I: Iterator<Item = f64>,
+{
+    type Item = f64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.iter.next()?;
+        let smoothed = match self.current {
+            None => x,
+            Some(prev) => self.alpha * x + (1.0 - self.alpha) * prev,
+        };
+        self.current = Some(smoothed);
+        Some(smoothed)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+pub fn ewma<I>(iter: I, alpha: f64) -> Ewma<I::IntoIter>
+where
+    I: IntoIterator<Item = f64>,
+{
+    assert!(alpha > 0.0 && alpha <= 1.0, "ewma: alpha must be in (0, 1]");
+    Ewma { iter: iter.into_iter(), alpha, current: None }
+}
+
+#[test]
+fn test_ewma() {
+    let smoothed: Vec<f64> = ewma(vec![0.0, 10.0, 10.0, 10.0], 0.5).collect();
+    assert_eq!(smoothed, vec![0.0, 5.0, 7.5, 8.75]);
+
+    // alpha = 1 means no smoothing at all
+    let same: Vec<f64> = ewma(vec![3.0, 1.0, 4.0], 1.0).collect();
+    assert_eq!(same, vec![3.0, 1.0, 4.0]);
+
+    assert_eq!(ewma(Vec::new(), 0.3).count(), 0);
+
+    // Chains with moving_average: smooth, then average
+    let both: Vec<f64> =
+        moving_average(ewma(vec![2.0, 2.0, 2.0], 0.1), 2).collect();
+    assert_eq!(both, vec![2.0, 2.0]);
+}
+
+#[test]
+#[should_panic]
+fn test_ewma_bad_alpha() {
+    ewma(vec![1.0], 0.0);
+}
+
+/*
+    Sorting adapter
+
+    Not every adapter can be lazy! To yield the smallest item first we
+    have to look at *every* item, so sorted_by_key must consume the whole
+    inner iterator before it can return anything. (On an infinite
+    iterator it never returns.)
+
+    We still delay that work until the first call to .next(), so building
+    the pipeline is free. The sort is stable: items with equal keys stay in
+    their original order.
+*/
+
+pub struct SortedByKey<I: Iterator, F> {
+    // The unsorted source and the key function, until we've sorted
+    source: Option<(I, F)>,
+    sorted: vec::IntoIter<I::Item>,
+}
+
+impl<I, F, K> Iterator for SortedByKey<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item) -> K,
+    K: Ord,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((iter, mut key)) = self.source.take() {
+            let mut items: Vec<I::Item> = iter.collect();
+            items.sort_by_key(|x| key(x));
+            self.sorted = items.into_iter();
+        }
+        self.sorted.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.source {
+            Some((iter, _)) => iter.size_hint(),
+            None => self.sorted.size_hint(),
+        }
+    }
+}
+
+pub fn sorted_by_key<I, F, K>(iter: I, key: F) -> SortedByKey<I::IntoIter, F>
+where
+    I: IntoIterator,
+    F: FnMut(&I::Item) -> K,
+    K: Ord,
+{
+    SortedByKey {
+        source: Some((iter.into_iter(), key)),
+        sorted: Vec::new().into_iter(),
+    }
+}
+
+#[test]
+fn test_sorted_by_key() {
+    let words = vec!["banana", "fig", "apple", "kiwi", "date"];
+    let by_len: Vec<&str> = sorted_by_key(words, |w| w.len()).collect();
+    // Stable: "kiwi" before "date", "banana" last
+    assert_eq!(by_len, vec!["fig", "kiwi", "date", "apple", "banana"]);
+
+    // Reverse order using std::cmp::Reverse as the key
+    let desc: Vec<i32> =
+        sorted_by_key(vec![2, 9, 4], |&x| std::cmp::Reverse(x)).collect();
+    assert_eq!(desc, vec![9, 4, 2]);
+
+    // Sorting is part of a pipeline like any other adapter
+    let total: i32 = sorted_by_key(1..=5, |&x| -x).take(2).sum();
+    assert_eq!(total, 9);
+}
+
+#[test]
+fn test_sorted_by_key_is_deferred() {
+    use std::cell::Cell;
+    // Nothing is pulled from the source until the first .next()
+    let pulled = Cell::new(0);
+    let source = (0..3).inspect(|_| pulled.set(pulled.get() + 1));
+    let mut it = sorted_by_key(source, |&x| x);
+    assert_eq!(pulled.get(), 0);
+    assert_eq!(it.size_hint(), (3, Some(3)));
+    assert_eq!(it.next(), Some(0));
+    assert_eq!(pulled.get(), 3);
+}
+
+/*
+    Coalesce
+
+    Merge adjacent items whenever the closure says they can be merged.
+    The closure gets two neighbours by value and returns either
+    - Ok(merged) -- replace both with one item, then try to merge again
+      with whatever comes next
+    - Err((a, b)) -- they can't be merged; a is yielded and b is kept as
+      the start of the next group
+
+    Because the closure takes ownership, it has to hand the items back in
+    the Err case (we'd have nothing left to yield otherwise).
+
+    State machine: we hold at most one pending item between calls to
+    .next(), the second half of the last Err.
+*/
+
+pub struct Coalesce<I: Iterator, F> {
+    iter: I,
+    pending: Option<I::Item>,
+    f: F,
+}
+
+impl<I, F> Iterator for Coalesce<I, F>
+where
+    I: Iterator,
+    F: FnMut(I::Item, I::Item) -> Result<I::Item, (I::Item, I::Item)>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut acc = match self.pending.take() {
+            Some(x) => x,
+            None => self.iter.next()?,
+        };
+        for x in self.iter.by_ref() {
+            match (self.f)(acc, x) {
+                Ok(merged) => acc = merged,
+                Err((done, next)) => {
+                    self.pending = Some(next);
+                    return Some(done);
+                }
+            }
+        }
+        Some(acc)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Everything could merge into one item, or nothing could merge
+        let (lo, hi) = self.iter.size_hint();
+        let extra = self.pending.is_some() as usize;
+        (lo.saturating_add(extra).min(1), hi.and_then(|h| h.checked_add(extra)))
+    }
+}
+
+pub fn coalesce<I, F>(iter: I, f: F) -> Coalesce<I::IntoIter, F>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item, I::Item) -> Result<I::Item, (I::Item, I::Item)>,
+{
+    Coalesce { iter: iter.into_iter(), pending: None, f }
+}
+
+#[test]
+fn test_coalesce_runs() {
+    // Sum runs of numbers with the same sign
+    let v = vec![1, 2, -1, -3, 4, -2, -2, -2];
+    let runs: Vec<i32> =
+        coalesce(
+            v,
+            |a, b| {
+                if (a < 0) == (b < 0) {
+                    Ok(a + b)
+                } else {
+                    Err((a, b))
+                }
+            },
+        )
+        .collect();
+    assert_eq!(runs, vec![3, -4, 4, -6]);
+
+    // Collapse repeated characters
+    let squeezed: String =
+        coalesce(
+            "aaabccdd".chars(),
+            |a, b| {
+                if a == b {
+                    Ok(a)
+                } else {
+                    Err((a, b))
+                }
+            },
+        )
+        .collect();
+    assert_eq!(squeezed, "abcd");
+
+    let empty: Vec<i32> = coalesce(Vec::new(), |a, b| Err((a, b))).collect();
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn test_coalesce_ranges() {
+    // Merge overlapping (start, end) ranges, assumed sorted by start
+    let ranges = vec![(1, 3), (2, 6), (8, 10), (10, 12), (15, 18)];
+    let merged: Vec<(i32, i32)> = coalesce(ranges, |(s1, e1), (s2, e2)| {
+        if s2 <= e1 {
+            Ok((s1, e1.max(e2)))
+        } else {
+            Err(((s1, e1), (s2, e2)))
+        }
+    })
+    .collect();
+    assert_eq!(merged, vec![(1, 6), (8, 12), (15, 18)]);
+}
+
+#[test]
+fn test_coalesce_infinite() {
+    // 0.. reports a lower bound of usize::MAX; size_hint mustn't overflow
+    let it = coalesce(0.., |a, b| Err((a, b)));
+    assert_eq!(it.size_hint(), (1, None));
+    let first: Vec<u32> = it.take(10).collect();
+    assert_eq!(first, (0..10).collect::<Vec<_>>());
+
+    // Same with an item pending from the last Err
+    let mut it =
+        coalesce(0.., |a, b| if b % 3 == 0 { Err((a, b)) } else { Ok(b) });
+    assert_eq!(it.next(), Some(2));
+    assert_eq!(it.size_hint(), (1, None));
+}
+
+/*
+    Fallible adapters
+
+    Real pipelines often have Result items: parsing each line of a file
+    can fail. With plain .map() every closure has to match on Ok/Err
+    itself. These adapters do that once:
+
+    - try_map applies a fallible closure to Ok values; Errs pass through
+      untouched
+    - try_filter filters Ok values; Errs are always kept, so that the
+      consumer still gets to see them
+
+    Neither adapter stops at the first Err. That's the consumer's choice
+    (see consumers::collect_until_err).
+*/
+
+pub struct TryMap<I, F> {
+    iter: I,
+    f: F,
+}
+
+impl<I, F, T, U, E> Iterator for TryMap<I, F>
+where
+    I: Iterator<Item = Result<T, E>>,
+    F: FnMut(T) -> Result<U, E>,
+{
+    type Item = Result<U, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Result::and_then does exactly this for one item
+        self.iter.next().map(|r| r.and_then(&mut self.f))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+pub fn try_map<I, F, T, U, E>(iter: I, f: F) -> TryMap<I::IntoIter, F>
+where
+    I: IntoIterator<Item = Result<T, E>>,
+    F: FnMut(T) -> Result<U, E>,
+{
+    TryMap { iter: iter.into_iter(), f }
+}
+
+pub struct TryFilter<I, F> {
+    iter: I,
+    predicate: F,
+}
+
+impl<I, F, T, E> Iterator for TryFilter<I, F>
+where
+    I: Iterator<Item = Result<T, E>>,
+    F: FnMut(&T) -> bool,
+{
+    type Item = Result<T, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let predicate = &mut self.predicate;
+        self.iter.find(|r| match r {
+            Ok(x) => predicate(x),
+            Err(_) => true,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+pub fn try_filter<I, F, T, E>(
+    iter: I,
+    predicate: F,
+) -> TryFilter<I::IntoIter, F>
+where
+    I: IntoIterator<Item = Result<T, E>>,
+    F: FnMut(&T) -> bool,
+{
+    TryFilter { iter: iter.into_iter(), predicate }
+}
+
+#[test]
+fn test_try_map() {
+    let lines = vec!["1", "2", "x", "4"];
+    let parsed = lines.into_iter().map(|s| s.parse::<i32>());
+    let doubled: Vec<Result<i32, _>> = try_map(parsed, |n| Ok(n * 2)).collect();
+    assert_eq!(doubled[0], Ok(2));
+    assert_eq!(doubled[1], Ok(4));
+    assert!(doubled[2].is_err());
+    assert_eq!(doubled[3], Ok(8));
+
+    // The closure can fail too
+    let checked: Vec<Result<u8, String>> = try_map(
+        vec![Ok(100), Err("bad input".to_string()), Ok(200)],
+        |x: u8| x.checked_add(100).ok_or(format!("{} overflows", x)),
+    )
+    .collect();
+    assert_eq!(
+        checked,
+        vec![
+            Ok(200),
+            Err("bad input".to_string()),
+            Err("200 overflows".to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_try_filter() {
+    let items: Vec<Result<i32, &str>> = vec![Ok(1), Err("e1"), Ok(2), Ok(3)];
+    let odd: Vec<Result<i32, &str>> =
+        try_filter(items, |&x| x % 2 == 1).collect();
+    assert_eq!(odd, vec![Ok(1), Err("e1"), Ok(3)]);
+}
+
+/*
+    Pagination
+
+    Split items into pages of `page_size`, the way a web API returns
+    results. Each Page knows its number (starting from 1) and whether it's
+    the last page.
+
+    Knowing is_last is the interesting part: after filling a page we have
+    to check whether *anything* is left, without losing that item.
+    Peekable does the buffering for us: .peek() looks at the next item
+    and keeps it for the next call to .next().
+*/
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page<T> {
+    pub number: usize,
+    pub items: Vec<T>,
+    pub is_last: bool,
+}
+
+pub struct Paginate<I: Iterator> {
+    iter: Peekable<I>,
+    page_size: usize,
+    number: usize,
+}
+
+impl<I: Iterator> Iterator for Paginate<I> {
+    type Item = Page<I::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let items: Vec<I::Item> =
+            self.iter.by_ref().take(self.page_size).collect();
+        if items.is_empty() {
+            return None;
+        }
+        self.number += 1;
+        let is_last = self.iter.peek().is_none();
+        Some(Page { number: self.number, items, is_last })
+    }
+}
+
+pub fn paginate<I>(iter: I, page_size: usize) -> Paginate<I::IntoIter>
+where
+    I: IntoIterator,
+{
+    assert!(page_size > 0, "paginate: page_size must be positive");
+    Paginate { iter: iter.into_iter().peekable(), page_size, number: 0 }
+}
+
+#[test]
+fn test_paginate() {
+    let pages: Vec<Page<i32>> = paginate(1..=7, 3).collect();
+    assert_eq!(
+        pages,
+        vec![
+            Page { number: 1, items: vec![1, 2, 3], is_last: false },
+            Page { number: 2, items: vec![4, 5, 6], is_last: false },
+            Page { number: 3, items: vec![7], is_last: true },
+        ]
+    );
+
+    // Exact multiple: the last full page is marked, no empty page after it
+    let pages: Vec<Page<i32>> = paginate(1..=4, 2).collect();
+    assert_eq!(pages.len(), 2);
+    assert!(pages[1].is_last);
+
+    assert_eq!(paginate(Vec::<i32>::new(), 5).count(), 0);
+}
+
+/*
+    Progress reporting
+
+    progress_every passes items through unchanged, but calls a closure
+    with the number of items seen so far every `n` items, and once more
+    when the inner iterator runs out (unless that count was just
+    reported). For example, printing a progress line every 1000 records
+    of a long pipeline:
+
+        records.progress_every(1000, |n| eprintln!("{} records", n))
+*/
+
+pub struct ProgressEvery<I, F> {
+    iter: I,
+    every: usize,
+    count: usize,
+    callback: F,
+    finished: bool,
+}
+
+impl<I, F> Iterator for ProgressEvery<I, F>
+where
+    I: Iterator,
+    F: FnMut(usize),
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.iter.next() {
+            Some(item) => {
+                self.count += 1;
+                if self.count.is_multiple_of(self.every) {
+                    (self.callback)(self.count);
+                }
+                Some(item)
+            }
+            None => {
+                // Only report the end once, however many times next() is
+                // called after that
+                if !self.finished {
+                    self.finished = true;
+                    if !self.count.is_multiple_of(self.every) || self.count == 0
+                    {
+                        (self.callback)(self.count);
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+pub fn progress_every<I, F>(
+    iter: I,
+    every: usize,
+    callback: F,
+) -> ProgressEvery<I::IntoIter, F>
+where
+    I: IntoIterator,
+    F: FnMut(usize),
+{
+    assert!(every > 0, "progress_every: n must be positive");
+    ProgressEvery {
+        iter: iter.into_iter(),
+        every,
+        count: 0,
+        callback,
+        finished: false,
+    }
+}
+
+#[test]
+fn test_progress_every() {
+    let mut reports = Vec::new();
+    let total: i32 =
+        progress_every(1..=7, 3, |n| reports.push(n)).map(|x| x * 10).sum();
+    assert_eq!(total, 280);
+    assert_eq!(reports, vec![3, 6, 7]);
+
+    // Exact multiple: the final count isn't reported twice
+    let mut reports = Vec::new();
+    progress_every(0..6, 3, |n| reports.push(n)).for_each(drop);
+    assert_eq!(reports, vec![3, 6]);
+
+    // Empty input still reports once at the end
+    let mut reports = Vec::new();
+    let mut it = progress_every(Vec::<u8>::new(), 3, |n| reports.push(n));
+    assert_eq!(it.next(), None);
+    assert_eq!(it.next(), None);
+    drop(it);
+    assert_eq!(reports, vec![0]);
+}
+
+/*
+    Parallel map
+
+    par_map(threads, f) computes f on a pool of worker threads, but still
+    yields the results in the original order, so it's a drop-in
+    replacement for .map(f) when f is slow.
+
+    How it works:
+    - The input iterator stays on the calling thread. Items are numbered
+      and sent to the workers over a channel (a shared job queue).
+    - Each worker receives (index, item), computes f(item) and sends
+      (index, result) back over a second channel.
+    - Results arrive in whatever order the workers finish, so they wait
+      in a BTreeMap until it's their turn.
+    - At most 2 * threads items are in flight at once, so par_map stays
+      lazy and works on infinite iterators.
+
+    The bounds say what crosses threads: items (T: Send) go to the
+    workers, results (U: Send) come back, and all workers share the one
+    closure (F: Send + Sync). 'static because the threads could outlive
+    the caller's stack frame as far as the compiler knows.
+
+    If f panics on a worker, the panic is caught there and re-raised on
+    the consuming thread when that item's turn comes.
+*/
+
+type Job<T> = (usize, T);
+type JobResult<U> = (usize, thread::Result<U>);
+
+pub struct ParMap<I: Iterator, U> {
+    iter: I,
+    jobs: Option<mpsc::Sender<Job<I::Item>>>,
+    results: mpsc::Receiver<JobResult<U>>,
+    workers: Vec<thread::JoinHandle<()>>,
+    // Results that arrived before their turn
+    pending: BTreeMap<usize, thread::Result<U>>,
+    next_in: usize,
+    next_out: usize,
+    max_in_flight: usize,
+    input_done: bool,
+}
+
+impl<I, U> Iterator for ParMap<I, U>
+where
+    I: Iterator,
+{
+    type Item = U;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Keep the workers busy
+        while !self.input_done
+            && self.next_in - self.next_out < self.max_in_flight
+        {
+            match self.iter.next() {
+                Some(item) => {
+                    let jobs =
+                        self.jobs.as_ref().expect("par_map: pool shut down");
+                    jobs.send((self.next_in, item))
+                        .expect("par_map: workers gone");
+                    self.next_in += 1;
+                }
+                None => self.input_done = true,
+            }
+        }
+        if self.next_out == self.next_in {
+            return None;
+        }
+        // Wait until the result we need next has arrived
+        let result = loop {
+            if let Some(result) = self.pending.remove(&self.next_out) {
+                break result;
+            }
+            let (i, result) =
+                self.results.recv().expect("par_map: workers gone");
+            self.pending.insert(i, result);
+        };
+        self.next_out += 1;
+        match result {
+            Ok(u) => Some(u),
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl<I: Iterator, U> Drop for ParMap<I, U> {
+    fn drop(&mut self) {
+        // Closing the job channel makes every worker's recv() fail, which
+        // ends its loop
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+pub fn par_map<I, T, U, F>(
+    iter: I,
+    threads: usize,
+    f: F,
+) -> ParMap<I::IntoIter, U>
+where
+    I: IntoIterator<Item = T>,
+    T: Send + 'static,
+    U: Send + 'static,
+    F: Fn(T) -> U + Send + Sync + 'static,
+{
+    assert!(threads > 0, "par_map: need at least one thread");
+    let (job_tx, job_rx) = mpsc::channel::<Job<T>>();
+    let (result_tx, result_rx) = mpsc::channel();
+    // mpsc receivers can't be cloned, so the workers share one behind a
+    // Mutex; the closure is shared with an Arc
+    let job_rx = Arc::new(Mutex::new(job_rx));
+    let f = Arc::new(f);
+
+    let workers = (0..threads)
+        .map(|_| {
+            let job_rx = Arc::clone(&job_rx);
+            let result_tx = result_tx.clone();
+            let f = Arc::clone(&f);
+            thread::spawn(move || loop {
+                // The lock is released at the end of this statement, before
+                // the (possibly slow) call to f
+                let job = job_rx.lock().unwrap().recv();
+                let (i, item) = match job {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+                let result = panic::catch_unwind(AssertUnwindSafe(|| f(item)));
+                if result_tx.send((i, result)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+
+    ParMap {
+        iter: iter.into_iter(),
+        jobs: Some(job_tx),
+        results: result_rx,
+        workers,
+        pending: BTreeMap::new(),
+        next_in: 0,
+        next_out: 0,
+        max_in_flight: 2 * threads,
+        input_done: false,
+    }
+}
+
+#[test]
+fn test_par_map_ordered() {
+    use std::time::Duration;
+    // Later items finish first, but come out in order
+    let out: Vec<u64> = par_map(0..8u64, 4, |x| {
+        thread::sleep(Duration::from_millis(8 - x));
+        x * x
+    })
+    .collect();
+    assert_eq!(out, vec![0, 1, 4, 9, 16, 25, 36, 49]);
+
+    assert_eq!(par_map(Vec::<u8>::new(), 2, |x| x).count(), 0);
+}
+
+#[test]
+fn test_par_map_lazy() {
+    // Infinite input: only as many items as needed (plus a bounded
+    // look-ahead) are ever computed
+    let strings: Vec<String> =
+        par_map(0.., 3, |x: u32| format!("#{}", x)).take(5).collect();
+    assert_eq!(strings, vec!["#0", "#1", "#2", "#3", "#4"]);
+
+    let total: usize = par_map(0..1000usize, 8, |x| x % 7).sum();
+    assert_eq!(total, (0..1000).map(|x| x % 7).sum());
+}
+
+#[test]
+#[should_panic]
+fn test_par_map_propagates_panics() {
+    par_map(0..4, 2, |x: i32| if x == 2 { panic!("boom") } else { x })
+        .for_each(drop);
+}
+
+/*
+    Tee
+
+    tee() splits one iterator into two that each see every item, like the
+    Unix `tee` command. Both halves pull from the same source, so they
+    share it through Rc<RefCell<..>>: Rc for two owners, RefCell so that
+    either half can mutate the shared state from its own &mut self.
+
+    Items pulled by the half that's ahead are cloned into a buffer for
+    the half that's behind. The buffer only ever holds items for one
+    side (whichever is behind), and it grows as large as the distance
+    between them -- consuming one half completely before the other
+    buffers everything.
+
+    Once one half is dropped nobody will read its copies, so the buffer
+    has no reason to exist: Drop clears whatever was waiting for the
+    dropped half, and the survivor stops saving copies as soon as it's
+    the only owner of the shared state (Rc::strong_count is 1).
+*/
+
+struct TeeShared<I: Iterator> {
+    iter: I,
+    buffer: VecDeque<I::Item>,
+    // Which half the buffered items are waiting for
+    buffer_owner: bool,
+}
+
+pub struct Tee<I: Iterator> {
+    shared: Rc<RefCell<TeeShared<I>>>,
+    id: bool,
+}
+
+impl<I> Iterator for Tee<I>
+where
+    I: Iterator,
+    I::Item: Clone,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut shared = self.shared.borrow_mut();
+        if shared.buffer_owner == self.id {
+            if let Some(item) = shared.buffer.pop_front() {
+                return Some(item);
+            }
+        }
+        // We're ahead (or level): pull a new item and save a copy for the
+        // other half
+        let item = shared.iter.next()?;
+        if Rc::strong_count(&self.shared) > 1 {
+            shared.buffer.push_back(item.clone());
+            shared.buffer_owner = !self.id;
+        }
+        Some(item)
+    }
+}
+
+impl<I: Iterator> Drop for Tee<I> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+        if shared.buffer_owner == self.id {
+            shared.buffer.clear();
+        }
+    }
+}
+
+pub fn tee<I>(iter: I) -> (Tee<I::IntoIter>, Tee<I::IntoIter>)
+where
+    I: IntoIterator,
+    I::Item: Clone,
+{
+    let shared = Rc::new(RefCell::new(TeeShared {
+        iter: iter.into_iter(),
+        buffer: VecDeque::new(),
+        buffer_owner: false,
+    }));
+    (Tee { shared: Rc::clone(&shared), id: false }, Tee { shared, id: true })
+}
+
+#[test]
+fn test_tee() {
+    let (a, b) = tee(vec![1, 2, 3]);
+    assert_eq!(a.collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(b.collect::<Vec<_>>(), vec![1, 2, 3]);
+
+    // Interleaved consumption
+    let (mut a, mut b) = tee("abcd".chars());
+    assert_eq!(a.next(), Some('a'));
+    assert_eq!(a.next(), Some('b'));
+    assert_eq!(b.next(), Some('a'));
+    assert_eq!(b.next(), Some('b'));
+    assert_eq!(b.next(), Some('c'));
+    assert_eq!(a.next(), Some('c'));
+    assert_eq!(a.next(), Some('d'));
+    assert_eq!(b.next(), Some('d'));
+    assert_eq!(a.next(), None);
+    assert_eq!(b.next(), None);
+}
+
+#[test]
+fn test_tee_pulls_source_once() {
+    use std::cell::Cell;
+    let pulled = Cell::new(0);
+    let source = (0..5).inspect(|_| pulled.set(pulled.get() + 1));
+    let (evens, odds) = tee(source);
+    let evens: Vec<i32> = evens.filter(|x| x % 2 == 0).collect();
+    let odds: Vec<i32> = odds.filter(|x| x % 2 == 1).collect();
+    assert_eq!((evens, odds), (vec![0, 2, 4], vec![1, 3]));
+    assert_eq!(pulled.get(), 5);
+}
+
+#[test]
+fn test_tee_dropped_half() {
+    // With the other half gone, nothing is buffered
+    let (mut a, b) = tee(0u64..);
+    drop(b);
+    assert_eq!(a.by_ref().take(1000).last(), Some(999));
+    assert_eq!(a.shared.borrow().buffer.len(), 0);
+
+    // Dropping the half that's behind frees what it hadn't read yet
+    let (mut a, b) = tee(0..10);
+    assert_eq!(a.by_ref().take(5).count(), 5);
+    assert_eq!(a.shared.borrow().buffer.len(), 5);
+    drop(b);
+    assert_eq!(a.shared.borrow().buffer.len(), 0);
+    assert_eq!(a.collect::<Vec<_>>(), vec![5, 6, 7, 8, 9]);
+}
+
+/*
+    Overlapping mutable windows
+
+    std has slice.windows(n) for overlapping &[T] windows, but no
+    windows_mut. Why not? Iterator::next has the signature
+
+        fn next(&mut self) -> Option<Self::Item>
+
+    and Self::Item can't mention the lifetime of that &mut self borrow.
+    So every item returned by an Iterator must be able to live
+    independently of the iterator -- e.g. you could .collect() them all.
+    Overlapping &mut [T] windows that all exist at once would alias, which
+    Rust forbids.
+
+    Two ways around it:
+    - A "lending" iterator: a struct whose next() returns a window
+      borrowing from &mut self. The borrow checker then forces each window
+      to be dropped before asking for the next one. It can't implement
+      Iterator (so no for loops or adapters), but a while let loop works.
+    - A driver function taking a closure, which gets each window in turn.
+*/
+
+pub struct WindowsMut<'a, T> {
+    slice: &'a mut [T],
+    size: usize,
+    start: usize,
+}
+
+impl<'a, T> WindowsMut<'a, T> {
+    pub fn new(slice: &'a mut [T], size: usize) -> Self {
+        assert!(size > 0, "WindowsMut: size must be positive");
+        WindowsMut { slice, size, start: 0 }
+    }
+
+    // Note the elided lifetime: the window borrows from `self`, not 'a
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&mut [T]> {
+        let end = self.start + self.size;
+        if end > self.slice.len() {
+            return None;
+        }
+        let window = &mut self.slice[self.start..end];
+        self.start += 1;
+        Some(window)
+    }
+}
+
+pub fn for_each_window_mut<T, F>(slice: &mut [T], size: usize, mut f: F)
+where
+    F: FnMut(&mut [T]),
+{
+    let mut windows = WindowsMut::new(slice, size);
+    while let Some(window) = windows.next() {
+        f(window);
+    }
+}
+
+#[test]
+fn test_windows_mut() {
+    // Each window sees the writes made through the previous ones:
+    // a prefix sum, done in place
+    let mut v = vec![1, 2, 3, 4];
+    for_each_window_mut(&mut v, 2, |w| w[1] += w[0]);
+    assert_eq!(v, vec![1, 3, 6, 10]);
+
+    let mut v = vec![0; 5];
+    let mut windows = WindowsMut::new(&mut v, 3);
+    let mut count = 0;
+    while let Some(w) = windows.next() {
+        for x in w.iter_mut() {
+            *x += 1;
+        }
+        count += 1;
+    }
+    assert_eq!(count, 3);
+    assert_eq!(v, vec![1, 2, 3, 2, 1]);
+
+    // Window bigger than the slice: no windows at all
+    let mut short = [1, 2];
+    for_each_window_mut(&mut short, 3, |_| panic!("no windows expected"));
+}
+
+/*
+    Bounded cycle
+
+    std's .cycle() repeats an iterator forever. cycle_n(times) repeats it
+    exactly `times` times, e.g. "play the album three times".
+
+    Like .cycle(), it needs Clone: we keep an untouched copy of the
+    original iterator and clone it at the start of every pass.
+
+    size_hint = (what's left of this pass) + passes_left * (a full pass),
+    being careful that the multiplication can overflow.
+*/
+
+pub struct CycleN<I> {
+    orig: I,
+    // None once we're done
+    current: Option<I>,
+    // Full passes still to go after the current one
+    passes_left: usize,
+}
+
+impl<I> Iterator for CycleN<I>
+where
+    I: Iterator + Clone,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let item = self.current.as_mut()?.next();
+            if item.is_some() {
+                return item;
+            }
+            if self.passes_left == 0 {
+                self.current = None;
+                return None;
+            }
+            self.passes_left -= 1;
+            self.current = Some(self.orig.clone());
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let current = match &self.current {
+            Some(it) => it.size_hint(),
+            None => return (0, Some(0)),
+        };
+        let (lo, hi) = self.orig.size_hint();
+        let n = self.passes_left;
+        let total_lo = lo.saturating_mul(n).saturating_add(current.0);
+        let total_hi = hi
+            .and_then(|hi| hi.checked_mul(n))
+            .and_then(|rest| current.1.and_then(|cur| rest.checked_add(cur)));
+        (total_lo, total_hi)
+    }
+}
+
+pub fn cycle_n<I>(iter: I, times: usize) -> CycleN<I::IntoIter>
+where
+    I: IntoIterator,
+    I::IntoIter: Clone,
+{
+    let orig = iter.into_iter();
+    let current = if times == 0 { None } else { Some(orig.clone()) };
+    CycleN { orig, current, passes_left: times.saturating_sub(1) }
+}
+
+#[test]
+fn test_cycle_n() {
+    let album = ["intro", "single", "outro"];
+    let played: Vec<&str> = cycle_n(album.iter().copied(), 2).collect();
+    assert_eq!(
+        played,
+        vec!["intro", "single", "outro", "intro", "single", "outro"]
+    );
+
+    assert_eq!(cycle_n(1..4, 0).count(), 0);
+    assert_eq!(cycle_n(1..4, 1).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(cycle_n(0..0, 1_000_000).count(), 0);
+}
+
+#[test]
+fn test_cycle_n_size_hint() {
+    let mut it = cycle_n(0..4, 3);
+    assert_eq!(it.size_hint(), (12, Some(12)));
+    it.nth(5);
+    assert_eq!(it.size_hint(), (6, Some(6)));
+    assert_eq!(it.by_ref().count(), 6);
+    assert_eq!(it.size_hint(), (0, Some(0)));
+
+    // Overflowing upper bound becomes None rather than wrapping
+    let huge = cycle_n(0..usize::MAX, 2);
+    assert_eq!(huge.size_hint(), (usize::MAX, None));
+    // Unknown length (filter): lower bound 0
+    assert_eq!(
+        cycle_n((0..10).filter(|x| x % 2 == 0), 3).size_hint(),
+        (0, Some(30))
+    );
+}
+
+/*
+    Lenient vs strict handling of Options
+
+    Two ways to deal with an iterator of Option<T>:
+    - flatten_options: lenient, just skip the Nones
+    - all_some: strict, all-or-nothing. Some(vec) if every item was Some,
+      otherwise None -- and it stops at the first None without looking at
+      the rest. (This is what .collect::<Option<Vec<T>>>() does.)
+*/
+
+pub struct FlattenOptions<I> {
+    iter: I,
+}
+
+impl<I, T> Iterator for FlattenOptions<I>
+where
+    I: Iterator<Item = Option<T>>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Keep pulling until we find a Some (or run out)
+        self.iter.find_map(|opt| opt)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+pub fn flatten_options<I, T>(iter: I) -> FlattenOptions<I::IntoIter>
+where
+    I: IntoIterator<Item = Option<T>>,
+{
+    FlattenOptions { iter: iter.into_iter() }
+}
+
+pub fn all_some<I, T>(iter: I) -> Option<Vec<T>>
+where
+    I: IntoIterator<Item = Option<T>>,
+{
+    let mut result = Vec::new();
+    for item in iter {
+        result.push(item?);
+    }
+    Some(result)
+}
+
+#[test]
+fn test_flatten_options() {
+    let v = vec![Some(1), None, Some(3), None];
+    assert_eq!(flatten_options(v.clone()).collect::<Vec<_>>(), vec![1, 3]);
+    assert_eq!(all_some(v), None);
+
+    let w = vec![Some('a'), Some('b')];
+    assert_eq!(all_some(w.clone()), Some(vec!['a', 'b']));
+    assert_eq!(flatten_options(w).count(), 2);
+    assert_eq!(all_some(Vec::<Option<u8>>::new()), Some(vec![]));
+}
+
+#[test]
+fn test_all_some_short_circuits() {
+    let words = ["1", "2", "x", "4"];
+    let mut parsed = 0;
+    let nums = all_some(words.iter().map(|w| {
+        parsed += 1;
+        w.parse::<i32>().ok()
+    }));
+    assert_eq!(nums, None);
+    assert_eq!(parsed, 3);
+
+    let lenient: i32 =
+        flatten_options(words.iter().map(|w| w.parse::<i32>().ok())).sum();
+    assert_eq!(lenient, 7);
+}
+
+/*
+    Rewinding (backtracking)
+
+    A backtracking parser tries one way of reading the input, and if that
+    fails, goes back and tries another. Iterators only go forward, so
+    Rewind remembers everything it has handed out: every item pulled
+    from the source is cloned into a buffer.
+
+    checkpoint() is just a position in that buffer. rewind_to(cp) moves
+    back to it, and the following calls to next() replay the buffered
+    items before pulling anything new from the source -- so the source
+    itself is still only consumed once.
+
+    A checkpoint is only meaningful for the Rewind that made it. Nothing
+    in the type ties the two together, so rewind_to checks the one thing
+    it can: a checkpoint must not be past the end of the buffer (it
+    can't be, if it came from this Rewind). Rewinding to one that is
+    panics rather than corrupting the buffer position.
+
+    The buffer is never trimmed: memory grows with the number of items
+    consumed. That's fine for a line's worth of tokens, not for a file.
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint(usize);
+
+pub struct Rewind<I: Iterator> {
+    iter: I,
+    buffer: Vec<I::Item>,
+    // Index in buffer of the next item to yield; == buffer.len() when
+    // we're reading from the source
+    pos: usize,
+}
+
+impl<I: Iterator> Rewind<I> {
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.pos)
+    }
+
+    pub fn rewind_to(&mut self, checkpoint: Checkpoint) {
+        assert!(
+            checkpoint.0 <= self.buffer.len(),
+            "rewind_to: checkpoint at item {} but only {} items were read \
+             (was it made by a different Rewind?)",
+            checkpoint.0,
+            self.buffer.len()
+        );
+        self.pos = checkpoint.0;
+    }
+}
+
+impl<I> Iterator for Rewind<I>
+where
+    I: Iterator,
+    I::Item: Clone,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos == self.buffer.len() {
+            let item = self.iter.next()?;
+            self.buffer.push(item);
+        }
+        self.pos += 1;
+        Some(self.buffer[self.pos - 1].clone())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let replay = self.buffer.len() - self.pos;
+        let (lo, hi) = self.iter.size_hint();
+        (lo.saturating_add(replay), hi.and_then(|hi| hi.checked_add(replay)))
+    }
+}
+
+pub fn rewind<I>(iter: I) -> Rewind<I::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: Clone,
+{
+    Rewind { iter: iter.into_iter(), buffer: Vec::new(), pos: 0 }
+}
+
+#[test]
+fn test_rewind() {
+    let mut it = rewind(1..=5);
+    assert_eq!(it.next(), Some(1));
+    let cp = it.checkpoint();
+    assert_eq!(it.next(), Some(2));
+    assert_eq!(it.next(), Some(3));
+    assert_eq!(it.size_hint(), (2, Some(2)));
+
+    it.rewind_to(cp);
+    assert_eq!(it.size_hint(), (4, Some(4)));
+    assert_eq!(it.collect::<Vec<_>>(), vec![2, 3, 4, 5]);
+}
+
+#[test]
+#[should_panic(expected = "only 0 items were read")]
+fn test_rewind_foreign_checkpoint() {
+    let mut other = rewind(1..=5);
+    other.nth(2);
+    let mut it = rewind(1..=5);
+    it.rewind_to(other.checkpoint());
+}
+
+#[test]
+fn test_rewind_backtracking() {
+    use parsing::Tokens;
+    use std::borrow::Cow;
+
+    // Try to read "let <name> = <value>"; on failure, back up so the
+    // caller can try reading a plain expression instead
+    fn parse_let<'a, I>(toks: &mut Rewind<I>) -> Option<(String, String)>
+    where
+        I: Iterator<Item = Cow<'a, str>>,
+    {
+        let start = toks.checkpoint();
+        let result = (|| {
+            if toks.next()? != "let" {
+                return None;
+            }
+            let name = toks.next()?;
+            if toks.next()? != "=" {
+                return None;
+            }
+            Some((name.into_owned(), toks.next()?.into_owned()))
+        })();
+        if result.is_none() {
+            toks.rewind_to(start);
+        }
+        result
+    }
+
+    let mut toks = rewind(Tokens::new("let x = 5", &[' ']));
+    assert_eq!(parse_let(&mut toks), Some(("x".to_string(), "5".to_string())));
+    assert_eq!(toks.next(), None);
+
+    let mut toks = rewind(Tokens::new("let x + 5", &[' ']));
+    assert_eq!(parse_let(&mut toks), None);
+    let rest: Vec<Cow<str>> = toks.collect();
+    assert_eq!(rest, vec!["let", "x", "+", "5"]);
+}
+
+/*
+    Triples
+
+    std's zip and unzip stop at pairs. a.zip(b).zip(c) works, but yields
+    ((a, b), c) which has to be untangled in every closure. Zip3 yields
+    flat (a, b, c) triples and, like zip, stops as soon as any of the
+    three runs out. unzip3 goes the other way, splitting an iterator of
+    triples into three Vecs in one pass.
+*/
+
+pub struct Zip3<A, B, C> {
+    a: A,
+    b: B,
+    c: C,
+}
+
+impl<A, B, C> Iterator for Zip3<A, B, C>
+where
+    A: Iterator,
+    B: Iterator,
+    C: Iterator,
+{
+    type Item = (A::Item, B::Item, C::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some((self.a.next()?, self.b.next()?, self.c.next()?))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let hints =
+            [self.a.size_hint(), self.b.size_hint(), self.c.size_hint()];
+        let lo = hints.iter().map(|h| h.0).min().unwrap_or(0);
+        // The smallest known upper bound; None only if all are unbounded
+        let hi = hints.iter().filter_map(|h| h.1).min();
+        (lo, hi)
+    }
+}
+
+pub fn zip3<A, B, C>(
+    a: A,
+    b: B,
+    c: C,
+) -> Zip3<A::IntoIter, B::IntoIter, C::IntoIter>
+where
+    A: IntoIterator,
+    B: IntoIterator,
+    C: IntoIterator,
+{
+    Zip3 { a: a.into_iter(), b: b.into_iter(), c: c.into_iter() }
+}
+
+pub fn unzip3<I, A, B, C>(iter: I) -> (Vec<A>, Vec<B>, Vec<C>)
+where
+    I: IntoIterator<Item = (A, B, C)>,
+{
+    let iter = iter.into_iter();
+    let (lo, _) = iter.size_hint();
+    let mut result = (
+        Vec::with_capacity(lo),
+        Vec::with_capacity(lo),
+        Vec::with_capacity(lo),
+    );
+    for (a, b, c) in iter {
+        result.0.push(a);
+        result.1.push(b);
+        result.2.push(c);
+    }
+    result
+}
+
+#[test]
+fn test_zip3() {
+    let xs = [1.0, 2.0, 3.0];
+    let ys = [4.0, 5.0, 6.0];
+    let zs = [7.0, 8.0, 9.0, 10.0];
+    let dots: Vec<f64> =
+        zip3(&xs, &ys, &zs).map(|(x, y, z)| x * y * z).collect();
+    assert_eq!(dots, vec![28.0, 80.0, 162.0]);
+
+    assert_eq!(zip3(0..10, ['a', 'b'], 0..).size_hint(), (2, Some(2)));
+    assert_eq!(zip3(0.., 0.., 0..).size_hint(), (usize::MAX, None));
+    assert_eq!(zip3(0..5, 0.., 0..3).size_hint(), (3, Some(3)));
+}
+
+#[test]
+fn test_unzip3() {
+    let rows = vec![("a", 1, true), ("b", 2, false), ("c", 3, true)];
+    let (names, nums, flags) = unzip3(rows.clone());
+    assert_eq!(names, vec!["a", "b", "c"]);
+    assert_eq!(nums, vec![1, 2, 3]);
+    assert_eq!(flags, vec![true, false, true]);
+
+    // Round trip
+    let zipped: Vec<_> = zip3(names, nums, flags).collect();
+    assert_eq!(zipped, rows);
+}
+
+/*
+    Extension trait
+
+    All of the above are free functions: running_sum(moving_average(v, 3)).
+    That reads inside-out, unlike std's adapters which chain left to right:
+    v.iter().map(..).filter(..).
+
+    We can't add methods to std's Iterator trait, but we can define our own
+    trait with Iterator as a supertrait and implement it for every iterator
+    at once (a "blanket impl"). Then, once MyIteratorExt is imported, every
+    iterator has our methods too:
+
+        use adapters::MyIteratorExt;
+        v.into_iter().moving_average(3).running_sum()
+
+    Each method just forwards to the free function.
+*/
+
+pub trait MyIteratorExt: Iterator + Sized {
+    fn running_fold<B, F>(self, init: B, f: F) -> RunningFold<Self, B, F>
+    where
+        B: Clone,
+        F: FnMut(&B, Self::Item) -> B,
+    {
+        running_fold(self, init, f)
+    }
+
+    fn running_sum(self) -> RunningSum<Self, Self::Item>
+    where
+        Self::Item: Add<Output = Self::Item> + Copy + Default,
+    {
+        running_sum(self)
+    }
+
+    fn moving_average(self, window: usize) -> MovingAverage<Self>
+    where
+        Self: Iterator<Item = f64>,
+    {
+        moving_average(self, window)
+    }
+
+    fn ewma(self, alpha: f64) -> Ewma<Self>
+    where
+        Self: Iterator<Item = f64>,
+    {
+        ewma(self, alpha)
+    }
+
+    fn sorted_by_key<F, K>(self, key: F) -> SortedByKey<Self, F>
+    where
+        F: FnMut(&Self::Item) -> K,
+        K: Ord,
+    {
+        sorted_by_key(self, key)
+    }
+
+    fn coalesce<F>(self, f: F) -> Coalesce<Self, F>
+    where
+        F: FnMut(
+            Self::Item,
+            Self::Item,
+        ) -> Result<Self::Item, (Self::Item, Self::Item)>,
+    {
+        coalesce(self, f)
+    }
+
+    fn try_map<F, T, U, E>(self, f: F) -> TryMap<Self, F>
+    where
+        Self: Iterator<Item = Result<T, E>>,
+        F: FnMut(T) -> Result<U, E>,
+    {
+        try_map(self, f)
+    }
+
+    fn try_filter<F, T, E>(self, predicate: F) -> TryFilter<Self, F>
+    where
+        Self: Iterator<Item = Result<T, E>>,
+        F: FnMut(&T) -> bool,
+    {
+        try_filter(self, predicate)
+    }
+
+    fn paginate(self, page_size: usize) -> Paginate<Self> {
+        paginate(self, page_size)
+    }
+
+    fn progress_every<F>(self, n: usize, callback: F) -> ProgressEvery<Self, F>
+    where
+        F: FnMut(usize),
+    {
+        progress_every(self, n, callback)
+    }
+
+    fn par_map<T, U, F>(self, threads: usize, f: F) -> ParMap<Self, U>
+    where
+        Self: Iterator<Item = T>,
+        T: Send + 'static,
+        U: Send + 'static,
+        F: Fn(T) -> U + Send + Sync + 'static,
+    {
+        par_map(self, threads, f)
+    }
+
+    fn tee(self) -> (Tee<Self>, Tee<Self>)
+    where
+        Self::Item: Clone,
+    {
+        tee(self)
+    }
+
+    fn cycle_n(self, times: usize) -> CycleN<Self>
+    where
+        Self: Clone,
+    {
+        cycle_n(self, times)
+    }
+
+    fn flatten_options<T>(self) -> FlattenOptions<Self>
+    where
+        Self: Iterator<Item = Option<T>>,
+    {
+        flatten_options(self)
+    }
+
+    fn all_some<T>(self) -> Option<Vec<T>>
+    where
+        Self: Iterator<Item = Option<T>>,
+    {
+        all_some(self)
+    }
+
+    fn rewind(self) -> Rewind<Self>
+    where
+        Self::Item: Clone,
+    {
+        rewind(self)
+    }
+
+    fn zip3<B, C>(self, b: B, c: C) -> Zip3<Self, B::IntoIter, C::IntoIter>
+    where
+        B: IntoIterator,
+        C: IntoIterator,
+    {
+        zip3(self, b, c)
+    }
+
+    fn unzip3<A, B, C>(self) -> (Vec<A>, Vec<B>, Vec<C>)
+    where
+        Self: Iterator<Item = (A, B, C)>,
+    {
+        unzip3(self)
+    }
+}
+
+impl<I: Iterator> MyIteratorExt for I {}
+
+#[test]
+fn test_my_iterator_ext() {
+    let v = vec![1.0, 3.0, 5.0, 7.0];
+    let totals: Vec<f64> =
+        v.into_iter().moving_average(2).running_sum().collect();
+    assert_eq!(totals, vec![2.0, 6.0, 12.0]);
+
+    let words = "b a c a b".split(' ');
+    let distinct: Vec<&str> = words
+        .sorted_by_key(|w| *w)
+        .coalesce(|a, b| if a == b { Ok(a) } else { Err((a, b)) })
+        .collect();
+    assert_eq!(distinct, vec!["a", "b", "c"]);
+
+    let smoothed =
+        (0..4).map(f64::from).ewma(1.0).running_fold(0.0, |a, x| a + x);
+    assert_eq!(smoothed.last(), Some(6.0));
+}
@@ -0,0 +1,70 @@
+/*
+    Counting allocations (tests only)
+
+    A #[global_allocator] replaces the allocator for the whole program.
+    This one forwards everything to the system allocator, but first bumps
+    a counter, so tests can measure how many allocations some code makes:
+
+        let (result, allocs) = count_allocations(|| v.clone());
+
+    Tests run in parallel on several threads, all sharing this allocator,
+    so the counter is thread-local: each test only sees its own
+    allocations. It's const-initialized, so reading it never allocates
+    (which would recurse back into alloc).
+*/
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // try_with: the thread-local may already be gone while a thread
+        // is shutting down
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    // Growing counts too: it may have to move to a new allocation
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// Runs f, returning its result and the number of allocations it made on
+// this thread
+pub fn count_allocations<R, F: FnOnce() -> R>(f: F) -> (R, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    let after = ALLOCATIONS.with(Cell::get);
+    (result, after - before)
+}
+
+#[test]
+fn test_count_allocations() {
+    let (v, n) = count_allocations(|| vec![1, 2, 3]);
+    assert_eq!(n, 1);
+    let (_, n) = count_allocations(|| v.iter().sum::<i32>());
+    assert_eq!(n, 0);
+    let (s, n) = count_allocations(|| format!("{}-{}", "a", 1));
+    assert!(n >= 1);
+    drop(s);
+}
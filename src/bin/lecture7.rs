@@ -0,0 +1,307 @@
+/*
+    Running the lecture examples
+
+    The lecture's example functions -- example_for, closure_examples_move
+    and friends -- show what they do by printing. Before this binary the
+    only way to see that output was to call one from a test and read the
+    test's captured output. Now they run by name:
+
+        cargo run -- list
+        cargo run -- run example_for
+        cargo run -- run closures          (every example in a topic)
+
+    Capturing output
+
+    The examples print with println!, straight to stdout, and std has no
+    stable way to point stdout somewhere else for the length of one call.
+    So each example runs in a child process: a second copy of this same
+    program, started with a hidden `__example <name>` command that runs
+    just that example. The parent collects the child's stdout through a
+    pipe and prints it under a header. Two things come for free: an
+    example that panics only takes down its own process, and its output
+    is never interleaved with the next one's.
+
+    The argument parser is hand-written: three commands don't need a
+    crate.
+*/
+
+extern crate lecture7;
+
+use std::env;
+use std::io;
+use std::process;
+
+use lecture7::closures::{
+    add_1_to_each_using_do_for_each, closure_examples, closure_examples_move,
+    closure_with_arguments_example, example_do_all_fixed, get_last_3,
+    return_print_and_clear, use_fn, zero_rest_each_using_do_for_each,
+};
+use lecture7::consumers::display_table;
+use lecture7::generators::{fibonacci, primes};
+use lecture7::iterators::{
+    copy_increasing, example_for, example_into_iter, example_iter,
+    example_iter_explicit1, sum_squares_lt,
+};
+use lecture7::prelude::*;
+
+struct Example {
+    topic: &'static str,
+    name: &'static str,
+    run: fn(),
+}
+
+// Examples that take arguments get the ones the lecture uses
+const EXAMPLES: &[Example] = &[
+    Example {
+        topic: "closures",
+        name: "closure_examples",
+        run: || {
+            closure_examples(Some(String::from("a value")));
+            closure_examples(None);
+        },
+    },
+    Example {
+        topic: "closures",
+        name: "closure_examples_move",
+        run: || closure_examples_move(None),
+    },
+    Example {
+        topic: "closures",
+        name: "closure_with_arguments_example",
+        run: closure_with_arguments_example,
+    },
+    Example { topic: "closures", name: "get_last_3", run: get_last_3_example },
+    Example {
+        topic: "closures",
+        name: "do_for_each",
+        run: do_for_each_example,
+    },
+    Example { topic: "closures", name: "use_fn", run: use_fn_example },
+    Example {
+        topic: "closures",
+        name: "example_do_all_fixed",
+        run: || {
+            example_do_all_fixed(vec![
+                Box::new(|| format!("2 + 2 = {}", 2 + 2)),
+                Box::new(|| format!("Hello, {}", "Caleb")),
+            ])
+        },
+    },
+    Example {
+        topic: "closures",
+        name: "return_print_and_clear",
+        run: || return_print_and_clear(vec![1, 2, 3])(),
+    },
+    Example { topic: "iterators", name: "example_for", run: example_for },
+    Example { topic: "iterators", name: "example_iter", run: example_iter },
+    Example {
+        topic: "iterators",
+        name: "example_iter_explicit1",
+        run: example_iter_explicit1,
+    },
+    Example {
+        topic: "iterators",
+        name: "example_into_iter",
+        run: example_into_iter,
+    },
+    Example {
+        topic: "iterators",
+        name: "copy_increasing",
+        run: || println!("{:?}", copy_increasing(vec![1, 2, 3])),
+    },
+    Example {
+        topic: "iterators",
+        name: "sum_squares_lt",
+        run: || println!("{}", sum_squares_lt(10)),
+    },
+    Example {
+        topic: "iterators",
+        name: "fibonacci",
+        run: || println!("{:?}", fibonacci().take(15).collect::<Vec<_>>()),
+    },
+    Example {
+        topic: "iterators",
+        name: "primes",
+        run: || println!("{:?}", primes().take(10).collect::<Vec<_>>()),
+    },
+    Example { topic: "songs", name: "play_songs", run: play_songs_example },
+];
+
+fn get_last_3_example() {
+    println!("{}", get_last_3(&[1, 2, 3]));
+    // Prints a warning and falls back to 0
+    println!("{}", get_last_3(&[]));
+}
+
+fn do_for_each_example() {
+    let mut v = vec![1, 2, 3];
+    add_1_to_each_using_do_for_each(&mut v);
+    println!("{:?}", v);
+    zero_rest_each_using_do_for_each(&mut v);
+    println!("{:?}", v);
+}
+
+// Quiz 1, as in closures::test_use_fn
+fn use_fn_example() {
+    let closure = {
+        let two = "2";
+        let three = "3";
+        println!("Hello {}!", two);
+        move || println!("Hello {}!", three)
+    };
+    use_fn(closure);
+}
+
+fn play_songs_example() {
+    let mut profile = SongUserProfile::new("alice");
+    for name in &["Help!", "Yesterday"] {
+        profile.like(name.parse().unwrap());
+    }
+    for s in profile.play_songs() {
+        println!("Now playing: {}", s);
+    }
+    for s in profile.get_iter() {
+        println!("Again: {}", s);
+    }
+    println!("{}", profile);
+}
+
+/*
+    Command line
+*/
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    List,
+    // An example name, or a topic to run all of its examples
+    Run(String),
+    Help,
+    // Internal: run one example in this process (see "Capturing output")
+    Example(String),
+}
+
+fn parse_args<I: IntoIterator<Item = String>>(
+    args: I,
+) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let command = match args.next() {
+        None => return Ok(Command::Help),
+        Some(command) => command,
+    };
+    let command = match command.as_str() {
+        "list" => Command::List,
+        "help" | "-h" | "--help" => Command::Help,
+        "run" | "__example" => match args.next() {
+            Some(name) if command == "run" => Command::Run(name),
+            Some(name) => Command::Example(name),
+            None => return Err(format!("{}: which example?", command)),
+        },
+        other => return Err(format!("unknown command {:?}", other)),
+    };
+    match args.next() {
+        Some(extra) => Err(format!("unexpected argument {:?}", extra)),
+        None => Ok(command),
+    }
+}
+
+fn usage() -> String {
+    String::from(
+        "CIS198 Lecture 7: Closures and Iterators\n\n\
+         usage: lecture7 <command>\n\n\
+         commands:\n  \
+         list          list the examples\n  \
+         run <name>    run one example, or every example in a topic\n  \
+         help          show this message",
+    )
+}
+
+fn list() -> String {
+    display_table(EXAMPLES.iter().map(|e| vec![e.topic, e.name]))
+}
+
+fn find(name: &str) -> Vec<&'static Example> {
+    EXAMPLES.iter().filter(|e| e.name == name || e.topic == name).collect()
+}
+
+// Runs the example in a copy of this program and collects what it printed
+fn capture(example: &Example) -> io::Result<process::Output> {
+    process::Command::new(env::current_exe()?)
+        .args(["__example", example.name])
+        .output()
+}
+
+fn run(name: &str) {
+    let examples = find(name);
+    if examples.is_empty() {
+        eprintln!("error: no example or topic named {:?}", name);
+        eprintln!("try `lecture7 list`");
+        process::exit(1);
+    }
+    for example in examples {
+        println!("== {} ==", example.name);
+        let output = match capture(example) {
+            Ok(output) => output,
+            Err(e) => {
+                eprintln!("error: couldn't start {}: {}", example.name, e);
+                process::exit(1);
+            }
+        };
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+        if !output.status.success() {
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            eprintln!("error: {} failed ({})", example.name, output.status);
+            process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let command = match parse_args(env::args().skip(1)) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, usage());
+            process::exit(2);
+        }
+    };
+    match command {
+        Command::Help => println!("{}", usage()),
+        Command::List => println!("{}", list()),
+        Command::Run(name) => run(&name),
+        // We're the child: print straight into the parent's pipe
+        Command::Example(name) => {
+            for example in find(&name) {
+                (example.run)();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |s: &str| parse_args(s.split_whitespace().map(String::from));
+    assert_eq!(args(""), Ok(Command::Help));
+    assert_eq!(args("list"), Ok(Command::List));
+    assert_eq!(args("--help"), Ok(Command::Help));
+    assert_eq!(args("run primes"), Ok(Command::Run(String::from("primes"))));
+    assert_eq!(
+        args("__example primes"),
+        Ok(Command::Example(String::from("primes")))
+    );
+    assert_eq!(args("run"), Err(String::from("run: which example?")));
+    assert!(args("run primes twice").is_err());
+    assert!(args("jump").is_err());
+}
+
+#[test]
+fn test_examples_registry() {
+    for example in EXAMPLES {
+        // Names are unique, and never clash with a topic
+        assert_eq!(find(example.name).len(), 1, "{}", example.name);
+        // In-process here: the test harness captures println! itself
+        (example.run)();
+    }
+    assert_eq!(list().lines().count(), EXAMPLES.len());
+    assert_eq!(find("songs").len(), 1);
+    assert!(find("closures").len() > 1);
+    assert!(find("nothing").is_empty());
+}
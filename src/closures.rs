@@ -2,8 +2,11 @@
     Closures in Rust
 */
 
+use std::cmp::Ordering;
 use std::fmt::Debug;
 
+use utility_traits::NonEmptyVec;
+
 /*
     QUIZ
 
@@ -47,6 +50,20 @@ pub fn get_last_3(v: &[usize]) -> usize {
     })
 }
 
+// If the vector can't be empty, there's nothing to fall back on at all
+// (see utility_traits::NonEmptyVec)
+pub fn get_last_non_empty(v: &NonEmptyVec<usize>) -> usize {
+    *v.last()
+}
+
+#[test]
+fn test_get_last_non_empty() {
+    let v = NonEmptyVec::new(1, vec![2, 3]);
+    assert_eq!(get_last_non_empty(&v), 3);
+    // The same slice still works with the Option-based versions
+    assert_eq!(get_last_3(&v), 3);
+}
+
 /*
     What are closures?
 
@@ -103,6 +120,7 @@ pub fn closure_examples_move(input: Option<String>) {
     println!("{}", input.unwrap_or_else(c2));
 }
 
+#[allow(clippy::unnecessary_literal_unwrap)]
 pub fn closure_with_arguments_example() {
     // Closure with two arguments
     let add_2_nums = |num1, num2| num1 + num2;
@@ -149,12 +167,14 @@ pub fn closure_with_arguments_example() {
     with essentially the same form or pattern. Examples:
 */
 
+#[allow(clippy::ptr_arg)]
 pub fn add_1_to_each(v: &mut Vec<usize>) {
     for x in v.iter_mut() {
         *x += 1;
     }
 }
 
+#[allow(clippy::ptr_arg)]
 pub fn zero_rest_each(v: &mut Vec<usize>) {
     for x in v.iter_mut() {
         println!("Resetting: {}", x);
@@ -167,6 +187,7 @@ pub fn zero_rest_each(v: &mut Vec<usize>) {
     How can we unify these examples?
 */
 
+#[allow(clippy::ptr_arg)]
 pub fn do_for_each<F>(v: &mut Vec<usize>, f: F)
 where
     F: Fn(&mut usize),
@@ -479,3 +500,108 @@ pub fn return_print_and_clear(mut v: Vec<usize>) -> impl FnOnce() {
 // - I know this is a type that implements Trait, but I don't know
 //   (or don't want to specify) exactly what type it is.
 //   So, please figure it out for me.
+
+/*
+    Putting it together: a reusable Comparator
+
+    slice.sort_by takes a closure, but that closure's type is unnamed --
+    you can't store it in a struct field, put several in a Vec, or return
+    different ones from the two arms of an if. Boxing it as a trait object
+    fixes all three: every Comparator<T> is the same type, whatever
+    closure is inside.
+
+    The combinators consume a comparator and return a new one whose
+    closure *owns* the old one (move ||), so they chain:
+
+        Comparator::by_key(|s: &Song| s.plays).reverse().then(by_title)
+
+    means "most played first, ties broken by title". Each step wraps the
+    previous closure in another box; calling it calls down the chain.
+*/
+
+type CompareFn<T> = Box<dyn Fn(&T, &T) -> Ordering>;
+
+pub struct Comparator<T> {
+    cmp: CompareFn<T>,
+}
+
+impl<T: 'static> Comparator<T> {
+    pub fn new<F>(cmp: F) -> Self
+    where
+        F: Fn(&T, &T) -> Ordering + 'static,
+    {
+        Comparator { cmp: Box::new(cmp) }
+    }
+
+    // T's own ordering
+    pub fn natural() -> Self
+    where
+        T: Ord,
+    {
+        Comparator::new(|a: &T, b: &T| a.cmp(b))
+    }
+
+    // Compare by something computed from each item
+    pub fn by_key<K, F>(key: F) -> Self
+    where
+        K: Ord,
+        F: Fn(&T) -> K + 'static,
+    {
+        Comparator::new(move |a, b| key(a).cmp(&key(b)))
+    }
+
+    pub fn compare(&self, a: &T, b: &T) -> Ordering {
+        (self.cmp)(a, b)
+    }
+
+    pub fn reverse(self) -> Self {
+        Comparator::new(move |a, b| self.compare(b, a))
+    }
+
+    // Use `other` only to break ties
+    pub fn then(self, other: Comparator<T>) -> Self {
+        Comparator::new(move |a, b| {
+            self.compare(a, b).then_with(|| other.compare(a, b))
+        })
+    }
+}
+
+// Stable: items that compare equal keep their order
+pub fn sort_with<T: 'static>(items: &mut [T], cmp: &Comparator<T>) {
+    items.sort_by(|a, b| cmp.compare(a, b));
+}
+
+#[test]
+fn test_comparator_combinators() {
+    let mut words = vec!["pear", "fig", "apple", "kiwi", "date"];
+    sort_with(&mut words, &Comparator::natural());
+    assert_eq!(words, vec!["apple", "date", "fig", "kiwi", "pear"]);
+
+    // Longest first, then alphabetical
+    let cmp = Comparator::by_key(|w: &&str| w.len())
+        .reverse()
+        .then(Comparator::natural());
+    sort_with(&mut words, &cmp);
+    assert_eq!(words, vec!["apple", "date", "kiwi", "pear", "fig"]);
+    assert_eq!(cmp.compare(&"fig", &"figs"), Ordering::Greater);
+}
+
+#[test]
+fn test_comparator_chosen_at_runtime() {
+    // (title, plays)
+    let mut songs = vec![("Help!", 12), ("Girl", 40), ("Yesterday", 40)];
+    let pick = |by_plays: bool| -> Comparator<(&str, u32)> {
+        if by_plays {
+            Comparator::by_key(|s: &(&str, u32)| s.1).reverse()
+        } else {
+            Comparator::by_key(|s: &(&str, u32)| s.0)
+        }
+    };
+    // Stable: Girl stays ahead of Yesterday
+    sort_with(&mut songs, &pick(true));
+    assert_eq!(songs[0].0, "Girl");
+    assert_eq!(songs[2].0, "Help!");
+    sort_with(&mut songs, &pick(false));
+    let titles: Vec<&str> = songs.iter().map(|s| s.0).collect();
+    assert_eq!(titles, vec!["Girl", "Help!", "Yesterday"]);
+}
@@ -0,0 +1,398 @@
+/*
+    Configuration: Into<String> and AsRef<Path> in a real API
+
+    The lecture's waitpid example makes the point that a constructor
+    should take what the caller already has. Config does the same:
+
+        Config::new("lecture7")                 // &'static str
+        Config::new(format!("user-{}", id))     // String, moved in
+        config.set_data_dir("/tmp/songs")       // &str
+        config.set_data_dir(home.join(".songs")) // PathBuf
+
+    A function that stores a string takes S: Into<String>. If the caller
+    passes a String, into() is a no-op move; if it passes a &str, the
+    copy happens inside -- exactly once, and only because we need to own
+    it anyway. A function that stores a path takes P: AsRef<Path> and
+    copies it with to_path_buf().
+
+    Settings are plain key = value strings. merge() layers one config on
+    top of another (defaults, then a file, then command-line flags...),
+    with the later layer winning for every setting it has.
+*/
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use error::{ErrContext, LectureError};
+use parsing::parse_kv;
+use utility_traits::read_to_string_from;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    name: String,
+    // None means "the current directory"
+    data_dir: Option<PathBuf>,
+    values: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new("lecture7")
+    }
+}
+
+impl Config {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Config { name: name.into(), data_dir: None, values: BTreeMap::new() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        self.data_dir.as_ref().map_or(Path::new("."), |p| p.as_path())
+    }
+
+    pub fn set_data_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.data_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    // Returns the previous value, like HashMap::insert
+    pub fn set<K, V>(&mut self, key: K, value: V) -> Option<String>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.values.insert(key.into(), value.into())
+    }
+
+    // Typed lookup: get_as::<u32>("volume"), get_as::<Duration>("timeout")
+    pub fn get_as<T>(&self, key: &str) -> Result<T, ConfigError>
+    where
+        T: TryFrom<ConfigValue, Error = InvalidValue>,
+    {
+        let raw = self
+            .values
+            .get(key)
+            .ok_or_else(|| ConfigError::Missing(key.to_string()))?;
+        T::try_from(ConfigValue(raw.clone())).map_err(|e| {
+            ConfigError::Invalid { key: key.to_string(), source: e }
+        })
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.values.remove(key)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(|k| k.as_str())
+    }
+
+    // Settings from a key = value file (see parsing::parse_kv), on top
+    // of the defaults. The first bad line stops the load.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, LectureError> {
+        let path = path.as_ref();
+        let text = read_to_string_from(path)
+            .with_context(|| format!("reading {}", path.display()))?;
+        let mut config = Config::default();
+        for pair in parse_kv(&text) {
+            let (key, value) =
+                pair.with_context(|| path.display().to_string())?;
+            config.set(key, value);
+        }
+        Ok(config)
+    }
+
+    // Every setting in `overrides` replaces ours; the data directory too,
+    // if the override sets one. The name stays -- it identifies this
+    // config, not one of its layers.
+    pub fn merge(&mut self, overrides: Config) -> &mut Self {
+        if overrides.data_dir.is_some() {
+            self.data_dir = overrides.data_dir;
+        }
+        self.values.extend(overrides.values);
+        self
+    }
+}
+
+#[test]
+fn test_config_into_and_as_ref() {
+    let id = 7;
+    let mut config = Config::new(format!("user-{}", id));
+    assert_eq!(config.name(), "user-7");
+    assert_eq!(config.data_dir(), Path::new("."));
+
+    // &str and String both work as keys and values
+    let key = String::from("theme");
+    config.set(key, "dark");
+    assert_eq!(config.set("volume", String::from("11")), None);
+    assert_eq!(config.set("volume", "7"), Some(String::from("11")));
+    assert_eq!(config.get("theme"), Some("dark"));
+    assert_eq!(config.get("volume"), Some("7"));
+    assert_eq!(config.get("missing"), None);
+
+    // &str, &Path and PathBuf all work as directories
+    config.set_data_dir("/tmp/songs");
+    assert_eq!(config.data_dir(), Path::new("/tmp/songs"));
+    let home = PathBuf::from("/home/alice");
+    config.set_data_dir(home.join(".songs")).set_name("alice");
+    assert_eq!(config.data_dir(), Path::new("/home/alice/.songs"));
+    assert_eq!(config.name(), "alice");
+
+    assert_eq!(config.remove("theme"), Some(String::from("dark")));
+    assert_eq!(config.keys().collect::<Vec<_>>(), vec!["volume"]);
+}
+
+#[test]
+fn test_config_load() {
+    use error::report;
+    use parsing::{ParseError, ParseErrorKind};
+    use utility_traits::{temp_path, TempFile};
+
+    let file = TempFile::new(
+        temp_path("config_load.conf"),
+        "# defaults\ntheme = dark\nvolume = 11\n",
+    )
+    .unwrap();
+    let config = Config::load(file.path()).unwrap();
+    assert_eq!(config.get("theme"), Some("dark"));
+    assert_eq!(config.get_as::<u32>("volume"), Ok(11));
+
+    let bad =
+        TempFile::new(temp_path("config_bad.conf"), "theme = dark\noops\n")
+            .unwrap();
+    let err = Config::load(bad.path()).unwrap_err();
+    assert_eq!(
+        report(&err),
+        format!(
+            "{}: invalid value: line 2: expected key = value",
+            bad.path().display()
+        )
+    );
+    let source = err.without_context().source().unwrap();
+    assert_eq!(
+        source.downcast_ref::<ParseError>(),
+        Some(&ParseError { line: 2, kind: ParseErrorKind::MissingEquals })
+    );
+
+    let missing = Config::load(temp_path("config_missing.conf")).unwrap_err();
+    assert!(matches!(missing.without_context(), LectureError::Io(_)));
+}
+
+#[test]
+fn test_config_merge() {
+    let mut config = Config::default();
+    assert_eq!(config.name(), "lecture7");
+    config.set("theme", "light");
+    config.set("volume", "5");
+    config.set_data_dir("/srv/songs");
+
+    let mut flags = Config::new("command line");
+    flags.set("volume", "9");
+    flags.set("shuffle", "true");
+
+    config.merge(flags);
+    assert_eq!(config.name(), "lecture7");
+    assert_eq!(config.get("theme"), Some("light"));
+    assert_eq!(config.get("volume"), Some("9"));
+    assert_eq!(config.get("shuffle"), Some("true"));
+    // The override didn't set a directory, so ours is kept
+    assert_eq!(config.data_dir(), Path::new("/srv/songs"));
+
+    let mut elsewhere = Config::default();
+    elsewhere.set_data_dir("/mnt/music");
+    config.merge(elsewhere);
+    assert_eq!(config.data_dir(), Path::new("/mnt/music"));
+}
+
+/*
+    Typed values with TryFrom
+
+    Every setting is stored as text, but callers want a bool, a number,
+    a Duration... Instead of a parse_bool / parse_u32 / parse_duration
+    function per type, each target type says how to get itself out of a
+    ConfigValue by implementing TryFrom<ConfigValue>. get_as then only
+    needs that one bound, and supporting a new type is one more impl --
+    get_as doesn't change.
+
+    All the impls share one error type, InvalidValue, which remembers
+    the text and what we expected it to be. get_as adds the key, so the
+    final message says exactly which setting is wrong and why:
+
+        setting `timeout`: "soon" is not a duration (like 30s or 250ms)
+*/
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigValue(pub String);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidValue {
+    pub value: String,
+    pub expected: &'static str,
+}
+
+impl InvalidValue {
+    fn new(value: ConfigValue, expected: &'static str) -> Self {
+        InvalidValue { value: value.0, expected }
+    }
+}
+
+impl fmt::Display for InvalidValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} is not {}", self.value, self.expected)
+    }
+}
+
+impl Error for InvalidValue {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    Missing(String),
+    Invalid { key: String, source: InvalidValue },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Missing(key) => {
+                write!(f, "setting `{}` is missing", key)
+            }
+            ConfigError::Invalid { key, source } => {
+                write!(f, "setting `{}`: {}", key, source)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Missing(_) => None,
+            ConfigError::Invalid { source, .. } => Some(source),
+        }
+    }
+}
+
+impl TryFrom<ConfigValue> for bool {
+    type Error = InvalidValue;
+
+    fn try_from(value: ConfigValue) -> Result<Self, Self::Error> {
+        match value.0.trim().to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(true),
+            "false" | "no" | "off" | "0" => Ok(false),
+            _ => Err(InvalidValue::new(value, "a boolean (true/false)")),
+        }
+    }
+}
+
+impl TryFrom<ConfigValue> for u32 {
+    type Error = InvalidValue;
+
+    fn try_from(value: ConfigValue) -> Result<Self, Self::Error> {
+        value
+            .0
+            .trim()
+            .parse()
+            .map_err(|_| InvalidValue::new(value, "a whole number"))
+    }
+}
+
+// "250ms", "30s", "5m", "2h"; a bare number means seconds
+impl TryFrom<ConfigValue> for Duration {
+    type Error = InvalidValue;
+
+    fn try_from(value: ConfigValue) -> Result<Self, Self::Error> {
+        let text = value.0.trim();
+        let split =
+            text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+        let (digits, unit) = text.split_at(split);
+        let parsed =
+            digits.parse::<u64>().ok().and_then(|n| match unit.trim() {
+                "ms" => Some(Duration::from_millis(n)),
+                "" | "s" => Some(Duration::from_secs(n)),
+                "m" => n.checked_mul(60).map(Duration::from_secs),
+                "h" => n.checked_mul(60 * 60).map(Duration::from_secs),
+                _ => None,
+            });
+        parsed.ok_or_else(|| {
+            InvalidValue::new(value, "a duration (like 30s or 250ms)")
+        })
+    }
+}
+
+impl TryFrom<ConfigValue> for PathBuf {
+    type Error = InvalidValue;
+
+    fn try_from(value: ConfigValue) -> Result<Self, Self::Error> {
+        if value.0.trim().is_empty() {
+            return Err(InvalidValue::new(value, "a path"));
+        }
+        // The String's buffer becomes the PathBuf's -- no copy
+        Ok(PathBuf::from(value.0))
+    }
+}
+
+#[test]
+fn test_config_get_as() {
+    let mut config = Config::default();
+    config.set("shuffle", "Yes");
+    config.set("volume", " 11 ");
+    config.set("timeout", "250ms");
+    config.set("fade", "2m");
+    config.set("library", "/srv/music");
+
+    assert_eq!(config.get_as::<bool>("shuffle"), Ok(true));
+    assert_eq!(config.get_as::<u32>("volume"), Ok(11));
+    assert_eq!(
+        config.get_as::<Duration>("timeout"),
+        Ok(Duration::from_millis(250))
+    );
+    assert_eq!(config.get_as("fade"), Ok(Duration::from_secs(120)));
+    let library: PathBuf = config.get_as("library").unwrap();
+    assert_eq!(library, Path::new("/srv/music"));
+}
+
+#[test]
+fn test_config_get_as_errors() {
+    let mut config = Config::default();
+    config.set("volume", "loud");
+    config.set("timeout", "soon");
+    config.set("shuffle", "maybe");
+    config.set("library", "  ");
+
+    let err = config.get_as::<u32>("volume").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "setting `volume`: \"loud\" is not a whole number"
+    );
+    assert_eq!(
+        config.get_as::<Duration>("timeout").unwrap_err().to_string(),
+        "setting `timeout`: \"soon\" is not a duration (like 30s or 250ms)"
+    );
+    assert!(config.get_as::<bool>("shuffle").is_err());
+    assert!(config.get_as::<PathBuf>("library").is_err());
+    // Same text, different types: "loud" is a fine path
+    assert!(config.get_as::<PathBuf>("volume").is_ok());
+
+    assert_eq!(
+        config.get_as::<bool>("missing"),
+        Err(ConfigError::Missing(String::from("missing")))
+    );
+    let source = err.source().unwrap().downcast_ref::<InvalidValue>().unwrap();
+    assert_eq!(source.value, "loud");
+}
@@ -0,0 +1,821 @@
+/*
+    Consuming iterators
+
+    Adapters (see adapters.rs) turn an iterator into another iterator.
+    Consumers are the other end of the pipeline: they pull every item
+    out of an iterator and produce a final answer. .collect(), .sum(),
+    .count(), .fold() are all consumers from the standard library.
+
+    We can write our own, and the answer doesn't have to be a Vec.
+*/
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt::{Display, Write};
+use std::hash::Hash;
+use std::iter::FromIterator;
+use std::thread;
+
+use rng::Rng;
+
+/*
+    One-pass statistics
+
+    Stats summarizes a stream of f64s in a single pass, without storing
+    the items: count, mean, variance, min and max.
+
+    The variance uses Welford's algorithm, which updates the mean and the
+    sum of squared differences incrementally. The textbook formula
+    E[x^2] - E[x]^2 loses a lot of precision when the values are large.
+
+    Stats implements FromIterator (so .collect() works) and Extend (so
+    more data can be added later).
+*/
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    count: usize,
+    mean: f64,
+    // Sum of squared differences from the current mean
+    m2: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+        self.min = Some(self.min.map_or(x, |m| m.min(x)));
+        self.max = Some(self.max.map_or(x, |m| m.max(x)));
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.mean)
+        }
+    }
+
+    // Population variance (divides by n)
+    pub fn variance(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.m2 / self.count as f64)
+        }
+    }
+
+    // Sample variance (divides by n - 1), needs at least two items
+    pub fn sample_variance(&self) -> Option<f64> {
+        if self.count < 2 {
+            None
+        } else {
+            Some(self.m2 / (self.count - 1) as f64)
+        }
+    }
+
+    pub fn std_dev(&self) -> Option<f64> {
+        self.variance().map(f64::sqrt)
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        self.max
+    }
+}
+
+impl Extend<f64> for Stats {
+    fn extend<I: IntoIterator<Item = f64>>(&mut self, iter: I) {
+        for x in iter {
+            self.push(x);
+        }
+    }
+}
+
+impl FromIterator<f64> for Stats {
+    fn from_iter<I: IntoIterator<Item = f64>>(iter: I) -> Self {
+        let mut stats = Stats::new();
+        stats.extend(iter);
+        stats
+    }
+}
+
+#[test]
+fn test_stats() {
+    let stats: Stats =
+        vec![2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0].into_iter().collect();
+    assert_eq!(stats.count(), 8);
+    assert_eq!(stats.mean(), Some(5.0));
+    assert_eq!(stats.variance(), Some(4.0));
+    assert_eq!(stats.std_dev(), Some(2.0));
+    assert_eq!(stats.min(), Some(2.0));
+    assert_eq!(stats.max(), Some(9.0));
+
+    let empty = Stats::new();
+    assert_eq!(empty.count(), 0);
+    assert_eq!(empty.mean(), None);
+    assert_eq!(empty.variance(), None);
+    assert_eq!(empty.min(), None);
+}
+
+#[test]
+fn test_stats_extend() {
+    // Adding data in two batches gives the same answer as all at once
+    let mut stats = Stats::from_iter(vec![1.0, 2.0]);
+    assert_eq!(stats.sample_variance(), Some(0.5));
+    stats.extend(vec![3.0, 4.0]);
+    let all = Stats::from_iter(vec![1.0, 2.0, 3.0, 4.0]);
+    assert_eq!(stats, all);
+    assert_eq!(stats.mean(), Some(2.5));
+
+    // Large offsets don't destroy precision (Welford)
+    let shifted = Stats::from_iter(vec![1e9 + 4.0, 1e9 + 7.0, 1e9 + 13.0]);
+    assert!((shifted.sample_variance().unwrap() - 21.0).abs() < 1e-6);
+}
+
+/*
+    min_max
+
+    Calling .min() and then .max() needs two passes, which means cloning
+    the iterator (or collecting it first). Not every iterator can be
+    cloned, e.g. one that reads lines from a file.
+
+    Finding both in one pass naively costs 2 comparisons per item. Taking
+    items in pairs brings it down to 3 comparisons per 2 items: compare the
+    pair with each other first, then only the smaller one against the
+    current min and the larger one against the current max.
+
+    Like std's .min() and .max(), ties go to the first minimum and the
+    last maximum.
+
+    The second item of a pair is only asked for once the first one
+    exists: an iterator that isn't fused may yield again after a None,
+    and we stop at the first None.
+*/
+
+pub fn min_max<I, T>(iter: I) -> Option<(T, T)>
+where
+    I: IntoIterator<Item = T>,
+    T: PartialOrd + Clone,
+{
+    let mut iter = iter.into_iter();
+    // A single item is both the min and the max, which is the one place we
+    // need Clone
+    let first = iter.next()?;
+    let (mut min, mut max) = (first.clone(), first);
+
+    while let Some(a) = iter.next() {
+        let (small, large) = match iter.next() {
+            Some(b) if b < a => (b, a),
+            Some(b) => (a, b),
+            // Odd one out at the end: compare against both
+            None => {
+                if a < min {
+                    min = a;
+                } else if a >= max {
+                    max = a;
+                }
+                break;
+            }
+        };
+        if small < min {
+            min = small;
+        }
+        if large >= max {
+            max = large;
+        }
+    }
+    Some((min, max))
+}
+
+#[test]
+fn test_min_max() {
+    use std::iter;
+
+    assert_eq!(min_max(vec![3, 1, 4, 1, 5, 9, 2, 6]), Some((1, 9)));
+    assert_eq!(min_max(vec![3, 1, 4, 1, 5, 9, 2]), Some((1, 9)));
+    assert_eq!(min_max(vec![7]), Some((7, 7)));
+    assert_eq!(min_max(Vec::<i32>::new()), None);
+    assert_eq!(min_max(vec![2.5, -1.0, 0.0]), Some((-1.0, 2.5)));
+
+    // Works on a one-shot iterator
+    let words = "the quick brown fox".split_whitespace();
+    assert_eq!(min_max(words), Some(("brown", "the")));
+
+    // Stops at the first None, even if the iterator would carry on
+    let mut calls = 0;
+    let unfused = iter::from_fn(|| {
+        calls += 1;
+        match calls {
+            2 | 5 => None,
+            n => Some(n),
+        }
+    });
+    assert_eq!(min_max(unfused), Some((1, 1)));
+    assert_eq!(calls, 2);
+    let mut calls = 0;
+    let unfused = iter::from_fn(|| {
+        calls += 1;
+        match calls {
+            4 => None,
+            n => Some(10 - n),
+        }
+    });
+    assert_eq!(min_max(unfused), Some((7, 9)));
+    assert_eq!(calls, 4);
+}
+
+#[test]
+fn test_min_max_ties() {
+    // Same tie-breaking as std: first min, last max
+    let v = [Keyed(1, 'a'), Keyed(0, 'b'), Keyed(1, 'c'), Keyed(0, 'd')];
+    let (min, max) = min_max(v.iter().cloned()).unwrap();
+    assert_eq!((min.1, max.1), ('b', 'c'));
+    let odd = [Keyed(1, 'a'), Keyed(0, 'b'), Keyed(1, 'c')];
+    let (min, max) = min_max(odd.iter().cloned()).unwrap();
+    assert_eq!((min.1, max.1), ('b', 'c'));
+}
+
+/*
+    top_k
+
+    The k largest items, largest first.
+
+    The obvious solution is to collect everything into a Vec, sort it,
+    and take k. That stores the whole stream: O(n) memory and
+    O(n log n) time.
+
+    Instead we keep a min-heap of the best k items seen so far. Each new
+    item only needs to beat the smallest of those (the top of the heap).
+    That's O(k) memory and O(n log k) time, and it works on streams that
+    are too large to collect.
+
+    BinaryHeap is a max-heap, so we wrap items in Reverse to get a min-heap.
+*/
+
+pub fn top_k<I, T>(iter: I, k: usize) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+    T: Ord,
+{
+    if k == 0 {
+        return Vec::new();
+    }
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    for item in iter {
+        if heap.len() < k {
+            heap.push(Reverse(item));
+        } else if let Some(mut smallest) = heap.peek_mut() {
+            if item > smallest.0 {
+                // Replacing the top through PeekMut re-heapifies on drop
+                *smallest = Reverse(item);
+            }
+        }
+    }
+    // Ascending order of Reverse(x) is descending order of x
+    let result: Vec<T> =
+        heap.into_sorted_vec().into_iter().map(|Reverse(x)| x).collect();
+    debug_assert!(is_sorted_by(&result, |a, b| a >= b));
+    result
+}
+
+#[test]
+fn test_top_k() {
+    let v = vec![5, 1, 9, 3, 7, 9, 2];
+    assert_eq!(top_k(v.clone(), 3), vec![9, 9, 7]);
+    assert_eq!(top_k(v.clone(), 0), Vec::<i32>::new());
+    // k larger than the input: everything, sorted descending
+    assert_eq!(top_k(v, 100), vec![9, 9, 7, 5, 3, 2, 1]);
+
+    // Works on a large lazy stream without collecting it
+    let best = top_k((0..1_000_000u64).map(|x| (x * 7919) % 1_000_003), 2);
+    assert_eq!(best, vec![1_000_002, 1_000_001]);
+
+    let words = "a bb ccc dd e".split(' ').map(|w| (w.len(), w));
+    assert_eq!(top_k(words, 2), vec![(3, "ccc"), (2, "dd")]);
+}
+
+/*
+    Reservoir sampling
+
+    Pick k items uniformly at random from an iterator whose length we
+    don't know in advance (maybe it's a stream that's too large to store).
+
+    Algorithm R: keep the first k items. After that, the i-th item
+    (0-indexed) replaces a random slot with probability k / (i + 1).
+    At the end every item has had the same chance, k / n, to be in the
+    reservoir.
+
+    The seed makes the sample reproducible, which is what we want in tests.
+*/
+
+pub fn sample_reservoir<I, T>(iter: I, k: usize, seed: u64) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+{
+    let mut rng = Rng::new(seed);
+    let mut reservoir = Vec::with_capacity(k);
+    for (i, item) in iter.into_iter().enumerate() {
+        if i < k {
+            reservoir.push(item);
+        } else {
+            let j = rng.below(i as u64 + 1) as usize;
+            if j < k {
+                reservoir[j] = item;
+            }
+        }
+    }
+    reservoir
+}
+
+#[test]
+fn test_sample_reservoir() {
+    // Fewer items than k: we get all of them
+    assert_eq!(sample_reservoir(vec![1, 2, 3], 5, 0), vec![1, 2, 3]);
+    assert!(sample_reservoir(0..100, 0, 0).is_empty());
+
+    let sample = sample_reservoir(0..1000, 10, 1);
+    assert_eq!(sample.len(), 10);
+    assert!(sample.iter().all(|&x| x < 1000));
+    // Same seed, same sample
+    assert_eq!(sample, sample_reservoir(0..1000, 10, 1));
+
+    // Works on a stream we could never collect... as long as it ends
+    let evens = (0..).map(|x| 2 * x).take_while(|&x| x < 1_000_000);
+    assert!(sample_reservoir(evens, 3, 5).iter().all(|x| x % 2 == 0));
+}
+
+#[test]
+fn test_sample_reservoir_uniform() {
+    // Each of 10 items should be picked about 3/10 of the time
+    let mut counts = [0; 10];
+    let trials = 10_000;
+    for seed in 0..trials {
+        for x in sample_reservoir(0..10, 3, seed) {
+            counts[x] += 1;
+        }
+    }
+    let expected = trials as f64 * 3.0 / 10.0;
+    for &c in &counts {
+        assert!((c as f64 - expected).abs() < expected * 0.1);
+    }
+}
+
+/*
+    Frequency counting
+
+    count_frequencies is the classic "fold into a HashMap" consumer.
+    The entry API lets us insert-or-update in one lookup.
+
+    Histogram generalizes this: a closure decides which bucket each item
+    falls into (e.g. word length, or score / 10), and we count buckets.
+*/
+
+pub fn count_frequencies<I, T>(iter: I) -> HashMap<T, usize>
+where
+    I: IntoIterator<Item = T>,
+    T: Hash + Eq,
+{
+    let mut counts = HashMap::new();
+    for item in iter {
+        *counts.entry(item).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[derive(Clone, Debug)]
+pub struct Histogram<B> {
+    counts: HashMap<B, usize>,
+    total: usize,
+}
+
+impl<B: Hash + Eq> Histogram<B> {
+    pub fn from_iter_by<I, F>(iter: I, mut bucket: F) -> Self
+    where
+        I: IntoIterator,
+        F: FnMut(&I::Item) -> B,
+    {
+        let mut counts = HashMap::new();
+        let mut total = 0;
+        for item in iter {
+            *counts.entry(bucket(&item)).or_insert(0) += 1;
+            total += 1;
+        }
+        Histogram { counts, total }
+    }
+
+    pub fn count(&self, bucket: &B) -> usize {
+        self.counts.get(bucket).copied().unwrap_or(0)
+    }
+
+    // Total number of items counted (over all buckets)
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    // (bucket, count) pairs, most common first. Ties are broken by bucket
+    // so the order is deterministic (HashMap iteration order is not).
+    pub fn iter_by_count(&self) -> impl Iterator<Item = (&B, usize)>
+    where
+        B: Ord,
+    {
+        let mut pairs: Vec<(&B, usize)> =
+            self.counts.iter().map(|(b, &c)| (b, c)).collect();
+        pairs.sort_by(|(b1, c1), (b2, c2)| c2.cmp(c1).then(b1.cmp(b2)));
+        pairs.into_iter()
+    }
+}
+
+#[test]
+fn test_count_frequencies() {
+    let counts = count_frequencies("a b a c b a".split(' '));
+    assert_eq!(counts["a"], 3);
+    assert_eq!(counts["b"], 2);
+    assert_eq!(counts["c"], 1);
+    assert_eq!(counts.get("d"), None);
+    assert!(count_frequencies(Vec::<u8>::new()).is_empty());
+}
+
+#[test]
+fn test_histogram() {
+    let scores = vec![95, 82, 87, 91, 78, 100, 85];
+    let hist = Histogram::from_iter_by(scores, |&s| s / 10 * 10);
+    assert_eq!(hist.count(&80), 3);
+    assert_eq!(hist.count(&50), 0);
+    assert_eq!(hist.total(), 7);
+    let by_count: Vec<(&i32, usize)> = hist.iter_by_count().collect();
+    assert_eq!(by_count, vec![(&80, 3), (&90, 2), (&70, 1), (&100, 1)]);
+
+    // Word lengths
+    let words = "the quick brown fox jumps over the lazy dog".split(' ');
+    let hist = Histogram::from_iter_by(words, |w| w.len());
+    assert_eq!(hist.iter_by_count().next(), Some((&3, 4)));
+}
+
+/*
+    Sortedness checks
+
+    is_sorted_by walks the iterator comparing each item with the previous
+    one, and stops at the first pair that's out of order. `in_order(a, b)`
+    should return true if a is allowed to come before b.
+
+    Only the previous item is kept around, so this works on any iterator,
+    not just slices (std's slice::is_sorted_by needs a slice).
+*/
+
+pub fn is_sorted_by<I, T, F>(iter: I, mut in_order: F) -> bool
+where
+    I: IntoIterator<Item = T>,
+    F: FnMut(&T, &T) -> bool,
+{
+    let mut iter = iter.into_iter();
+    let mut prev = match iter.next() {
+        Some(x) => x,
+        None => return true,
+    };
+    for x in iter {
+        if !in_order(&prev, &x) {
+            return false;
+        }
+        prev = x;
+    }
+    true
+}
+
+pub fn is_strictly_increasing<I>(iter: I) -> bool
+where
+    I: IntoIterator,
+    I::Item: PartialOrd,
+{
+    is_sorted_by(iter, |a, b| a < b)
+}
+
+#[test]
+fn test_is_sorted_by() {
+    assert!(is_sorted_by(vec![1, 2, 2, 3], |a, b| a <= b));
+    assert!(!is_sorted_by(vec![1, 3, 2], |a, b| a <= b));
+    assert!(is_sorted_by(vec![3, 2, 2], |a, b| a >= b));
+    assert!(is_sorted_by(Vec::<i32>::new(), |_, _| false));
+    assert!(is_sorted_by(vec![1], |_, _| false));
+
+    assert!(is_strictly_increasing(vec![1, 2, 3]));
+    assert!(!is_strictly_increasing(vec![1, 2, 2]));
+    assert!(is_strictly_increasing("abc".chars()));
+}
+
+#[test]
+fn test_is_sorted_by_short_circuits() {
+    // Would never finish if we didn't stop at the first violation
+    let mut checked = 0;
+    let sorted = is_sorted_by((0..).map(|x| x % 5), |a, b| {
+        checked += 1;
+        a <= b
+    });
+    assert!(!sorted);
+    assert_eq!(checked, 5);
+}
+
+/*
+    Collecting Results
+
+    .collect::<Result<Vec<T>, E>>() stops at the first Err and throws away
+    the Ok values seen so far. Sometimes we want to keep them (e.g. "the
+    first 3 lines parsed fine, line 4 is broken").
+
+    collect_until_err returns everything before the first Err, plus the
+    Err itself if there was one. Items after the Err are not consumed.
+*/
+
+pub fn collect_until_err<I, T, E>(iter: I) -> (Vec<T>, Option<E>)
+where
+    I: IntoIterator<Item = Result<T, E>>,
+{
+    let mut oks = Vec::new();
+    for item in iter {
+        match item {
+            Ok(x) => oks.push(x),
+            Err(e) => return (oks, Some(e)),
+        }
+    }
+    (oks, None)
+}
+
+#[test]
+fn test_collect_until_err() {
+    let items: Vec<Result<i32, &str>> = vec![Ok(1), Ok(2), Err("e"), Ok(4)];
+    assert_eq!(collect_until_err(items), (vec![1, 2], Some("e")));
+
+    let all_ok: Vec<Result<i32, &str>> = vec![Ok(1), Ok(2)];
+    assert_eq!(collect_until_err(all_ok), (vec![1, 2], None));
+
+    // Combined with the fallible adapters
+    use adapters::MyIteratorExt;
+    let (nums, err) = collect_until_err(
+        "3 8 5 x 7"
+            .split(' ')
+            .map(|s| s.parse::<u32>())
+            .try_filter(|&n| n != 8)
+            .try_map(|n| Ok(n * 10)),
+    );
+    assert_eq!(nums, vec![30, 50]);
+    assert!(err.is_some());
+}
+
+// Like .collect::<Result<Vec<T>, E>>(), but the error also says *where*
+// things went wrong: the index of the first Err item. (Line numbers are
+// much more useful error messages than just "parse error".)
+pub fn try_collect_vec<I, T, E>(iter: I) -> Result<Vec<T>, (usize, E)>
+where
+    I: IntoIterator<Item = Result<T, E>>,
+{
+    let mut result = Vec::new();
+    for (i, item) in iter.into_iter().enumerate() {
+        result.push(item.map_err(|e| (i, e))?);
+    }
+    Ok(result)
+}
+
+#[test]
+fn test_try_collect_vec() {
+    let ok: Vec<Result<i32, &str>> = vec![Ok(1), Ok(2), Ok(3)];
+    assert_eq!(try_collect_vec(ok), Ok(vec![1, 2, 3]));
+
+    let bad: Vec<Result<i32, &str>> = vec![Ok(1), Err("a"), Err("b")];
+    assert_eq!(try_collect_vec(bad), Err((1, "a")));
+
+    let parsed =
+        try_collect_vec("10\n20\nthirty\n40".lines().map(str::parse::<u8>));
+    let (line, _) = parsed.unwrap_err();
+    assert_eq!(line, 2);
+
+    // Short-circuits: stops pulling items at the first error
+    let mut pulled = 0;
+    let res = try_collect_vec((0..10).map(|i| {
+        pulled += 1;
+        if i == 3 {
+            Err(i)
+        } else {
+            Ok(i)
+        }
+    }));
+    assert_eq!(res, Err((3, 3)));
+    assert_eq!(pulled, 4);
+}
+
+/*
+    Joining into a String
+
+    The usual way is items.map(|x| x.to_string()).collect::<Vec<_>>()
+    .join(", "), which allocates a String per item plus a Vec. A single
+    fold can write every item straight into one String instead.
+
+    The separator goes *before* every item except the first, so there's
+    never a trailing separator to trim.
+*/
+
+pub fn join_display(
+    iter: impl Iterator<Item = impl Display>,
+    sep: &str,
+) -> String {
+    iter.enumerate().fold(String::new(), |mut acc, (i, item)| {
+        if i > 0 {
+            acc.push_str(sep);
+        }
+        // Writing to a String can't fail
+        write!(acc, "{}", item).unwrap();
+        acc
+    })
+}
+
+#[test]
+fn test_join_display() {
+    assert_eq!(join_display(vec![1, 2, 3].into_iter(), ", "), "1, 2, 3");
+    assert_eq!(join_display(["solo"].iter(), " | "), "solo");
+    assert_eq!(join_display(Vec::<u8>::new().into_iter(), ","), "");
+    assert_eq!(
+        join_display((1..=3).map(|x| x as f64 / 2.0), "; "),
+        "0.5; 1; 1.5"
+    );
+}
+
+/*
+    The same, with friendlier bounds
+
+    join_display wants an Iterator and a &str. display_join accepts
+    anything iterable (a Vec, an array, &[T], a range...) and any
+    separator that can be viewed as a str (&str, String, Cow<str>...).
+    Each bound says exactly what the function needs:
+
+        I: IntoIterator     -- we only loop over it once
+        I::Item: Display    -- and format each item
+        S: AsRef<str>       -- and read the separator as a &str
+
+    display_table lines up rows of cells into columns. It has to see
+    every cell twice (once to measure the columns, once to print), so it
+    formats each cell to a String first. Widths are counted in chars,
+    so accented names don't throw the alignment off.
+*/
+
+pub fn display_join<I, S>(items: I, sep: S) -> String
+where
+    I: IntoIterator,
+    I::Item: Display,
+    S: AsRef<str>,
+{
+    join_display(items.into_iter(), sep.as_ref())
+}
+
+pub fn display_table<R, C>(rows: R) -> String
+where
+    R: IntoIterator,
+    R::Item: IntoIterator<Item = C>,
+    C: Display,
+{
+    let cells: Vec<Vec<String>> = rows
+        .into_iter()
+        .map(|row| row.into_iter().map(|cell| cell.to_string()).collect())
+        .collect();
+    let mut widths: Vec<usize> = Vec::new();
+    for row in &cells {
+        for (i, cell) in row.iter().enumerate() {
+            let w = cell.chars().count();
+            match widths.get_mut(i) {
+                Some(max) => *max = (*max).max(w),
+                None => widths.push(w),
+            }
+        }
+    }
+    let lines = cells.iter().map(|row| {
+        let padded = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &w)| format!("{:<width$}", cell, width = w));
+        display_join(padded, "  ").trim_end().to_string()
+    });
+    display_join(lines, "\n")
+}
+
+#[test]
+fn test_display_join() {
+    let v = vec![1, 2, 3];
+    assert_eq!(display_join(&v, ", "), "1, 2, 3");
+    assert_eq!(display_join(v, ", "), "1, 2, 3");
+    assert_eq!(display_join(1..=3, String::from("-")), "1-2-3");
+    assert_eq!(display_join(vec!['a', 'b'], ""), "ab");
+    assert_eq!(display_join(Vec::<i32>::new(), ", "), "");
+}
+
+#[test]
+fn test_display_table() {
+    let table = display_table(vec![
+        vec!["song", "plays", "rating"],
+        vec!["Help!", "12", "5"],
+        vec!["Für Elise", "7"],
+    ]);
+    assert_eq!(
+        table,
+        "song       plays  rating\n\
+         Help!      12     5\n\
+         Für Elise  7"
+    );
+    // Any Display cells
+    assert_eq!(
+        display_table(vec![vec![1, 200], vec![30, 4]]),
+        "1   200\n30  4"
+    );
+    assert_eq!(display_table(Vec::<Vec<u8>>::new()), "");
+}
+
+/*
+    Parallel fold (map-reduce)
+
+    A fold over a slice can be split up: fold each chunk separately
+    (in parallel), then combine the partial results with `reduce`. For
+    this to give the same answer as a sequential fold, `reduce` must be
+    associative and `identity()` must be a neutral starting value.
+
+    thread::scope lets the worker threads borrow `items` and the
+    closures: all threads are joined before scope returns, so the borrows
+    can't outlive the data. The bounds say what's shared across threads:
+    the closures are called from several threads at once (Sync), and
+    partial results are sent back to this thread (Send).
+*/
+
+pub fn par_fold<T, A, I, F, R>(
+    items: &[T],
+    identity: I,
+    fold: F,
+    reduce: R,
+) -> A
+where
+    T: Sync,
+    A: Send,
+    I: Fn() -> A + Sync,
+    F: Fn(A, &T) -> A + Sync,
+    R: Fn(A, A) -> A,
+{
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    // Round up, and at least 1 (chunks(0) panics)
+    let chunk_size = items.len().div_ceil(threads).max(1);
+    let (identity, fold) = (&identity, &fold);
+    let partials: Vec<A> = thread::scope(|s| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| s.spawn(move || chunk.iter().fold(identity(), fold)))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    partials.into_iter().fold(identity(), reduce)
+}
+
+#[test]
+fn test_par_fold() {
+    let v: Vec<u64> = (1..=10_000).collect();
+    let sum = par_fold(&v, || 0, |acc, &x| acc + x, |a, b| a + b);
+    assert_eq!(sum, 50_005_000);
+
+    // A richer accumulator: (count, max)
+    let words = ["a", "abcd", "ab", "abc"];
+    let (count, longest) = par_fold(
+        &words,
+        || (0, 0),
+        |(n, m), w| (n + 1, m.max(w.len())),
+        |(n1, m1), (n2, m2)| (n1 + n2, m1.max(m2)),
+    );
+    assert_eq!((count, longest), (4, 4));
+
+    let empty: [i32; 0] = [];
+    assert_eq!(par_fold(&empty, || 7, |a, _| a, |a, b| a + b), 7);
+}
+
+// Compares only by the first field, so we can observe tie-breaking
+#[cfg(test)]
+#[derive(Clone, Debug)]
+struct Keyed(i32, char);
+#[cfg(test)]
+impl PartialEq for Keyed {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+#[cfg(test)]
+impl PartialOrd for Keyed {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.0.partial_cmp(&other.0)
+    }
+}
@@ -0,0 +1,1039 @@
+/*
+    Iterators for custom data structures
+
+    More examples of the recipe from the end of iterators.rs:
+    - a data structure
+    - a dedicated iterator struct (usually borrowing the data structure,
+      hence the lifetime parameter)
+    - impl Iterator for the iterator struct
+    - a method on the data structure that creates the iterator
+*/
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, VecDeque};
+use std::iter::Peekable;
+
+/*
+    Trees
+
+    A tree is a value plus a list of subtrees. There are two standard
+    orders to visit the nodes in:
+
+    - Depth-first (DFS): go all the way down the first child before
+      looking at the second. Uses a stack.
+    - Breadth-first (BFS): visit the root, then all nodes at depth 1,
+      then depth 2, ... Uses a queue.
+
+    A recursive function would use the call stack implicitly, but an
+    iterator has to stop after every item and pick up where it left off
+    on the next call to .next(). So the stack/queue is stored explicitly
+    in the iterator struct.
+*/
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tree<T> {
+    value: T,
+    children: Vec<Tree<T>>,
+}
+
+impl<T> Tree<T> {
+    pub fn new(value: T) -> Self {
+        Tree { value, children: Vec::new() }
+    }
+
+    // Builder-style, so trees can be written as nested expressions
+    pub fn with_child(mut self, child: Tree<T>) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn add_child(&mut self, child: Tree<T>) {
+        self.children.push(child);
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn children(&self) -> &[Tree<T>] {
+        &self.children
+    }
+
+    pub fn iter_dfs(&self) -> DfsIter<'_, T> {
+        DfsIter { stack: vec![self] }
+    }
+
+    pub fn iter_bfs(&self) -> BfsIter<'_, T> {
+        let mut queue = VecDeque::new();
+        queue.push_back(self);
+        BfsIter { queue }
+    }
+}
+
+pub struct DfsIter<'a, T> {
+    stack: Vec<&'a Tree<T>>,
+}
+
+impl<'a, T> Iterator for DfsIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        // Push in reverse so the first child is popped first
+        self.stack.extend(node.children.iter().rev());
+        Some(&node.value)
+    }
+}
+
+pub struct BfsIter<'a, T> {
+    queue: VecDeque<&'a Tree<T>>,
+}
+
+impl<'a, T> Iterator for BfsIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(node.children.iter());
+        Some(&node.value)
+    }
+}
+
+#[cfg(test)]
+fn example_tree() -> Tree<u32> {
+    //        1
+    //      / | \
+    //     2  3  4
+    //    / \     \
+    //   5   6     7
+    Tree::new(1)
+        .with_child(
+            Tree::new(2).with_child(Tree::new(5)).with_child(Tree::new(6)),
+        )
+        .with_child(Tree::new(3))
+        .with_child(Tree::new(4).with_child(Tree::new(7)))
+}
+
+#[test]
+fn test_tree_dfs() {
+    let tree = example_tree();
+    let order: Vec<u32> = tree.iter_dfs().copied().collect();
+    assert_eq!(order, vec![1, 2, 5, 6, 3, 4, 7]);
+    assert_eq!(Tree::new("leaf").iter_dfs().count(), 1);
+}
+
+#[test]
+fn test_tree_bfs() {
+    let mut tree = example_tree();
+    let order: Vec<u32> = tree.iter_bfs().copied().collect();
+    assert_eq!(order, vec![1, 2, 3, 4, 5, 6, 7]);
+
+    tree.add_child(Tree::new(8));
+    assert_eq!(tree.iter_bfs().nth(4), Some(&8));
+    // Both orders visit the same nodes
+    assert_eq!(tree.iter_dfs().sum::<u32>(), tree.iter_bfs().sum::<u32>());
+}
+
+/*
+    Singly linked list
+
+    Like Vec, a collection usually offers three iterators:
+    - iter()      -> &T      (borrows the list)
+    - iter_mut()  -> &mut T  (borrows the list mutably)
+    - into_iter() -> T       (consumes the list)
+
+    Each one is its own struct. With a Vec they can all just keep an
+    index; with a linked list they follow pointers instead, and the
+    lifetimes matter: Iter<'a, T> holds an Option<&'a Node<T>>, the next
+    node to visit.
+
+    Implementing IntoIterator for LinkedList, &LinkedList and
+    &mut LinkedList is what makes all three kinds of for loop work.
+*/
+
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T> {
+    value: T,
+    next: Link<T>,
+}
+
+pub struct LinkedList<T> {
+    head: Link<T>,
+    len: usize,
+}
+
+impl<T> LinkedList<T> {
+    pub fn new() -> Self {
+        LinkedList { head: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let next = self.head.take();
+        self.head = Some(Box::new(Node { value, next }));
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let node = self.head.take()?;
+        self.head = node.next;
+        self.len -= 1;
+        Some(node.value)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { next: self.head.as_deref() }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut { next: self.head.as_deref_mut() }
+    }
+}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        LinkedList::new()
+    }
+}
+
+// The default recursive drop would recurse once per node and can overflow
+// the stack for long lists, so we unlink the nodes one at a time.
+impl<T> Drop for LinkedList<T> {
+    fn drop(&mut self) {
+        let mut link = self.head.take();
+        while let Some(mut node) = link {
+            link = node.next.take();
+        }
+    }
+}
+
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next?;
+        self.next = node.next.as_deref();
+        Some(&node.value)
+    }
+}
+
+pub struct IterMut<'a, T> {
+    next: Option<&'a mut Node<T>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // take() is essential: &mut references can't be copied, so we move
+        // the reference out of self.next before splitting it into a
+        // reference to the value and a reference to the next node
+        let node = self.next.take()?;
+        self.next = node.next.as_deref_mut();
+        Some(&mut node.value)
+    }
+}
+
+pub struct IntoIter<T> {
+    list: LinkedList<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> IntoIterator for LinkedList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { list: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a LinkedList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut LinkedList<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[test]
+fn test_linked_list_iterators() {
+    let mut list = LinkedList::new();
+    assert!(list.is_empty());
+    for x in (1..=3).rev() {
+        list.push_front(x);
+    }
+    assert_eq!(list.len(), 3);
+    assert_eq!(list.iter().collect::<Vec<_>>(), vec![&1, &2, &3]);
+
+    for x in &mut list {
+        *x *= 10;
+    }
+    let mut sum = 0;
+    for x in &list {
+        sum += x;
+    }
+    assert_eq!(sum, 60);
+
+    let owned: Vec<i32> = list.into_iter().collect();
+    assert_eq!(owned, vec![10, 20, 30]);
+}
+
+#[test]
+fn test_linked_list_long() {
+    // Would overflow the stack without the iterative Drop
+    let mut list = LinkedList::new();
+    for i in 0..200_000 {
+        list.push_front(i);
+    }
+    assert_eq!(list.iter().count(), 200_000);
+    assert_eq!(list.pop_front(), Some(199_999));
+    assert_eq!(list.into_iter().size_hint(), (199_999, Some(199_999)));
+}
+
+/*
+    Ring buffer
+
+    A fixed-capacity queue stored in a Vec. `head` is the physical index
+    of the oldest element; the logical i-th element lives at
+    (head + i) % capacity. When the buffer is full, pushing overwrites
+    the oldest element (useful for "keep the last N log lines").
+
+    The physical layout wraps around:
+
+        logical order:  c d e f
+        physical slots: [e, f, c, d]   head = 2
+
+    but the iterator hides that and always yields oldest to newest.
+*/
+
+pub struct RingBuffer<T> {
+    slots: Vec<Option<T>>,
+    head: usize,
+    len: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "RingBuffer: capacity must be positive");
+        RingBuffer {
+            slots: (0..capacity).map(|_| None).collect(),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == self.capacity()
+    }
+
+    // Adds to the newest end. If the buffer was full, the oldest element
+    // is overwritten and returned.
+    pub fn push(&mut self, value: T) -> Option<T> {
+        let tail = (self.head + self.len) % self.capacity();
+        let evicted = self.slots[tail].replace(value);
+        if self.is_full() {
+            self.head = (self.head + 1) % self.capacity();
+        } else {
+            self.len += 1;
+        }
+        evicted
+    }
+
+    // Removes the oldest element
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let value = self.slots[self.head].take();
+        self.head = (self.head + 1) % self.capacity();
+        self.len -= 1;
+        value
+    }
+
+    pub fn iter(&self) -> RingIter<'_, T> {
+        RingIter { ring: self, offset: 0 }
+    }
+}
+
+pub struct RingIter<'a, T> {
+    ring: &'a RingBuffer<T>,
+    // Logical index of the next element to yield
+    offset: usize,
+}
+
+impl<'a, T> Iterator for RingIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset == self.ring.len {
+            return None;
+        }
+        let physical = (self.ring.head + self.offset) % self.ring.capacity();
+        self.offset += 1;
+        self.ring.slots[physical].as_ref()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.ring.len - self.offset;
+        (remaining, Some(remaining))
+    }
+}
+
+#[test]
+fn test_ring_buffer_wraps() {
+    let mut ring = RingBuffer::with_capacity(4);
+    for c in "abcd".chars() {
+        assert_eq!(ring.push(c), None);
+    }
+    assert!(ring.is_full());
+    // Overwrites 'a' and 'b'
+    assert_eq!(ring.push('e'), Some('a'));
+    assert_eq!(ring.push('f'), Some('b'));
+    assert_eq!(ring.iter().collect::<String>(), "cdef");
+    assert_eq!(ring.iter().size_hint(), (4, Some(4)));
+
+    assert_eq!(ring.pop(), Some('c'));
+    assert_eq!(ring.len(), 3);
+    ring.push('g');
+    assert_eq!(ring.iter().collect::<String>(), "defg");
+}
+
+#[test]
+fn test_ring_buffer_fifo() {
+    let mut ring = RingBuffer::with_capacity(3);
+    assert_eq!(ring.pop(), None);
+    assert_eq!(ring.iter().count(), 0);
+    // Many laps around the buffer, checked against a VecDeque
+    let mut model = VecDeque::new();
+    for i in 0..100 {
+        ring.push(i);
+        model.push_back(i);
+        if model.len() > 3 {
+            model.pop_front();
+        }
+        if i % 3 == 0 {
+            assert_eq!(ring.pop(), model.pop_front());
+        }
+        assert!(ring.iter().eq(model.iter()));
+    }
+    assert_eq!(ring.capacity(), 3);
+}
+
+/*
+    Graphs
+
+    An adjacency-list graph: for every node, the list of nodes it has an
+    edge to. Nodes are identified by NodeId, a small Copy newtype around an
+    index, rather than by references (a graph full of references to
+    itself is a lifetime nightmare in Rust).
+
+    neighbors() is the "easy way": just return `impl Iterator` built from
+    std adapters. The traversals are the "harder way": a dedicated struct,
+    because they need state between calls -- a stack or queue of nodes to
+    visit, and the set of nodes already seen (graphs, unlike trees, can
+    have cycles).
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Clone, Debug, Default)]
+pub struct Graph {
+    adjacency: Vec<Vec<NodeId>>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add_node(&mut self) -> NodeId {
+        self.adjacency.push(Vec::new());
+        NodeId(self.adjacency.len() - 1)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    // Directed edge from -> to
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId) {
+        assert!(to.0 < self.node_count(), "add_edge: unknown node {:?}", to);
+        self.adjacency[from.0].push(to);
+    }
+
+    pub fn add_undirected_edge(&mut self, a: NodeId, b: NodeId) {
+        self.add_edge(a, b);
+        self.add_edge(b, a);
+    }
+
+    pub fn neighbors(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.adjacency[node.0].iter().copied()
+    }
+
+    pub fn bfs_from(&self, start: NodeId) -> Bfs<'_> {
+        let mut visited = vec![false; self.node_count()];
+        visited[start.0] = true;
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        Bfs { graph: self, queue, visited }
+    }
+
+    pub fn dfs_from(&self, start: NodeId) -> Dfs<'_> {
+        Dfs {
+            graph: self,
+            stack: vec![start],
+            visited: vec![false; self.node_count()],
+        }
+    }
+}
+
+pub struct Bfs<'a> {
+    graph: &'a Graph,
+    queue: VecDeque<NodeId>,
+    visited: Vec<bool>,
+}
+
+impl<'a> Iterator for Bfs<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.queue.pop_front()?;
+        for next in self.graph.neighbors(node) {
+            // Mark when enqueued, so each node is queued at most once
+            if !self.visited[next.0] {
+                self.visited[next.0] = true;
+                self.queue.push_back(next);
+            }
+        }
+        Some(node)
+    }
+}
+
+pub struct Dfs<'a> {
+    graph: &'a Graph,
+    stack: Vec<NodeId>,
+    visited: Vec<bool>,
+}
+
+impl<'a> Iterator for Dfs<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // A node can be pushed several times before it's visited;
+        // skip the stale copies
+        loop {
+            let node = self.stack.pop()?;
+            if self.visited[node.0] {
+                continue;
+            }
+            self.visited[node.0] = true;
+            // Push in reverse so the first neighbor is visited first
+            let visited = &self.visited;
+            let unvisited = self.graph.adjacency[node.0]
+                .iter()
+                .rev()
+                .filter(|n| !visited[n.0]);
+            self.stack.extend(unvisited);
+            return Some(node);
+        }
+    }
+}
+
+#[cfg(test)]
+fn example_graph() -> (Graph, Vec<NodeId>) {
+    // 0 - 1 - 3
+    // |   |
+    // 2 --+   4 (unreachable from 0)
+    let mut g = Graph::new();
+    let n: Vec<NodeId> = (0..5).map(|_| g.add_node()).collect();
+    g.add_undirected_edge(n[0], n[1]);
+    g.add_undirected_edge(n[0], n[2]);
+    g.add_undirected_edge(n[1], n[2]);
+    g.add_undirected_edge(n[1], n[3]);
+    (g, n)
+}
+
+#[test]
+fn test_graph_neighbors() {
+    let (g, n) = example_graph();
+    assert_eq!(g.neighbors(n[1]).collect::<Vec<_>>(), vec![n[0], n[2], n[3]]);
+    assert_eq!(g.neighbors(n[4]).count(), 0);
+    assert_eq!(g.node_count(), 5);
+}
+
+#[test]
+fn test_graph_traversals() {
+    let (g, n) = example_graph();
+    let bfs: Vec<NodeId> = g.bfs_from(n[0]).collect();
+    assert_eq!(bfs, vec![n[0], n[1], n[2], n[3]]);
+    let dfs: Vec<NodeId> = g.dfs_from(n[0]).collect();
+    assert_eq!(dfs, vec![n[0], n[1], n[2], n[3]]);
+    let dfs: Vec<NodeId> = g.dfs_from(n[3]).collect();
+    assert_eq!(dfs, vec![n[3], n[1], n[0], n[2]]);
+
+    // Cycles don't cause infinite loops, and unreachable nodes are skipped
+    assert_eq!(g.bfs_from(n[2]).count(), 4);
+    assert_eq!(g.dfs_from(n[4]).collect::<Vec<_>>(), vec![n[4]]);
+}
+
+/*
+    2D grids
+
+    Grid stores rows * cols items in a single flat Vec, row by row
+    ("row-major" order): item (r, c) lives at index r * cols + c.
+
+    - A row is contiguous, so rows() is just .chunks(cols).
+    - A column is not: consecutive items of column c are `cols` apart.
+    - A diagonal (going down-right) steps by cols + 1.
+
+    So columns and diagonals both need a strided iterator: start at some
+    index, yield `len` items, stepping by `step` each time.
+*/
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid<T> {
+    rows: usize,
+    cols: usize,
+    data: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    pub fn from_vec(rows: usize, cols: usize, data: Vec<T>) -> Self {
+        assert_eq!(data.len(), rows * cols, "Grid: wrong number of items");
+        assert!(cols > 0, "Grid: need at least one column");
+        Grid { rows, cols, data }
+    }
+
+    pub fn filled(rows: usize, cols: usize, value: T) -> Self
+    where
+        T: Clone,
+    {
+        Grid::from_vec(rows, cols, vec![value; rows * cols])
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn num_cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, r: usize, c: usize) -> Option<&T> {
+        if r < self.rows && c < self.cols {
+            self.data.get(r * self.cols + c)
+        } else {
+            None
+        }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.data.chunks(self.cols)
+    }
+
+    pub fn cols(&self) -> impl Iterator<Item = Strided<'_, T>> {
+        (0..self.cols).map(move |c| Strided {
+            data: &self.data,
+            index: c,
+            step: self.cols,
+            remaining: self.rows,
+        })
+    }
+
+    // All down-right diagonals, starting with the bottom-left corner and
+    // ending with the top-right corner. Diagonal number d contains the
+    // cells with c - r == d - (rows - 1).
+    pub fn diagonals(&self) -> impl Iterator<Item = Strided<'_, T>> {
+        let (rows, cols) = (self.rows, self.cols);
+        (0..(rows + cols).saturating_sub(1)).map(move |d| {
+            // Starting cell: on the left edge, then along the top edge
+            let (r, c) =
+                if d < rows { (rows - 1 - d, 0) } else { (0, d + 1 - rows) };
+            Strided {
+                data: &self.data,
+                index: r * cols + c,
+                step: cols + 1,
+                remaining: (rows - r).min(cols - c),
+            }
+        })
+    }
+
+    // Every item together with its (row, column)
+    pub fn indexed(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        let cols = self.cols;
+        self.data
+            .iter()
+            .enumerate()
+            .map(move |(i, x)| ((i / cols, i % cols), x))
+    }
+}
+
+pub struct Strided<'a, T> {
+    data: &'a [T],
+    index: usize,
+    step: usize,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Strided<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let item = &self.data[self.index];
+        self.remaining -= 1;
+        // Don't step past the end after the last item
+        if self.remaining > 0 {
+            self.index += self.step;
+        }
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+#[cfg(test)]
+fn example_grid() -> Grid<u32> {
+    // 1 2 3
+    // 4 5 6
+    Grid::from_vec(2, 3, vec![1, 2, 3, 4, 5, 6])
+}
+
+#[test]
+fn test_grid_rows_cols() {
+    let g = example_grid();
+    let rows: Vec<&[u32]> = g.rows().collect();
+    assert_eq!(rows, vec![&[1, 2, 3][..], &[4, 5, 6][..]]);
+    let cols: Vec<Vec<u32>> = g.cols().map(|c| c.copied().collect()).collect();
+    assert_eq!(cols, vec![vec![1, 4], vec![2, 5], vec![3, 6]]);
+    assert_eq!(g.get(1, 2), Some(&6));
+    assert_eq!(g.get(2, 0), None);
+    assert_eq!(g.get(0, 3), None);
+}
+
+#[test]
+fn test_grid_diagonals() {
+    let g = example_grid();
+    let diags: Vec<Vec<u32>> =
+        g.diagonals().map(|d| d.copied().collect()).collect();
+    assert_eq!(diags, vec![vec![4], vec![1, 5], vec![2, 6], vec![3]]);
+
+    // Every cell is on exactly one diagonal
+    let tall = Grid::from_vec(4, 2, (0..8).collect());
+    let mut seen: Vec<u32> = tall.diagonals().flatten().copied().collect();
+    seen.sort();
+    assert_eq!(seen, (0..8).collect::<Vec<_>>());
+    assert_eq!(tall.diagonals().count(), 5);
+}
+
+#[test]
+fn test_grid_indexed() {
+    let g = example_grid();
+    let cells: Vec<((usize, usize), &u32)> = g.indexed().collect();
+    assert_eq!(cells[0], ((0, 0), &1));
+    assert_eq!(cells[4], ((1, 1), &5));
+    assert!(g.indexed().all(|((r, c), x)| g.get(r, c) == Some(x)));
+
+    let empty = Grid::filled(0, 3, 'x');
+    assert_eq!(empty.rows().count(), 0);
+    assert_eq!(empty.diagonals().flatten().count(), 0);
+    assert_eq!(Grid::filled(2, 2, 'x').cols().count(), 2);
+}
+
+/*
+    Sparse vectors
+
+    A vector that's mostly zeros is stored as just its non-zero entries,
+    as (index, value) pairs sorted by index.
+
+    The dot product only needs the indices where *both* vectors are
+    non-zero. Since both lists are sorted, we can walk them side by side
+    like the merge step of merge sort: whichever side has the smaller
+    index advances, and when the indices match we've found a pair.
+    That's one pass, O(nnz1 + nnz2), with no lookups.
+*/
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseVec {
+    dim: usize,
+    // Sorted by index, no zero values
+    entries: Vec<(usize, f64)>,
+}
+
+impl SparseVec {
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn iter_nonzero(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        self.entries.iter().copied()
+    }
+
+    pub fn dot(&self, other: &SparseVec) -> f64 {
+        assert_eq!(self.dim, other.dim, "dot: dimension mismatch");
+        MatchingEntries::new(self.iter_nonzero(), other.iter_nonzero())
+            .map(|(_, a, b)| a * b)
+            .sum()
+    }
+}
+
+impl<'a> From<&'a [f64]> for SparseVec {
+    fn from(dense: &'a [f64]) -> Self {
+        let entries = dense
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, x)| x != 0.0)
+            .collect();
+        SparseVec { dim: dense.len(), entries }
+    }
+}
+
+// Yields (index, a, b) for every index present in both sorted inputs
+pub struct MatchingEntries<A: Iterator, B: Iterator> {
+    a: Peekable<A>,
+    b: Peekable<B>,
+}
+
+impl<A, B> MatchingEntries<A, B>
+where
+    A: Iterator<Item = (usize, f64)>,
+    B: Iterator<Item = (usize, f64)>,
+{
+    pub fn new(a: A, b: B) -> Self {
+        MatchingEntries { a: a.peekable(), b: b.peekable() }
+    }
+}
+
+impl<A, B> Iterator for MatchingEntries<A, B>
+where
+    A: Iterator<Item = (usize, f64)>,
+    B: Iterator<Item = (usize, f64)>,
+{
+    type Item = (usize, f64, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (&(i, x), &(j, y)) = (self.a.peek()?, self.b.peek()?);
+            match i.cmp(&j) {
+                Ordering::Less => {
+                    self.a.next();
+                }
+                Ordering::Greater => {
+                    self.b.next();
+                }
+                Ordering::Equal => {
+                    self.a.next();
+                    self.b.next();
+                    return Some((i, x, y));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_sparse_vec() {
+    let v = SparseVec::from(&[0.0, 2.0, 0.0, 0.0, 3.0][..]);
+    assert_eq!(v.dim(), 5);
+    assert_eq!(v.iter_nonzero().collect::<Vec<_>>(), vec![(1, 2.0), (4, 3.0)]);
+    let zeros = SparseVec::from(&[0.0; 4][..]);
+    assert_eq!(zeros.iter_nonzero().count(), 0);
+}
+
+#[test]
+fn test_sparse_dot() {
+    let a = [1.0, 0.0, 2.0, 0.0, 0.0, 5.0];
+    let b = [0.0, 7.0, 3.0, 0.0, 1.0, -1.0];
+    let dense_dot: f64 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+    let (sa, sb) = (SparseVec::from(&a[..]), SparseVec::from(&b[..]));
+    assert_eq!(sa.dot(&sb), dense_dot);
+    assert_eq!(sa.dot(&sb), 1.0);
+    assert_eq!(sa.dot(&SparseVec::from(&[0.0; 6][..])), 0.0);
+
+    let matches: Vec<usize> =
+        MatchingEntries::new(sa.iter_nonzero(), sb.iter_nonzero())
+            .map(|(i, _, _)| i)
+            .collect();
+    assert_eq!(matches, vec![2, 5]);
+}
+
+/*
+    Tries (prefix trees)
+
+    Each node has one child per next character; a word is a path from the
+    root to a node marked `is_word`. All words with a given prefix live in
+    the subtree under the prefix's node.
+
+    iter_prefix walks that subtree lazily with an explicit stack, like
+    Tree::iter_dfs, except each stack entry also remembers the string
+    spelled out on the way down. Using a BTreeMap for the children makes
+    the words come out in alphabetical order.
+*/
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    children: BTreeMap<char, TrieNode>,
+    is_word: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Trie {
+    root: TrieNode,
+    len: usize,
+}
+
+impl Trie {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // Number of distinct words
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Returns false if the word was already present
+    pub fn insert(&mut self, word: &str) -> bool {
+        let mut node = &mut self.root;
+        for c in word.chars() {
+            node = node.children.entry(c).or_default();
+        }
+        let is_new = !node.is_word;
+        node.is_word = true;
+        if is_new {
+            self.len += 1;
+        }
+        is_new
+    }
+
+    fn find(&self, prefix: &str) -> Option<&TrieNode> {
+        let mut node = &self.root;
+        for c in prefix.chars() {
+            node = node.children.get(&c)?;
+        }
+        Some(node)
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.find(word).is_some_and(|node| node.is_word)
+    }
+
+    pub fn iter_prefix(&self, prefix: &str) -> PrefixIter<'_> {
+        let stack = match self.find(prefix) {
+            Some(node) => vec![(prefix.to_string(), node)],
+            None => Vec::new(),
+        };
+        PrefixIter { stack }
+    }
+}
+
+pub struct PrefixIter<'a> {
+    stack: Vec<(String, &'a TrieNode)>,
+}
+
+impl<'a> Iterator for PrefixIter<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (word, node) = self.stack.pop()?;
+            // Reverse so that the smallest character is popped first
+            for (&c, child) in node.children.iter().rev() {
+                let mut longer = word.clone();
+                longer.push(c);
+                self.stack.push((longer, child));
+            }
+            if node.is_word {
+                return Some(word);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_trie() {
+    let mut trie = Trie::new();
+    assert!(trie.is_empty());
+    for w in &["car", "cart", "care", "cat", "dog", "do"] {
+        assert!(trie.insert(w));
+    }
+    assert!(!trie.insert("cat"));
+    assert_eq!(trie.len(), 6);
+
+    assert!(trie.contains("cart"));
+    assert!(!trie.contains("ca"));
+    assert!(!trie.contains("cartoon"));
+    assert!(trie.contains("do"));
+}
+
+#[test]
+fn test_trie_iter_prefix() {
+    let mut trie = Trie::new();
+    for w in &["car", "cart", "care", "cat", "dog", "do"] {
+        trie.insert(w);
+    }
+    let car: Vec<String> = trie.iter_prefix("car").collect();
+    assert_eq!(car, vec!["car", "care", "cart"]);
+    let all: Vec<String> = trie.iter_prefix("").collect();
+    assert_eq!(all, vec!["car", "care", "cart", "cat", "do", "dog"]);
+    assert_eq!(trie.iter_prefix("x").count(), 0);
+
+    // Lazy: taking the first match doesn't walk the whole subtree
+    assert_eq!(trie.iter_prefix("c").next(), Some("car".to_string()));
+}
@@ -0,0 +1,552 @@
+/*
+    A small binary format: ToBytes / FromBytes
+
+    Two traits, one for each direction. Every value is written as a
+    sequence of bytes that can be read back without knowing its size in
+    advance:
+    - integers are 8 bytes, little-endian (usize is widened to u64)
+    - strings are a length (u32) followed by that many UTF-8 bytes
+    - Vec<T> is a count (u32) followed by each element
+    - Option<T> is a tag byte, 0 (None) or 1 (Some), then the value
+
+    The impls for Vec<T> and Option<T> are generic: they work for any T
+    that implements the trait, so the song types only describe their own
+    fields and get lists and optional values for free.
+
+    Reading works on `&mut &[u8]`: each read_bytes call takes what it
+    needs from the front of the slice and moves the slice past it. Bad
+    input is an Err(DecodeError), never a panic -- the bytes might come
+    from a truncated or corrupted file.
+*/
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str;
+
+use error::{ErrContext, LectureError};
+use iterators::{SongName, SongNameError, SongUserProfile};
+use songs::Playlist;
+use utility_traits::{Seconds, Validated};
+
+pub trait ToBytes {
+    fn write_bytes(&self, out: &mut Vec<u8>);
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write_bytes(&mut out);
+        out
+    }
+}
+
+pub trait FromBytes: Sized {
+    fn read_bytes(input: &mut &[u8]) -> Result<Self, DecodeError>;
+
+    // The whole input must be exactly one value
+    fn from_bytes(mut bytes: &[u8]) -> Result<Self, DecodeError> {
+        let value = Self::read_bytes(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(DecodeError::TrailingBytes(bytes.len()));
+        }
+        Ok(value)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    UnexpectedEnd,
+    TrailingBytes(usize),
+    InvalidUtf8,
+    InvalidTag(u8),
+    TooLarge(u64),
+    InvalidSongName(SongNameError),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "input ended too soon"),
+            DecodeError::TrailingBytes(n) => {
+                write!(f, "{} unexpected bytes after the value", n)
+            }
+            DecodeError::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            DecodeError::InvalidTag(tag) => {
+                write!(f, "invalid tag byte {}", tag)
+            }
+            DecodeError::TooLarge(n) => {
+                write!(f, "{} doesn't fit in this platform's usize", n)
+            }
+            DecodeError::InvalidSongName(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DecodeError::InvalidSongName(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SongNameError> for DecodeError {
+    fn from(e: SongNameError) -> Self {
+        DecodeError::InvalidSongName(e)
+    }
+}
+
+// Splits `n` bytes off the front of the input
+fn take<'a>(input: &mut &'a [u8], n: usize) -> Result<&'a [u8], DecodeError> {
+    if input.len() < n {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let (head, rest) = input.split_at(n);
+    *input = rest;
+    Ok(head)
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32, DecodeError> {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(take(input, 4)?);
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn write_len(len: usize, out: &mut Vec<u8>) {
+    // Song data is nowhere near 4 GiB; anything that is is a bug
+    let len = u32::try_from(len).expect("length too large to encode");
+    out.extend_from_slice(&len.to_le_bytes());
+}
+
+/*
+    Building blocks
+*/
+
+impl ToBytes for u64 {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl FromBytes for u64 {
+    fn read_bytes(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(take(input, 8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+}
+
+impl ToBytes for usize {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        (*self as u64).write_bytes(out);
+    }
+}
+
+impl FromBytes for usize {
+    fn read_bytes(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let n = u64::read_bytes(input)?;
+        if n > usize::MAX as u64 {
+            return Err(DecodeError::TooLarge(n));
+        }
+        Ok(n as usize)
+    }
+}
+
+impl ToBytes for str {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        write_len(self.len(), out);
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl ToBytes for String {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        self.as_str().write_bytes(out);
+    }
+}
+
+impl FromBytes for String {
+    fn read_bytes(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let len = read_u32(input)? as usize;
+        let bytes = take(input, len)?;
+        let s = str::from_utf8(bytes).map_err(|_| DecodeError::InvalidUtf8)?;
+        Ok(s.to_string())
+    }
+}
+
+impl<T: ToBytes> ToBytes for [T] {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        write_len(self.len(), out);
+        for item in self {
+            item.write_bytes(out);
+        }
+    }
+}
+
+impl<T: ToBytes> ToBytes for Vec<T> {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        self.as_slice().write_bytes(out);
+    }
+}
+
+impl<T: FromBytes> FromBytes for Vec<T> {
+    fn read_bytes(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let count = read_u32(input)? as usize;
+        // Not Vec::with_capacity(count): a corrupted count could ask for
+        // gigabytes before we find out the data isn't there
+        let mut items = Vec::new();
+        for _ in 0..count {
+            items.push(T::read_bytes(input)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T: ToBytes> ToBytes for Option<T> {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.write_bytes(out);
+            }
+        }
+    }
+}
+
+impl<T: FromBytes> FromBytes for Option<T> {
+    fn read_bytes(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        match take(input, 1)?[0] {
+            0 => Ok(None),
+            1 => Ok(Some(T::read_bytes(input)?)),
+            tag => Err(DecodeError::InvalidTag(tag)),
+        }
+    }
+}
+
+impl ToBytes for Seconds {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        self.0.write_bytes(out);
+    }
+}
+
+impl FromBytes for Seconds {
+    fn read_bytes(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(Seconds(u64::read_bytes(input)?))
+    }
+}
+
+/*
+    The song types
+
+    A SongName is written as its string, and read back through
+    SongName::new, so bytes can't smuggle in a name the constructor would
+    reject. A Playlist is its (name, duration) pairs; its owner is a
+    runtime link to a live profile and isn't saved.
+*/
+
+impl ToBytes for SongName {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        self.as_str().write_bytes(out);
+    }
+}
+
+impl FromBytes for SongName {
+    fn read_bytes(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(SongName::new(String::read_bytes(input)?)?)
+    }
+}
+
+impl ToBytes for Playlist {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        write_len(self.len(), out);
+        for (i, song) in self.iter().enumerate() {
+            song.write_bytes(out);
+            self.duration_of(i).write_bytes(out);
+        }
+    }
+}
+
+impl FromBytes for Playlist {
+    fn read_bytes(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let count = read_u32(input)?;
+        let mut playlist = Playlist::new();
+        for _ in 0..count {
+            let song = SongName::read_bytes(input)?;
+            match Option::<Seconds>::read_bytes(input)? {
+                Some(duration) => playlist.push_timed(song, duration),
+                None => playlist.push(song),
+            }
+        }
+        Ok(playlist)
+    }
+}
+
+impl ToBytes for SongUserProfile {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        self.username().write_bytes(out);
+        self.liked_songs().write_bytes(out);
+        self.disliked_songs().write_bytes(out);
+        self.total_listens().write_bytes(out);
+        self.days_active().write_bytes(out);
+    }
+}
+
+impl FromBytes for SongUserProfile {
+    fn read_bytes(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let mut profile = SongUserProfile::new(String::read_bytes(input)?);
+        for song in Vec::<SongName>::read_bytes(input)? {
+            profile.like(song);
+        }
+        for song in Vec::<SongName>::read_bytes(input)? {
+            profile.dislike(song);
+        }
+        profile.add_listens(usize::read_bytes(input)?);
+        profile.set_days_active(usize::read_bytes(input)?);
+        Ok(profile)
+    }
+}
+
+#[test]
+fn test_encoding_layout() {
+    assert_eq!(7u64.to_bytes(), [7, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!("hi".to_bytes(), [2, 0, 0, 0, b'h', b'i']);
+    assert_eq!(None::<u64>.to_bytes(), [0]);
+    assert_eq!(vec![Some(Seconds(1))].to_bytes().len(), 4 + 1 + 8);
+    assert_eq!(
+        Vec::<Option<u64>>::from_bytes(&[
+            2, 0, 0, 0, 0, 1, 5, 0, 0, 0, 0, 0, 0, 0
+        ]),
+        Ok(vec![None, Some(5)])
+    );
+}
+
+#[test]
+fn test_song_types_round_trip() {
+    use iterators::song;
+
+    let name = song("Here Comes the Sun");
+    assert_eq!(SongName::from_bytes(&name.to_bytes()), Ok(name));
+
+    let mut playlist = Playlist::new();
+    playlist.push_timed(song("Help!"), Seconds(139));
+    playlist.push(song("Yesterday"));
+    let decoded = Playlist::from_bytes(&playlist.to_bytes()).unwrap();
+    assert_eq!(decoded, playlist);
+    assert_eq!(decoded.duration_of(0), Some(Seconds(139)));
+    assert_eq!(decoded.duration_of(1), None);
+
+    let mut profile = SongUserProfile::new("alice");
+    profile.like(song("Help!"));
+    profile.like(song("Something"));
+    profile.dislike(song("Revolution 9"));
+    profile.add_listens(42);
+    profile.set_days_active(3);
+    let decoded = SongUserProfile::from_bytes(&profile.to_bytes()).unwrap();
+    assert_eq!(decoded.username(), "alice");
+    assert_eq!(decoded.liked_songs(), profile.liked_songs());
+    assert_eq!(decoded.disliked_songs(), profile.disliked_songs());
+    assert_eq!(decoded.total_listens(), 42);
+    assert_eq!(decoded.days_active(), 3);
+}
+
+#[test]
+fn test_decode_errors() {
+    use iterators::song;
+
+    let bytes = song("Help!").to_bytes();
+    assert_eq!(
+        SongName::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(DecodeError::UnexpectedEnd)
+    );
+    let mut extra = bytes.clone();
+    extra.push(0);
+    assert_eq!(
+        SongName::from_bytes(&extra),
+        Err(DecodeError::TrailingBytes(1))
+    );
+
+    // Valid strings that aren't valid song names
+    assert_eq!(
+        SongName::from_bytes(&"".to_bytes()),
+        Err(DecodeError::InvalidSongName(SongNameError::Empty))
+    );
+    assert_eq!(
+        String::from_bytes(&[2, 0, 0, 0, 0xff, 0xfe]),
+        Err(DecodeError::InvalidUtf8)
+    );
+    assert_eq!(
+        Option::<u64>::from_bytes(&[9]),
+        Err(DecodeError::InvalidTag(9))
+    );
+    // A huge count with nothing behind it fails cleanly
+    assert_eq!(
+        Vec::<u64>::from_bytes(&[0xff, 0xff, 0xff, 0xff]),
+        Err(DecodeError::UnexpectedEnd)
+    );
+}
+
+/*
+    Saving and loading profiles
+
+    The obvious way to save -- File::create(path) and write -- has a bad
+    failure mode: File::create truncates the old file first, so a crash
+    (or a full disk) halfway through leaves neither the old profile nor
+    the new one.
+
+    Instead we write the new bytes to a temporary file next to the real
+    one, flush them to disk with sync_all, and only then rename it over
+    the original. A rename within one directory is atomic: anyone
+    reading the path sees the complete old file or the complete new
+    one, never a mix. If anything fails before the rename, the temporary
+    file is removed and the original is untouched.
+
+    Errors come back as LectureError, so I/O failures and corrupt data
+    can both be handled (or ?-propagated) as one type.
+
+    Only a validated profile can be saved: save_to is a method of
+    Validated<SongUserProfile>, not of SongUserProfile, so a profile has
+    to pass validate() first (see "Validating profiles" in songs.rs).
+    Loading checks nothing beyond the format; call validate() on the
+    result if it matters.
+*/
+
+// "alice.profile" -> "alice.profile.tmp", in the same directory
+fn temp_sibling(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map_or_else(OsString::new, OsString::from);
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+pub fn save_atomically<T, P>(value: &T, path: P) -> Result<(), LectureError>
+where
+    T: ToBytes + ?Sized,
+    P: AsRef<Path>,
+{
+    fn inner(bytes: &[u8], path: &Path) -> Result<(), LectureError> {
+        let tmp = temp_sibling(path);
+        let written = File::create(&tmp).and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        });
+        let result = written.and_then(|()| fs::rename(&tmp, path));
+        if result.is_err() {
+            // Best effort: the original error is the one worth reporting
+            let _ = fs::remove_file(&tmp);
+        }
+        Ok(result.with_context(|| format!("saving {}", path.display()))?)
+    }
+    inner(&value.to_bytes(), path.as_ref())
+}
+
+pub fn load<T: FromBytes, P: AsRef<Path>>(path: P) -> Result<T, LectureError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path)
+        .with_context(|| format!("reading {}", path.display()))?;
+    Ok(T::from_bytes(&bytes)
+        .with_context(|| format!("decoding {}", path.display()))?)
+}
+
+impl Validated<SongUserProfile> {
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> Result<(), LectureError> {
+        save_atomically(&**self, path)
+    }
+}
+
+impl SongUserProfile {
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self, LectureError> {
+        load(path)
+    }
+}
+
+#[test]
+fn test_profile_save_and_load() {
+    use iterators::song;
+    use utility_traits::{temp_path, Validate};
+
+    let path = temp_path("alice.profile");
+    let mut alice = SongUserProfile::new("alice");
+    alice.like(song("Help!"));
+    alice.add_listens(5);
+    let valid = alice.validate().unwrap();
+    valid.save_to(&path).unwrap();
+
+    // Overwriting replaces the whole file and leaves no temp file behind
+    let mut alice = valid.into_inner();
+    alice.like(song("Yesterday"));
+    alice.clone().validate().unwrap().save_to(path.to_str().unwrap()).unwrap();
+    assert!(!temp_sibling(&path).exists());
+
+    let loaded = SongUserProfile::load_from(&path).unwrap();
+    assert_eq!(loaded.username(), "alice");
+    assert_eq!(loaded.liked_songs(), alice.liked_songs());
+    assert_eq!(loaded.total_listens(), 5);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_profile_save_and_load_errors() {
+    use error::report;
+    use std::io;
+    use utility_traits::{temp_path, Validate};
+
+    let missing = SongUserProfile::load_from(temp_path("nobody.profile"));
+    let missing = missing.unwrap_err();
+    assert!(matches!(
+        missing.without_context(),
+        LectureError::Io(e) if e.kind() == io::ErrorKind::NotFound
+    ));
+
+    // Truncated data is reported, not panicked on
+    let bytes = SongUserProfile::new("bob").to_bytes();
+    let path = temp_path("truncated.profile");
+    fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+    let err = SongUserProfile::load_from(&path).unwrap_err();
+    assert_eq!(
+        report(&err),
+        format!(
+            "decoding {}: invalid value: input ended too soon",
+            path.display()
+        )
+    );
+    let decode = err.without_context().source().unwrap();
+    assert_eq!(
+        decode.downcast_ref::<DecodeError>(),
+        Some(&DecodeError::UnexpectedEnd)
+    );
+    fs::remove_file(&path).unwrap();
+
+    // Saving into a directory that doesn't exist fails cleanly
+    let nowhere = temp_path("no-such-dir").join("x.profile");
+    let carol = SongUserProfile::new("carol").validate().unwrap();
+    let err = carol.save_to(&nowhere).unwrap_err();
+    assert!(err.to_string().starts_with("saving "));
+    assert!(matches!(err.without_context(), LectureError::Io(_)));
+    assert!(!temp_sibling(&nowhere).exists());
+}
+
+#[test]
+fn test_only_valid_profiles_are_saved() {
+    use iterators::song;
+    use songs::ProfileError;
+    use utility_traits::{temp_path, Validate};
+
+    // `torn.save_to(..)` doesn't compile: there's no save_to on a plain
+    // SongUserProfile. The only way to one is through validate()...
+    let mut torn = SongUserProfile::new("dave");
+    torn.like(song("Help!"));
+    torn.dislike(song("Help!"));
+    let path = temp_path("torn.profile");
+    match torn.validate() {
+        Ok(valid) => valid.save_to(&path).unwrap(),
+        Err(e) => {
+            assert_eq!(e, ProfileError::LikedAndDisliked(song("Help!")))
+        }
+    }
+    // ...which refused, so nothing was written
+    assert!(!path.exists());
+}
@@ -0,0 +1,383 @@
+/*
+    A crate-wide error type
+
+    Each fallible API here started out with its own error type
+    (CsvError, SongNameError, PercentOutOfRange...). That's right for the
+    API itself, but a function that reads a file, parses numbers *and*
+    validates song names can only return one error type.
+
+    LectureError is that type: one variant per kind of failure, each
+    keeping the original error so nothing is lost. The From impls are
+    what make `?` work -- on an Err(e), `?` returns Err(From::from(e)),
+    converting to the function's error type on the way out.
+
+    source() exposes the wrapped error, so callers (and error reporters)
+    can walk the chain down to the root cause. Because of that, Display
+    only names the kind of failure ("parse error"), not the wrapped
+    error's message as well: a reporter that prints every link would
+    otherwise show each message twice. report() prints the whole chain
+    on one line.
+
+    The Context variant adds a layer on top of any of the others; see
+    "Adding context" below.
+*/
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::num::ParseIntError;
+use std::path::Path;
+
+use config::ConfigError;
+use encoding::DecodeError;
+use iterators::SongNameError;
+use json::JsonError;
+use parsing::{CsvError, ParseError};
+use utility_traits::{self, EmptyVecError, PercentOutOfRange};
+
+#[derive(Debug)]
+pub enum LectureError {
+    Io(io::Error),
+    Parse(ParseIntError),
+    EmptyInput,
+    Validation(Box<dyn Error + Send + Sync>),
+    Context(Box<Contextual<LectureError>>),
+}
+
+impl fmt::Display for LectureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LectureError::Io(_) => write!(f, "I/O error"),
+            LectureError::Parse(_) => write!(f, "parse error"),
+            LectureError::EmptyInput => write!(f, "input was empty"),
+            LectureError::Validation(_) => write!(f, "invalid value"),
+            LectureError::Context(c) => write!(f, "{}", c.context()),
+        }
+    }
+}
+
+impl Error for LectureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LectureError::Io(e) => Some(e),
+            LectureError::Parse(e) => Some(e),
+            LectureError::EmptyInput => None,
+            LectureError::Validation(e) => Some(&**e),
+            // Straight to the wrapped error: the Contextual itself would
+            // just repeat the context
+            LectureError::Context(c) => Some(c.inner()),
+        }
+    }
+}
+
+impl From<io::Error> for LectureError {
+    fn from(e: io::Error) -> Self {
+        LectureError::Io(e)
+    }
+}
+
+impl From<ParseIntError> for LectureError {
+    fn from(e: ParseIntError) -> Self {
+        LectureError::Parse(e)
+    }
+}
+
+impl From<EmptyVecError> for LectureError {
+    fn from(_: EmptyVecError) -> Self {
+        LectureError::EmptyInput
+    }
+}
+
+impl From<SongNameError> for LectureError {
+    fn from(e: SongNameError) -> Self {
+        LectureError::Validation(Box::new(e))
+    }
+}
+
+impl From<PercentOutOfRange> for LectureError {
+    fn from(e: PercentOutOfRange) -> Self {
+        LectureError::Validation(Box::new(e))
+    }
+}
+
+impl From<ConfigError> for LectureError {
+    fn from(e: ConfigError) -> Self {
+        LectureError::Validation(Box::new(e))
+    }
+}
+
+impl From<DecodeError> for LectureError {
+    fn from(e: DecodeError) -> Self {
+        LectureError::Validation(Box::new(e))
+    }
+}
+
+impl From<JsonError> for LectureError {
+    fn from(e: JsonError) -> Self {
+        LectureError::Validation(Box::new(e))
+    }
+}
+
+impl From<ParseError> for LectureError {
+    fn from(e: ParseError) -> Self {
+        LectureError::Validation(Box::new(e))
+    }
+}
+
+// A CSV reader can fail to read as well as to parse; keep the two apart
+impl From<CsvError> for LectureError {
+    fn from(e: CsvError) -> Self {
+        match e {
+            CsvError::Io(e) => LectureError::Io(e),
+            e => LectureError::Validation(Box::new(e)),
+        }
+    }
+}
+
+/*
+    Adding context
+
+    "No such file or directory" is true, but which file? An io::Error
+    doesn't know what the program was trying to do when it happened;
+    only the caller does. Contextual<E> wraps an error together with that
+    missing piece, and report() puts the two together:
+
+        reading songs.txt: No such file or directory (os error 2)
+
+    The ErrContext extension trait (compare MyIteratorExt in adapters.rs)
+    adds .context(msg) and .with_context(|| msg) to every Result whose
+    error implements Error. with_context takes a closure so the message
+    -- often a format!() -- is only built if there actually is an error.
+
+    The original error isn't flattened into the message -- a Contextual
+    displays as just its context -- but source() returns it, so code (and
+    tests) can check exactly what went wrong.
+    A Contextual<E> converts into LectureError whenever E does, and
+    without_context() peels the layers off again.
+*/
+
+#[derive(Debug)]
+pub struct Contextual<E> {
+    context: String,
+    source: E,
+}
+
+impl<E> Contextual<E> {
+    pub fn new<C: Into<String>>(context: C, source: E) -> Self {
+        Contextual { context: context.into(), source }
+    }
+
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    pub fn inner(&self) -> &E {
+        &self.source
+    }
+
+    pub fn into_inner(self) -> E {
+        self.source
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Contextual<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.context)
+    }
+}
+
+impl<E: Error + 'static> Error for Contextual<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+pub trait ErrContext<T, E> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T, Contextual<E>>;
+
+    fn with_context<C, F>(self, f: F) -> Result<T, Contextual<E>>
+    where
+        C: Into<String>,
+        F: FnOnce() -> C;
+}
+
+impl<T, E: Error> ErrContext<T, E> for Result<T, E> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T, Contextual<E>> {
+        self.map_err(|e| Contextual::new(context, e))
+    }
+
+    fn with_context<C, F>(self, f: F) -> Result<T, Contextual<E>>
+    where
+        C: Into<String>,
+        F: FnOnce() -> C,
+    {
+        self.map_err(|e| Contextual::new(f(), e))
+    }
+}
+
+impl<E: Into<LectureError>> From<Contextual<E>> for LectureError {
+    fn from(c: Contextual<E>) -> Self {
+        let Contextual { context, source } = c;
+        LectureError::Context(Box::new(Contextual::new(context, source.into())))
+    }
+}
+
+impl LectureError {
+    // The error underneath any number of Context layers
+    pub fn without_context(&self) -> &LectureError {
+        match self {
+            LectureError::Context(c) => c.inner().without_context(),
+            other => other,
+        }
+    }
+}
+
+// The whole chain, outermost first: "reading x: I/O error: not found"
+pub fn report(err: &dyn Error) -> String {
+    let mut message = err.to_string();
+    let mut current = err;
+    while let Some(next) = current.source() {
+        message.push_str(": ");
+        message.push_str(&next.to_string());
+        current = next;
+    }
+    message
+}
+
+#[test]
+fn test_contextual_chain() {
+    let parsed: Result<u8, _> = "300".parse::<u8>().context("reading volume");
+    let err = parsed.unwrap_err();
+    assert_eq!(err.context(), "reading volume");
+    assert_eq!(err.to_string(), "reading volume");
+    assert_eq!(
+        report(&err),
+        "reading volume: number too large to fit in target type"
+    );
+    assert!(err.source().unwrap().is::<std::num::ParseIntError>());
+
+    // The closure only runs on the error path
+    let mut calls = 0;
+    let ok: Result<u8, _> = "7".parse::<u8>().with_context(|| {
+        calls += 1;
+        "never built"
+    });
+    assert_eq!(ok.unwrap(), 7);
+    assert_eq!(calls, 0);
+}
+
+#[test]
+fn test_lecture_error_context_layers() {
+    fn step() -> Result<i64, LectureError> {
+        Ok("x".parse::<i64>().context("line 3")?)
+    }
+    fn outer() -> Result<i64, LectureError> {
+        Ok(step().context("loading scores")?)
+    }
+    let err = outer().unwrap_err();
+    assert_eq!(err.to_string(), "loading scores");
+    assert_eq!(
+        report(&err),
+        "loading scores: line 3: parse error: invalid digit found in string"
+    );
+    assert!(matches!(err.without_context(), LectureError::Parse(_)));
+
+    // source() walks down one layer at a time to the ParseIntError, and
+    // no message appears twice along the way
+    let mut chain = vec![err.to_string()];
+    let mut current: &dyn Error = &err;
+    while let Some(next) = current.source() {
+        chain.push(next.to_string());
+        current = next;
+    }
+    assert_eq!(
+        chain,
+        vec![
+            "loading scores",
+            "line 3",
+            "parse error",
+            "invalid digit found in string"
+        ]
+    );
+}
+
+/*
+    `?` across error types
+
+    Sums the integers in a file, one per line (blank lines skipped).
+    Three different errors can happen, and each `?` converts its own.
+    I/O and parse errors also say which file (and line) they came from.
+*/
+
+pub fn sum_numbers_in<P: AsRef<Path>>(path: P) -> Result<i64, LectureError> {
+    let path = path.as_ref();
+    let text = utility_traits::read_to_string_from(path)
+        .with_context(|| format!("reading {}", path.display()))?;
+    let mut total = 0;
+    let mut count = 0;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        total += line
+            .parse::<i64>()
+            .with_context(|| format!("{}, line {}", path.display(), i + 1))?;
+        count += 1;
+    }
+    if count == 0 {
+        return Err(LectureError::EmptyInput);
+    }
+    Ok(total)
+}
+
+#[test]
+fn test_sum_numbers_in() {
+    use utility_traits::{temp_path, TempFile};
+
+    let good =
+        TempFile::new(temp_path("sum_good.txt"), "1\n2\n\n 39 \n").unwrap();
+    assert_eq!(sum_numbers_in(good.path()).unwrap(), 42);
+
+    let bad = TempFile::new(temp_path("sum_bad.txt"), "1\ntwo\n").unwrap();
+    let err = sum_numbers_in(bad.path()).unwrap_err();
+    assert!(matches!(err.without_context(), LectureError::Parse(_)));
+    assert_eq!(
+        report(&err),
+        format!(
+            "{}, line 2: parse error: invalid digit found in string",
+            bad.path().display()
+        )
+    );
+
+    let empty = TempFile::new(temp_path("sum_empty.txt"), "\n\n").unwrap();
+    assert!(matches!(
+        sum_numbers_in(empty.path()),
+        Err(LectureError::EmptyInput)
+    ));
+
+    let missing = sum_numbers_in(temp_path("sum_missing.txt")).unwrap_err();
+    assert!(missing.to_string().starts_with("reading "));
+    assert!(matches!(
+        missing.without_context(),
+        LectureError::Io(e) if e.kind() == io::ErrorKind::NotFound
+    ));
+}
+
+#[test]
+fn test_lecture_error_source() {
+    use iterators::SongName;
+
+    fn make_song(name: &str) -> Result<SongName, LectureError> {
+        Ok(SongName::new(name)?)
+    }
+    let err = make_song("").unwrap_err();
+    assert_eq!(err.to_string(), "invalid value");
+    assert_eq!(report(&err), "invalid value: song name is empty");
+    let source = err.source().unwrap();
+    assert_eq!(
+        source.downcast_ref::<SongNameError>(),
+        Some(&SongNameError::Empty)
+    );
+    assert!(LectureError::EmptyInput.source().is_none());
+}
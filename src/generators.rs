@@ -0,0 +1,299 @@
+/*
+    Generators: iterators that produce their own values
+
+    Everything in adapters.rs wraps another iterator. The iterators here
+    don't -- like (0..) or iter::repeat, they compute each item from
+    their own internal state. Many of them are infinite, so they're meant
+    to be combined with .take() / .take_while().
+*/
+
+/*
+    Fibonacci numbers
+
+    The state is just the current pair (a, b). Each call to .next()
+    yields a and moves to (b, a + b).
+
+    u128 overflows after the 186th Fibonacci number. Instead of
+    panicking (debug) or wrapping around (release), we use checked_add
+    and end the iterator after the last number that fits.
+*/
+
+pub struct Fibonacci {
+    // None once we've run out of representable numbers
+    state: Option<(u128, Option<u128>)>,
+}
+
+impl Iterator for Fibonacci {
+    type Item = u128;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (a, b) = self.state?;
+        self.state = b.map(|b| (b, a.checked_add(b)));
+        Some(a)
+    }
+}
+
+pub fn fibonacci() -> Fibonacci {
+    Fibonacci { state: Some((0, Some(1))) }
+}
+
+#[test]
+fn test_fibonacci() {
+    let first: Vec<u128> = fibonacci().take(10).collect();
+    assert_eq!(first, vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+
+    // Sum of the even Fibonacci numbers below four million
+    let even_sum: u128 =
+        fibonacci().take_while(|&x| x < 4_000_000).filter(|x| x % 2 == 0).sum();
+    assert_eq!(even_sum, 4_613_732);
+}
+
+#[test]
+fn test_fibonacci_terminates() {
+    // F(0) through F(186) fit in a u128
+    assert_eq!(fibonacci().count(), 187);
+    let last = fibonacci().last().unwrap();
+    assert_eq!(last, 332_825_110_087_067_562_321_196_029_789_634_457_848);
+}
+
+/*
+    Prime numbers
+
+    The Sieve of Eratosthenes needs to know the upper bound in advance.
+    Lazily, we don't know how many primes the caller will .take(), so
+    instead we remember every prime found so far and test each new
+    candidate against them: n is prime if no stored prime p with
+    p * p <= n divides it.
+
+    The Vec of found primes is the iterator's state -- it grows as we go.
+*/
+
+pub struct Primes {
+    found: Vec<u64>,
+}
+
+impl Iterator for Primes {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut candidate = match self.found.last() {
+            None => 2,
+            Some(2) => 3,
+            // Only odd numbers after 2
+            Some(&p) => p + 2,
+        };
+        while !self
+            .found
+            .iter()
+            .take_while(|&&p| p * p <= candidate)
+            .all(|&p| candidate % p != 0)
+        {
+            candidate += 2;
+        }
+        self.found.push(candidate);
+        Some(candidate)
+    }
+}
+
+pub fn primes() -> Primes {
+    Primes { found: Vec::new() }
+}
+
+#[test]
+fn test_primes() {
+    let first: Vec<u64> = primes().take(10).collect();
+    assert_eq!(first, vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+    assert_eq!(primes().nth(999), Some(7919));
+    assert_eq!(primes().take_while(|&p| p < 1000).sum::<u64>(), 76127);
+}
+
+#[test]
+fn test_sum_prime_squares_lt() {
+    // In the style of iterators::sum_squares_lt
+    let sum_prime_squares_lt =
+        |n: u64| primes().map(|p| p * p).take_while(|&x| x < n).sum::<u64>();
+    assert_eq!(sum_prime_squares_lt(50), 4 + 9 + 25 + 49);
+    assert_eq!(sum_prime_squares_lt(4), 0);
+}
+
+/*
+    Closure-driven iterators
+
+    Writing a struct + impl Iterator every time is a lot of boilerplate.
+    Since a closure can own (or borrow) its own data, a closure can *be*
+    the iterator state: FromFnIter just calls the closure on every
+    .next(). This is how std::iter::from_fn works.
+
+    Unfold makes the state explicit instead of hiding it inside the
+    closure: the iterator owns the state and lends it to the closure as
+    &mut on every call.
+*/
+
+pub struct FromFnIter<F> {
+    f: F,
+}
+
+impl<T, F> Iterator for FromFnIter<F>
+where
+    F: FnMut() -> Option<T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        (self.f)()
+    }
+}
+
+pub fn from_fn<T, F>(f: F) -> FromFnIter<F>
+where
+    F: FnMut() -> Option<T>,
+{
+    FromFnIter { f }
+}
+
+pub struct Unfold<St, F> {
+    state: St,
+    f: F,
+}
+
+impl<St, T, F> Iterator for Unfold<St, F>
+where
+    F: FnMut(&mut St) -> Option<T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        (self.f)(&mut self.state)
+    }
+}
+
+pub fn unfold<St, T, F>(state: St, f: F) -> Unfold<St, F>
+where
+    F: FnMut(&mut St) -> Option<T>,
+{
+    Unfold { state, f }
+}
+
+#[test]
+fn test_from_fn() {
+    // The counter lives inside the closure (moved in)
+    let mut count = 0;
+    let counter = from_fn(move || {
+        count += 1;
+        if count <= 3 {
+            Some(count)
+        } else {
+            None
+        }
+    });
+    assert_eq!(counter.collect::<Vec<_>>(), vec![1, 2, 3]);
+
+    // Or it can borrow from the environment
+    let words = ["a", "b", "c"];
+    let mut i = 0;
+    let it = from_fn(|| {
+        let w = words.get(i)?;
+        i += 1;
+        Some(w.to_uppercase())
+    });
+    assert_eq!(it.collect::<Vec<_>>(), vec!["A", "B", "C"]);
+}
+
+#[test]
+fn test_unfold() {
+    // Fibonacci again, with the pair as explicit state
+    let fib = unfold((0u64, 1u64), |state| {
+        let (a, b) = *state;
+        *state = (b, a + b);
+        Some(a)
+    });
+    assert_eq!(fib.take(7).collect::<Vec<_>>(), vec![0, 1, 1, 2, 3, 5, 8]);
+
+    // Digits of a number, least significant first
+    let digits = unfold(1234u32, |n| {
+        if *n == 0 {
+            None
+        } else {
+            let d = *n % 10;
+            *n /= 10;
+            Some(d)
+        }
+    });
+    assert_eq!(digits.collect::<Vec<_>>(), vec![4, 3, 2, 1]);
+}
+
+/*
+    Recurrences
+
+    iterate(seed, f) yields seed, f(seed), f(f(seed)), ... and stops as
+    soon as f returns None. (std calls this iter::successors.)
+
+    f gets a reference to the previous item because we've already handed
+    that item out by value -- we can't give it to f as well.
+*/
+
+pub struct Iterate<T, F> {
+    next: Option<T>,
+    f: F,
+}
+
+impl<T, F> Iterator for Iterate<T, F>
+where
+    F: FnMut(&T) -> Option<T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.next.take()?;
+        self.next = (self.f)(&item);
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.next.is_some() {
+            (1, None)
+        } else {
+            (0, Some(0))
+        }
+    }
+}
+
+pub fn iterate<T, F>(seed: T, f: F) -> Iterate<T, F>
+where
+    F: FnMut(&T) -> Option<T>,
+{
+    Iterate { next: Some(seed), f }
+}
+
+#[test]
+fn test_iterate_sum_squares_lt() {
+    use iterators::sum_squares_lt;
+    // (0..) is just iterate(0, |x| Some(x + 1))
+    let sum_squares = |n| {
+        iterate(0, |&x| Some(x + 1)).map(|x| x * x).take_while(move |&x| x < n)
+    };
+    for &n in &[0, 1, 4, 5, 30, 1000] {
+        assert_eq!(sum_squares(n).sum::<usize>(), sum_squares_lt(n));
+    }
+}
+
+#[test]
+fn test_iterate_collatz() {
+    let collatz = |n: u64| {
+        iterate(n, |&x| match x {
+            1 => None,
+            x if x % 2 == 0 => Some(x / 2),
+            x => Some(3 * x + 1),
+        })
+    };
+    assert_eq!(
+        collatz(6).collect::<Vec<_>>(),
+        vec![6, 3, 10, 5, 16, 8, 4, 2, 1]
+    );
+    assert_eq!(collatz(1).collect::<Vec<_>>(), vec![1]);
+    assert_eq!(collatz(27).count(), 112);
+    assert_eq!(collatz(27).max(), Some(9232));
+
+    // Powers of two until overflow
+    assert_eq!(iterate(1u8, |&x| x.checked_mul(2)).count(), 8);
+}
@@ -4,7 +4,16 @@
     Recall that every for loop is internally an iterator:
 */
 
-use std::iter;
+use std::borrow::Borrow;
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::iter::{self, FromIterator};
+use std::str::FromStr;
+
+use num::Num;
+use utility_traits::Immutable;
 
 pub fn example_for() {
     let v = vec![1, 2, 3];
@@ -13,6 +22,7 @@ pub fn example_for() {
     }
 }
 
+#[allow(clippy::useless_vec)]
 pub fn example_iter() {
     let v = vec![1, 2, 3];
     for x in v.iter() {
@@ -23,6 +33,7 @@ pub fn example_iter() {
 // More explicitly...
 // Iterators are objects which expose a .next() method.
 // This returns either Some(next_val) or None.
+#[allow(clippy::useless_vec)]
 pub fn example_iter_explicit1() {
     let v = vec![1, 2, 3];
     let mut iterator = v.iter();
@@ -37,6 +48,7 @@ pub fn example_iter_explicit1() {
 
 // And we can also use .next() directly, not in a loop
 #[test]
+#[allow(clippy::useless_vec)]
 pub fn example_iter_explicit2() {
     let v = vec![1, 2, 3];
     let mut iterator = v.iter();
@@ -105,28 +117,55 @@ pub fn example_into_iter() {
     5. Sum all the squares less than n
 */
 
-pub fn all_below(v: Vec<usize>, thresh: usize) -> bool {
+// Generic version: any collection (or iterator), anything comparable to the
+// threshold. Taking impl IntoIterator means callers can pass a Vec by value,
+// a &Vec or slice (comparing references), or a lazy iterator, without
+// cloning anything first.
+pub fn all_below<T, U>(items: impl IntoIterator<Item = T>, thresh: U) -> bool
+where
+    T: PartialOrd<U>,
+{
     // Could write a for loop...
     // Could also write it in a functional style with iterators
-    v.iter().all(|&ele| ele < thresh)
+    items.into_iter().all(|ele| ele < thresh)
 }
 
 #[test]
 fn test_all_below() {
     assert!(all_below(vec![0, 1, 3], 4));
     assert!(!all_below(vec![0, 1, 3], 3));
+
+    // Borrowing instead of moving: the Vec is still usable afterwards
+    let v = vec![1.5, 2.5];
+    assert!(all_below(&v, &3.0));
+    assert!(all_below(v.iter().map(|x| x * 2.0), 6.0));
+    assert!(all_below(vec!["apple", "banana"], "cherry"));
+    assert!(all_below(Vec::<u8>::new(), 0));
 }
 
-pub fn filter_below(v: Vec<usize>, thresh: usize) -> Vec<usize> {
+pub fn filter_below<T, U>(
+    items: impl IntoIterator<Item = T>,
+    thresh: U,
+) -> Vec<T>
+where
+    T: PartialOrd<U>,
+{
     // This will fail -- I'm returning an iterator instead of a vector
-    // v.iter().filter(|&ele| ele < thresh)
+    // items.into_iter().filter(|ele| *ele < thresh)
 
     // Fix: "collect" the iterator into a vector
-    // Unfortunately, doubule references come up when using .filter()
-    // v.iter().filter(|&&ele| ele < thresh).copied().collect()
+    // .filter() passes the closure a reference &T, hence the *.
+    // (With v.iter() over a Vec<usize> that was a double reference &&usize,
+    // which is why the old version called .copied() first.)
+    items.into_iter().filter(|ele| *ele < thresh).collect()
+}
 
-    // Alternative
-    v.iter().copied().filter(|&ele| ele < thresh).collect()
+#[test]
+fn test_filter_below() {
+    assert_eq!(filter_below(vec![5, 1, 4, 2], 3), vec![1, 2]);
+    let words = ["pear", "fig", "kiwi"];
+    assert_eq!(filter_below(words.iter(), &"kiwi"), vec![&"fig"]);
+    assert_eq!(filter_below(0..10, 3), vec![0, 1, 2]);
 }
 
 // Reminder: .copied() creates an iterator of values T from an iterator of
@@ -136,6 +175,7 @@ pub fn filter_below(v: Vec<usize>, thresh: usize) -> Vec<usize> {
     ========== Start of Lecture 7 Part 3 (short additional video) ==========
 */
 
+#[allow(clippy::manual_repeat_n)]
 pub fn copy_increasing(v: Vec<usize>) -> Vec<usize> {
     // [1, 2, 3].iter().flat_map(|...| {})
     v.iter()
@@ -162,6 +202,47 @@ pub fn pad_truncate(v: Vec<usize>, target_length: usize) -> Vec<usize> {
     result
 }
 
+// Generalization: any element type, and the padding can depend on the
+// position it's filling. iter::repeat(0) becomes (len..).map(fill):
+// the fill closure gets the index of each padded slot.
+pub fn pad_truncate_with<T: Clone>(
+    v: Vec<T>,
+    target_length: usize,
+    fill: impl Fn(usize) -> T,
+) -> Vec<T> {
+    let len = v.len();
+    let result: Vec<T> =
+        v.into_iter().chain((len..).map(fill)).take(target_length).collect();
+
+    debug_assert_eq!(result.len(), target_length);
+    result
+}
+
+#[test]
+fn test_pad_truncate_with() {
+    // Padding, with the fill value depending on the position
+    assert_eq!(
+        pad_truncate_with(vec![1, 2], 5, |i| i * 10),
+        vec![1, 2, 20, 30, 40]
+    );
+    // Truncating never calls fill
+    assert_eq!(
+        pad_truncate_with(vec![1, 2, 3, 4], 2, |_| unreachable!()),
+        vec![1, 2]
+    );
+    // Exact length: unchanged
+    assert_eq!(pad_truncate_with(vec!['a', 'b'], 2, |_| 'z'), vec!['a', 'b']);
+    // Non-Copy element type
+    let padded =
+        pad_truncate_with(vec!["x".to_string()], 3, |i| format!("pad{}", i));
+    assert_eq!(padded, vec!["x", "pad1", "pad2"]);
+    // Agrees with pad_truncate
+    assert_eq!(
+        pad_truncate_with(vec![7, 8], 4, |_| 0),
+        pad_truncate(vec![7, 8], 4)
+    );
+}
+
 pub fn sum_squares_lt(n: usize) -> usize {
     // (0..) : an iterator over all nonnegative integers
     // Note: .filter instead of .take_while leads to infinite loop :(
@@ -175,6 +256,48 @@ fn test_sum_squares_lt() {
     assert_eq!(sum_squares_lt(30), 1 + 4 + 9 + 16 + 25);
 }
 
+// The same thing for any numeric type (see num.rs). (0..) only works for
+// integer types, so we count up with iter::successors instead.
+// (Beware huge float thresholds: past 2^53, x + 1.0 == x for an f64, and
+// the count never advances.)
+pub fn sum_squares_lt_generic<N: Num>(n: N) -> N {
+    iter::successors(Some(N::zero()), |&x| Some(x + N::one()))
+        .map(|x| x * x)
+        .take_while(|&x| x < n)
+        .fold(N::zero(), |acc, x| acc + x)
+}
+
+// Overflow-checked: None if any square or partial sum overflows N.
+// try_fold stops at the first None.
+pub fn checked_sum_squares_lt<N: Num>(n: N) -> Option<N> {
+    iter::successors(Some(N::zero()), |&x| x.checked_add(N::one()))
+        .map(|x| x.checked_mul(x))
+        // Keep the overflowed (None) squares so try_fold can report them
+        .take_while(|sq| sq.is_none_or(|sq| sq < n))
+        .try_fold(N::zero(), |acc, sq| acc.checked_add(sq?))
+}
+
+#[test]
+fn test_sum_squares_lt_generic() {
+    for &n in &[0, 4, 5, 30, 1000] {
+        assert_eq!(sum_squares_lt_generic(n as u64), sum_squares_lt(n) as u64);
+    }
+    assert_eq!(sum_squares_lt_generic(30i128), 55);
+    assert_eq!(sum_squares_lt_generic(30.0f64), 55.0);
+    // For floats the threshold can be fractional
+    assert_eq!(sum_squares_lt_generic(4.5f64), 5.0);
+}
+
+#[test]
+fn test_checked_sum_squares_lt() {
+    assert_eq!(checked_sum_squares_lt(30u64), Some(55));
+    // 0 + 1 + 4 + ... + 64 = 204 fits in a u8, but adding 81 doesn't
+    assert_eq!(checked_sum_squares_lt(65u8), Some(204));
+    assert_eq!(checked_sum_squares_lt(82u8), None);
+    assert_eq!(checked_sum_squares_lt(u8::MAX), None);
+    assert_eq!(checked_sum_squares_lt(10.0f32), Some(14.0));
+}
+
 // Recap: particularly useful methods in the Iterator trait are:
 //     .map(), .take(), .take_while(), .filter()
 
@@ -195,7 +318,7 @@ fn test_sum_squares_lt() {
     Example return type in copy_increasing:
         Copied<FlatMap<Enumerate<std::slice::Iter<'_, usize>>,
         std::iter::Take<std::iter::Repeat<&usize>>,
-        [closure@src/iterators.rs:142:19: 144:10]>>
+        [closure@src/iterators.rs:183:19: 183:54]>>
     D:
 
     - Here the impl Trait syntax comes in handy again.
@@ -203,6 +326,7 @@ fn test_sum_squares_lt() {
     Instead: we just write 'impl Iterator<Item = usize>'
 */
 
+#[allow(clippy::manual_repeat_n)]
 pub fn copy_increasing_iter1(v: &[usize]) -> impl Iterator<Item = usize> + '_ {
     v.iter().enumerate().flat_map(|(i, ele)| iter::repeat(ele).take(i)).copied()
 }
@@ -211,6 +335,7 @@ pub fn copy_increasing_iter1(v: &[usize]) -> impl Iterator<Item = usize> + '_ {
 // So we use '_ to tell Rust to figure out the appropriate lifetime.
 // Alternatively we can write a function where both input/output are iterators:
 
+#[allow(clippy::manual_repeat_n)]
 pub fn copy_increasing_iter2(
     v: impl Iterator<Item = usize>,
 ) -> impl Iterator<Item = usize> {
@@ -221,6 +346,53 @@ pub fn copy_increasing_iter2(
 // T: Iterator<Item = usize>,
 // but this doesn't work for the return type, as we saw with Fn traits.
 
+// Nothing above really needs usize: the same pipeline works for any
+// Clone type (we need Clone to repeat an item), taking any IntoIterator.
+pub fn repeat_increasing<T: Clone>(
+    items: impl IntoIterator<Item = T>,
+) -> impl Iterator<Item = T> {
+    repeat_increasing_by(items, |i, _| i)
+}
+
+// And the repeat count can be any function of the position and the item
+pub fn repeat_increasing_by<T: Clone>(
+    items: impl IntoIterator<Item = T>,
+    count_fn: impl Fn(usize, &T) -> usize,
+) -> impl Iterator<Item = T> {
+    items.into_iter().enumerate().flat_map(move |(i, ele)| {
+        let count = count_fn(i, &ele);
+        iter::repeat_n(ele, count)
+    })
+}
+
+#[test]
+fn test_repeat_increasing() {
+    let v = vec![5, 6, 7];
+    let generic: Vec<usize> = repeat_increasing(v.clone()).collect();
+    assert_eq!(generic, copy_increasing(v));
+
+    let words: Vec<String> = repeat_increasing(vec![
+        "a".to_string(),
+        "b".to_string(),
+        "c".to_string(),
+    ])
+    .collect();
+    assert_eq!(words, vec!["b", "c", "c"]);
+}
+
+#[test]
+fn test_repeat_increasing_by() {
+    // Repeat each word as many times as it has letters
+    let out: Vec<&str> =
+        repeat_increasing_by(vec!["hi", "a", "hey"], |_, w| w.len()).collect();
+    assert_eq!(out, vec!["hi", "hi", "a", "hey", "hey", "hey"]);
+
+    // Position-based: the first item once, the second twice, ...
+    let out: String =
+        repeat_increasing_by("xyz".chars(), |i, _| i + 1).collect();
+    assert_eq!(out, "xyyzzz");
+}
+
 /*
     Writing iterators for your own data types
 
@@ -239,15 +411,15 @@ pub fn copy_increasing_iter2(
     Example:
 */
 
-#[derive(Clone, Debug)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SongName(String);
 
-#[derive(Debug)]
 pub struct SongUserProfile {
     username: String,
     liked_songs: Vec<SongName>,
     disliked_songs: Vec<SongName>,
-    listens: usize,
+    // A Cell so that playing songs (through &self) can count listens
+    listens: Cell<usize>,
     days_active: usize,
 }
 impl SongUserProfile {
@@ -261,7 +433,9 @@ impl SongUserProfile {
         // Simple idea: return an iterator over liked songs
         // self.liked_songs.iter() gives an iterator over references
         // So we want to call .cloned() to get an iterator over values
-        self.liked_songs.iter().cloned()
+        // Each song actually played (pulled from the iterator) counts as
+        // a listen -- see "Counting listens" below
+        self.liked_songs.iter().cloned().inspect(move |_| self.record_listen())
 
         // This is a finite iterator; we could also play songs repeatedly:
         // self.liked_songs.iter().cloned().cycle()
@@ -309,6 +483,7 @@ impl<'a> Iterator for SongIterator<'a> {
 // a .iter() method for
 
 impl SongUserProfile {
+    #[allow(clippy::needless_borrow, mismatched_lifetime_syntaxes)]
     pub fn get_iter(&self) -> SongIterator {
         SongIterator { user_profile: &self, current_song_index: 0 }
     }
@@ -328,3 +503,610 @@ impl SongUserProfile {
     This is the general recipe for implementing an iterator over a custom
     data structure.
 */
+
+/*
+    Standard traits for SongName
+
+    Wrapping a String in a newtype keeps song names from being mixed up
+    with usernames, but it also hides everything String could do. We get
+    the useful parts back one trait at a time:
+
+    - Display:               println!("{}", name), name.to_string()
+    - FromStr:               "Hey Jude".parse::<SongName>()
+    - AsRef<str>:            pass a SongName to anything taking
+                             impl AsRef<str>
+    - TryFrom<&str/String>:  SongName::try_from("x"), or .try_into()
+    - PartialEq/Eq:          compare names with ==
+    - PartialOrd/Ord:        sort names, use them as BTreeMap keys
+    - Hash:                  use them in a HashSet/HashMap
+
+    The last three are derived, so they just compare/hash the inner
+    String: ordering is by bytes, so "Zebra" < "apple". That's fine for
+    sets and maps, which only need *some* consistent order.
+
+    Debug stays derived, so {:?} shows SongName("...") -- handy for telling
+    a song name apart from a plain string in test output.
+
+    Validation
+
+    Not every string is a sensible song name, and a newtype is the place
+    to enforce that: if the only ways to make a SongName check the
+    string, then every SongName anywhere in the program is valid, and no
+    other code has to check again.
+
+    That rules out From<&str> and From<String>: From can't fail. (It's
+    not even allowed alongside TryFrom -- std's blanket impl already
+    derives TryFrom from any From, and the two impls would overlap.)
+    new(), parse() and both try_from()s all go through validate().
+
+    Sometimes the caller already knows the text is fine -- it came out
+    of a SongName, or a parser that only yields words -- and a Result
+    would just be unwrapped. from_valid() is the infallible constructor
+    for that:
+
+        words.map(SongName::from_valid).collect::<SongUserProfile>()
+
+    It still runs validate(), in every build, and panics on a bad name
+    the way .expect() would. Skipping the check would let an invalid
+    SongName exist, and code like normalize_in_place relies on there
+    being none.
+*/
+
+pub const MAX_SONG_NAME_LEN: usize = 200;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SongNameError {
+    // Empty, or only whitespace
+    Empty,
+    // Length in chars
+    TooLong { len: usize },
+    ControlChar { ch: char, index: usize },
+}
+
+impl fmt::Display for SongNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SongNameError::Empty => write!(f, "song name is empty"),
+            SongNameError::TooLong { len } => write!(
+                f,
+                "song name is {} characters long (max {})",
+                len, MAX_SONG_NAME_LEN
+            ),
+            SongNameError::ControlChar { ch, index } => write!(
+                f,
+                "song name contains control character {:?} at {}",
+                ch, index
+            ),
+        }
+    }
+}
+
+impl Error for SongNameError {}
+
+impl SongName {
+    fn validate(name: &str) -> Result<(), SongNameError> {
+        if name.trim().is_empty() {
+            return Err(SongNameError::Empty);
+        }
+        if let Some((index, ch)) =
+            name.char_indices().find(|(_, c)| c.is_control())
+        {
+            return Err(SongNameError::ControlChar { ch, index });
+        }
+        let len = name.chars().count();
+        if len > MAX_SONG_NAME_LEN {
+            return Err(SongNameError::TooLong { len });
+        }
+        Ok(())
+    }
+
+    pub fn new<S: Into<String>>(name: S) -> Result<Self, SongNameError> {
+        let name = name.into();
+        SongName::validate(&name)?;
+        Ok(SongName(name))
+    }
+
+    // Panics if the name is invalid; see "Validation" above
+    pub fn from_valid<S: Into<String>>(name: S) -> Self {
+        let name = name.into();
+        if let Err(e) = SongName::validate(&name) {
+            panic!("SongName::from_valid({:?}): {}", name, e);
+        }
+        SongName(name)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for SongName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for SongName {
+    type Err = SongNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SongName::new(s)
+    }
+}
+
+impl AsRef<str> for SongName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Correct because Eq, Ord and Hash (derived below) all just use the
+// inner String, which compares and hashes exactly like the str
+impl Borrow<str> for SongName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'a> TryFrom<&'a str> for SongName {
+    type Error = SongNameError;
+
+    fn try_from(s: &'a str) -> Result<Self, Self::Error> {
+        SongName::new(s)
+    }
+}
+
+// Reuses the String's buffer instead of copying
+impl TryFrom<String> for SongName {
+    type Error = SongNameError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        SongName::new(s)
+    }
+}
+
+// Shorthand for tests, where the names are known to be valid
+#[cfg(test)]
+pub fn song(name: &str) -> SongName {
+    SongName::new(name).unwrap()
+}
+
+impl SongUserProfile {
+    pub fn new<S: Into<String>>(username: S) -> Self {
+        SongUserProfile {
+            username: username.into(),
+            liked_songs: Vec::new(),
+            disliked_songs: Vec::new(),
+            listens: Cell::new(0),
+            days_active: 0,
+        }
+    }
+
+    // Liking a song twice has no effect
+    pub fn like(&mut self, song: SongName) {
+        if !self.liked_songs.contains(&song) {
+            self.liked_songs.push(song);
+        }
+    }
+
+    pub fn liked_songs(&self) -> &[SongName] {
+        &self.liked_songs
+    }
+
+    // An owned snapshot that can be kept after the profile changes, but
+    // not edited; liked_songs() is the borrowed, zero-copy view
+    pub fn liked(&self) -> Immutable<Vec<SongName>> {
+        Immutable::new(self.liked_songs.clone())
+    }
+
+    pub fn disliked_songs(&self) -> &[SongName] {
+        &self.disliked_songs
+    }
+
+    pub fn dislike(&mut self, song: SongName) {
+        if !self.disliked_songs.contains(&song) {
+            self.disliked_songs.push(song);
+        }
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn days_active(&self) -> usize {
+        self.days_active
+    }
+
+    pub fn set_days_active(&mut self, days: usize) {
+        self.days_active = days;
+    }
+
+    pub fn total_listens(&self) -> usize {
+        self.listens.get()
+    }
+
+    pub fn record_listen(&self) {
+        self.add_listens(1);
+    }
+
+    pub fn add_listens(&self, n: usize) {
+        self.listens.set(self.listens.get() + n);
+    }
+}
+
+#[test]
+fn test_song_name_traits() {
+    use std::convert::TryInto;
+
+    let a = song("Yesterday");
+    let b: SongName = String::from("Yesterday").try_into().unwrap();
+    let c: SongName = "Yesterday".parse().unwrap();
+    assert_eq!(a, b);
+    assert_eq!(b, c);
+    assert_ne!(a, song("Help!"));
+
+    assert_eq!(a.to_string(), "Yesterday");
+    assert_eq!(format!("{:?}", a), "SongName(\"Yesterday\")");
+    fn shout<S: AsRef<str>>(s: S) -> String {
+        s.as_ref().to_uppercase()
+    }
+    assert_eq!(shout(&a), "YESTERDAY");
+    assert_eq!(shout(a.as_str()), "YESTERDAY");
+}
+
+#[test]
+fn test_song_name_validation() {
+    assert_eq!(SongName::try_from(""), Err(SongNameError::Empty));
+    assert_eq!(SongName::try_from(" \t "), Err(SongNameError::Empty));
+    assert_eq!(
+        SongName::try_from("Line\nBreak"),
+        Err(SongNameError::ControlChar { ch: '\n', index: 4 })
+    );
+    let long = "la".repeat(101);
+    assert_eq!(
+        SongName::try_from(long),
+        Err(SongNameError::TooLong { len: 202 })
+    );
+    // Length is in chars, not bytes
+    assert!(SongName::try_from("é".repeat(MAX_SONG_NAME_LEN).as_str()).is_ok());
+
+    let err = "".parse::<SongName>().unwrap_err();
+    assert_eq!(err.to_string(), "song name is empty");
+}
+
+#[test]
+fn test_song_name_in_collections() {
+    use std::collections::{BTreeMap, HashSet};
+
+    let plays = ["Help!", "Yesterday", "Help!", "Let It Be", "Help!"];
+    let unique: HashSet<SongName> = plays.iter().map(|s| song(s)).collect();
+    assert_eq!(unique.len(), 3);
+    assert!(unique.contains(&song("Let It Be")));
+
+    let mut counts = BTreeMap::new();
+    for name in plays.iter().map(|s| song(s)) {
+        *counts.entry(name).or_insert(0) += 1;
+    }
+    // BTreeMap iterates in sorted key order
+    let sorted: Vec<(String, i32)> =
+        counts.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+    assert_eq!(
+        sorted,
+        vec![
+            ("Help!".to_string(), 3),
+            ("Let It Be".to_string(), 1),
+            ("Yesterday".to_string(), 1)
+        ]
+    );
+    assert!(song("Zebra") < song("apple"));
+}
+
+#[test]
+fn test_song_iterator() {
+    let mut profile = SongUserProfile::new("alice");
+    for name in &["Help!", "Yesterday"] {
+        profile.like(song(name));
+    }
+    let played: Vec<String> =
+        profile.get_iter().map(|song| song.to_string()).collect();
+    assert_eq!(played, vec!["Help!", "Yesterday"]);
+    assert!(profile.play_songs().eq(profile.get_iter()));
+}
+
+/*
+    Collecting into a profile
+
+    Extend and FromIterator are what make .extend() and .collect() work
+    for a type. Implementing them for SongUserProfile (with SongName
+    items) means any iterator of songs can be poured into a profile's
+    liked songs, e.g. at the end of an adapter pipeline:
+
+        let profile: SongUserProfile = names.map(..).collect();
+
+    As with like(), duplicates are dropped and the first occurrence keeps
+    its place. Calling like() for each song would make a big import
+    quadratic (it's a linear .contains()), so extend() keeps a HashSet of
+    the names it has already seen instead (SongName: Hash).
+
+    A collected profile starts from Default, with an empty username.
+*/
+
+impl Default for SongUserProfile {
+    fn default() -> Self {
+        SongUserProfile::new("")
+    }
+}
+
+impl Extend<SongName> for SongUserProfile {
+    fn extend<I: IntoIterator<Item = SongName>>(&mut self, iter: I) {
+        use std::collections::HashSet;
+
+        let mut seen: HashSet<SongName> =
+            self.liked_songs.iter().cloned().collect();
+        for song in iter {
+            if !seen.contains(&song) {
+                seen.insert(song.clone());
+                self.liked_songs.push(song);
+            }
+        }
+    }
+}
+
+impl FromIterator<SongName> for SongUserProfile {
+    fn from_iter<I: IntoIterator<Item = SongName>>(iter: I) -> Self {
+        let mut profile = SongUserProfile::default();
+        profile.extend(iter);
+        profile
+    }
+}
+
+#[test]
+fn test_profile_from_iterator() {
+    use adapters::MyIteratorExt;
+
+    // Skip invalid names, dedup the rest
+    let raw = ["Help!", "", "Yesterday", "Help!", "Bad\u{7}Name", "Let It Be"];
+    let profile: SongUserProfile = raw
+        .iter()
+        .map(|name| SongName::new(*name).ok())
+        .flatten_options()
+        .collect();
+    assert_eq!(
+        profile.liked_songs(),
+        &[song("Help!"), song("Yesterday"), song("Let It Be")]
+    );
+    assert_eq!(profile.username, "");
+}
+
+#[test]
+fn test_profile_from_valid_pipeline() {
+    let words = "Help! Yesterday Help! Girl";
+    let profile = words
+        .split_whitespace()
+        .map(SongName::from_valid)
+        .collect::<SongUserProfile>();
+    assert_eq!(
+        profile.liked_songs(),
+        &[song("Help!"), song("Yesterday"), song("Girl")]
+    );
+}
+
+#[test]
+#[should_panic(expected = "song name is empty")]
+fn test_from_valid_panics_on_invalid() {
+    SongName::from_valid("  ");
+}
+
+#[test]
+fn test_profile_extend() {
+    use parsing::word_frequencies;
+
+    let mut profile = SongUserProfile::new("bob");
+    profile.like(song("one"));
+    // The two most common words in some lyrics, as song names
+    let lyrics = "one love one heart love love one";
+    profile.extend(
+        word_frequencies(lyrics)
+            .take(2)
+            .map(|(word, _)| SongName::from_valid(word)),
+    );
+    assert_eq!(profile.liked_songs(), &[song("one"), song("love")]);
+
+    profile.extend(vec![song("heart"), song("love")]);
+    assert_eq!(profile.liked_songs().len(), 3);
+}
+
+#[test]
+fn test_profile_liked_snapshot() {
+    let mut profile = SongUserProfile::new("carol");
+    profile.like(song("Help!"));
+    let liked = profile.liked();
+    profile.like(song("Yesterday"));
+
+    // Reads go straight through to the Vec (and on to the slice)
+    assert_eq!(liked.len(), 1);
+    assert!(liked.contains(&song("Help!")));
+    assert_eq!(liked.first(), Some(&song("Help!")));
+    assert_eq!(profile.liked().len(), 2);
+    // liked.push(..) doesn't compile; an editable copy has to be asked for
+    let mut copy = liked.into_inner();
+    copy.push(song("Girl"));
+    assert_eq!(profile.liked_songs().len(), 2);
+}
+
+/*
+    Formatting a profile: Display vs Debug
+
+    Display is for users: a one-line summary, e.g.
+        alice: 42 liked, 3 disliked, 120 listens
+
+    Debug is for programmers, and #[derive(Debug)] would print every field
+    -- including the username, which then ends up in logs, panic messages
+    and bug reports. So Debug is written by hand to redact it. The
+    debug_struct helper does the layout, so {:#?} pretty-printing still
+    works.
+*/
+
+impl fmt::Display for SongUserProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} liked, {} disliked, {} listens",
+            self.username,
+            self.liked_songs.len(),
+            self.disliked_songs.len(),
+            self.listens.get()
+        )
+    }
+}
+
+impl fmt::Debug for SongUserProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SongUserProfile")
+            // format_args! so it prints without quotes
+            .field("username", &format_args!("<redacted>"))
+            .field("liked_songs", &self.liked_songs)
+            .field("disliked_songs", &self.disliked_songs)
+            .field("listens", &self.listens.get())
+            .field("days_active", &self.days_active)
+            .finish()
+    }
+}
+
+#[test]
+fn test_profile_display() {
+    let mut profile: SongUserProfile =
+        ["Help!", "Yesterday"].iter().map(|s| song(s)).collect();
+    profile.username = "alice".to_string();
+    profile.dislike(song("Revolution 9"));
+    profile.add_listens(120);
+    assert_eq!(profile.to_string(), "alice: 2 liked, 1 disliked, 120 listens");
+}
+
+#[test]
+fn test_profile_debug_redacts_username() {
+    let mut profile = SongUserProfile::new("secret_user_42");
+    profile.like(song("Help!"));
+    let debug = format!("{:?}", profile);
+    assert!(!debug.contains("secret_user_42"));
+    assert_eq!(
+        debug,
+        "SongUserProfile { username: <redacted>, liked_songs: [SongName(\"Help!\")], \
+         disliked_songs: [], listens: 0, days_active: 0 }"
+    );
+    assert!(!format!("{:#?}", profile).contains("secret"));
+}
+
+/*
+    Counting listens through &self
+
+    play_songs takes &self: playing shouldn't need exclusive access to
+    the profile, and the iterator it returns borrows the profile for as
+    long as it's alive. But each play should bump the listen count, which
+    is a mutation.
+
+    Cell<usize> allows exactly that: get() copies the value out, set()
+    replaces it, both through a shared reference. It's safe because a
+    Cell never hands out a reference to its contents, so nothing can be
+    looking at the value while it changes. (The price: Cell isn't Sync,
+    so a &SongUserProfile can't be shared between threads -- see
+    songs::ConcurrentProfile for that.)
+*/
+
+#[test]
+fn test_play_songs_counts_listens() {
+    let profile: SongUserProfile =
+        ["Help!", "Yesterday", "Let It Be"].iter().map(|s| song(s)).collect();
+    assert_eq!(profile.total_listens(), 0);
+
+    // Lazy: nothing is counted until songs are actually pulled
+    let mut player = profile.play_songs();
+    assert_eq!(profile.total_listens(), 0);
+    player.next();
+    player.next();
+    assert_eq!(profile.total_listens(), 2);
+
+    // Several players at once, all through &profile
+    let all: Vec<SongName> =
+        profile.play_songs().chain(profile.play_songs()).collect();
+    assert_eq!(all.len(), 6);
+    assert_eq!(profile.total_listens(), 8);
+}
+
+/*
+    Clone and clone_from
+
+    clone() always builds a brand-new value. a.clone_from(&b) means
+    "make a equal to b", and since a already exists, it can reuse a's
+    allocations: String::clone_from copies into the existing buffer if it
+    has room, and Vec::clone_from reuses its buffer *and* calls clone_from
+    on each element it overwrites.
+
+    The default clone_from is just `*self = source.clone()`, and
+    #[derive(Clone)] keeps that default. So these Clone impls are written
+    by hand, forwarding clone_from field by field. (SongName's too --
+    otherwise Vec<SongName>::clone_from would reuse the Vec's buffer but
+    still allocate a new String for every song.)
+
+    This matters when one value is refreshed from another over and over,
+    e.g. copying the current profile into a display buffer every frame.
+    The tests count allocations to check (see alloc_counter.rs).
+*/
+
+impl Clone for SongName {
+    fn clone(&self) -> Self {
+        SongName(self.0.clone())
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.0.clone_from(&source.0);
+    }
+}
+
+impl Clone for SongUserProfile {
+    fn clone(&self) -> Self {
+        SongUserProfile {
+            username: self.username.clone(),
+            liked_songs: self.liked_songs.clone(),
+            disliked_songs: self.disliked_songs.clone(),
+            listens: self.listens.clone(),
+            days_active: self.days_active,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.username.clone_from(&source.username);
+        self.liked_songs.clone_from(&source.liked_songs);
+        self.disliked_songs.clone_from(&source.disliked_songs);
+        self.listens.set(source.listens.get());
+        self.days_active = source.days_active;
+    }
+}
+
+#[test]
+fn test_profile_clone_from_reuses_buffers() {
+    use alloc_counter::count_allocations;
+
+    let mut source: SongUserProfile =
+        (0..20).map(|i| song(&format!("Song number {}", i))).collect();
+    source.username = "alice".to_string();
+    source.add_listens(5);
+
+    let (mut copy, allocs) = count_allocations(|| source.clone());
+    // Username, two Vecs (the empty one doesn't allocate), 20 names
+    assert_eq!(allocs, 1 + 1 + 20);
+
+    // A profile of the same shape changes...
+    source.like(song("Song number 20"));
+    source.username = "alicia".to_string();
+    // ...and the copy is refreshed from it. Only what outgrew its buffer
+    // allocates: the longer username, the liked Vec, and the new name
+    let ((), allocs) = count_allocations(|| copy.clone_from(&source));
+    assert_eq!(allocs, 3);
+    assert_eq!(copy.to_string(), source.to_string());
+    assert_eq!(copy.liked_songs(), source.liked_songs());
+
+    // Same shape again: no allocations at all
+    let ((), allocs) = count_allocations(|| copy.clone_from(&source));
+    assert_eq!(allocs, 0);
+}
@@ -0,0 +1,648 @@
+/*
+    JSON without serde
+
+    encoding.rs saves profiles in a compact binary format only this crate
+    can read. JSON is the opposite trade-off: bigger and slower, but any
+    language (or a person with a text editor) can read it.
+
+    Parsing happens in two steps:
+    1. Text -> JsonValue, a small enum that can hold any JSON document.
+       This is a recursive descent parser: one method per kind of value,
+       and parse_value looks at the next character to pick which one.
+       Arrays and objects call parse_value for their elements, which is
+       where the recursion comes in.
+    2. JsonValue -> SongUserProfile, by looking up each field and
+       checking it has the right type.
+
+    Recursion has a cost: each level of nesting is a stack frame, and
+    "[[[[..." a few hundred thousand deep would overflow the stack and
+    abort the whole program -- not something a file we were handed
+    should be able to do. So the parser counts how deep it is and gives
+    up with JsonError::TooDeep past MAX_DEPTH levels.
+
+    Errors carry the byte offset where parsing failed, so a message can
+    point at the problem: "expected ':' at byte 12".
+*/
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+use std::path::Path;
+
+use error::{ErrContext, LectureError};
+use iterators::{SongName, SongNameError, SongUserProfile};
+use utility_traits::read_to_string_from;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    // Sorted keys, so output is deterministic
+    Object(BTreeMap<String, JsonValue>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonError {
+    UnexpectedEnd,
+    Unexpected { found: char, expected: &'static str, at: usize },
+    InvalidNumber { at: usize },
+    InvalidEscape { at: usize },
+    TrailingCharacters { at: usize },
+    TooDeep { at: usize },
+    MissingField(&'static str),
+    WrongType { field: &'static str, expected: &'static str },
+    InvalidSongName(SongNameError),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            JsonError::Unexpected { found, expected, at } => write!(
+                f,
+                "expected {} at byte {}, found {:?}",
+                expected, at, found
+            ),
+            JsonError::InvalidNumber { at } => {
+                write!(f, "invalid number at byte {}", at)
+            }
+            JsonError::InvalidEscape { at } => {
+                write!(f, "invalid escape sequence at byte {}", at)
+            }
+            JsonError::TrailingCharacters { at } => {
+                write!(
+                    f,
+                    "unexpected characters after the value at byte {}",
+                    at
+                )
+            }
+            JsonError::TooDeep { at } => write!(
+                f,
+                "nested more than {} levels deep at byte {}",
+                MAX_DEPTH, at
+            ),
+            JsonError::MissingField(field) => {
+                write!(f, "missing field `{}`", field)
+            }
+            JsonError::WrongType { field, expected } => {
+                write!(f, "field `{}` should be {}", field, expected)
+            }
+            JsonError::InvalidSongName(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JsonError::InvalidSongName(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SongNameError> for JsonError {
+    fn from(e: SongNameError) -> Self {
+        JsonError::InvalidSongName(e)
+    }
+}
+
+/*
+    Writing
+*/
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                // Writing to a String can't fail
+                write!(out, "\\u{:04x}", c as u32).unwrap();
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonValue::Null => f.write_str("null"),
+            JsonValue::Bool(b) => write!(f, "{}", b),
+            // JSON has no NaN or infinity; write null, like JavaScript's
+            // JSON.stringify
+            JsonValue::Number(n) if !n.is_finite() => f.write_str("null"),
+            JsonValue::Number(n) => write!(f, "{}", n),
+            JsonValue::String(s) => {
+                let mut out = String::new();
+                write_string(s, &mut out);
+                f.write_str(&out)
+            }
+            JsonValue::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            JsonValue::Object(fields) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    let mut k = String::new();
+                    write_string(key, &mut k);
+                    write!(f, "{}:{}", k, value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/*
+    Parsing
+*/
+
+// Far deeper than any profile, far shallower than the stack
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    // How many arrays/objects we're inside
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next_char(&mut self) -> Result<char, JsonError> {
+        let c = self.peek().ok_or(JsonError::UnexpectedEnd)?;
+        self.pos += c.len_utf8();
+        Ok(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !matches!(c, ' ' | '\t' | '\n' | '\r') {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn expect(
+        &mut self,
+        want: char,
+        expected: &'static str,
+    ) -> Result<(), JsonError> {
+        let at = self.pos;
+        match self.next_char()? {
+            c if c == want => Ok(()),
+            found => Err(JsonError::Unexpected { found, expected, at }),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, JsonError> {
+        self.skip_whitespace();
+        let value = match self.peek().ok_or(JsonError::UnexpectedEnd)? {
+            c @ '{' | c @ '[' => {
+                if self.depth == MAX_DEPTH {
+                    return Err(JsonError::TooDeep { at: self.pos });
+                }
+                self.depth += 1;
+                let value = if c == '{' {
+                    self.parse_object()
+                } else {
+                    self.parse_array()
+                };
+                self.depth -= 1;
+                value?
+            }
+            '"' => JsonValue::String(self.parse_string()?),
+            '-' | '0'..='9' => self.parse_number()?,
+            't' => self.parse_literal("true", JsonValue::Bool(true))?,
+            'f' => self.parse_literal("false", JsonValue::Bool(false))?,
+            'n' => self.parse_literal("null", JsonValue::Null)?,
+            found => {
+                let expected = "a value";
+                return Err(JsonError::Unexpected {
+                    found,
+                    expected,
+                    at: self.pos,
+                });
+            }
+        };
+        self.skip_whitespace();
+        Ok(value)
+    }
+
+    fn parse_literal(
+        &mut self,
+        word: &'static str,
+        value: JsonValue,
+    ) -> Result<JsonValue, JsonError> {
+        for want in word.chars() {
+            self.expect(want, word)?;
+        }
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, JsonError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9') {
+                break;
+            }
+            self.pos += 1;
+        }
+        // f64's parser accepts a superset of JSON numbers ("1.", ".5"),
+        // which is fine for reading
+        self.text[start..self.pos]
+            .parse()
+            .map(JsonValue::Number)
+            .map_err(|_| JsonError::InvalidNumber { at: start })
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let at = self.pos;
+        let digits = self.text.get(self.pos..self.pos + 4);
+        // from_str_radix alone would also accept a sign: "\u+041"
+        let code = digits
+            .filter(|d| d.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|d| u32::from_str_radix(d, 16).ok());
+        self.pos += 4;
+        code.ok_or(JsonError::InvalidEscape { at })
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect('"', "'\"'")?;
+        let mut s = String::new();
+        loop {
+            let at = self.pos;
+            match self.next_char()? {
+                '"' => return Ok(s),
+                '\\' => s.push(match self.next_char()? {
+                    '"' => '"',
+                    '\\' => '\\',
+                    '/' => '/',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => {
+                        let mut code = self.parse_hex4()?;
+                        // Outside the BMP, JSON uses a UTF-16 surrogate pair
+                        if (0xd800..0xdc00).contains(&code) {
+                            self.expect('\\', "a low surrogate")?;
+                            self.expect('u', "a low surrogate")?;
+                            let low = self.parse_hex4()?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err(JsonError::InvalidEscape { at });
+                            }
+                            code = 0x10000
+                                + ((code - 0xd800) << 10)
+                                + (low - 0xdc00);
+                        }
+                        std::char::from_u32(code)
+                            .ok_or(JsonError::InvalidEscape { at })?
+                    }
+                    _ => return Err(JsonError::InvalidEscape { at }),
+                }),
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, JsonError> {
+        self.expect('[', "'['")?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            let at = self.pos;
+            match self.next_char()? {
+                ',' => continue,
+                ']' => return Ok(JsonValue::Array(items)),
+                found => {
+                    let expected = "',' or ']'";
+                    return Err(JsonError::Unexpected { found, expected, at });
+                }
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, JsonError> {
+        self.expect('{', "'{'")?;
+        let mut fields = BTreeMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':', "':'")?;
+            // A repeated key keeps the last value, like most parsers
+            fields.insert(key, self.parse_value()?);
+            let at = self.pos;
+            match self.next_char()? {
+                ',' => continue,
+                '}' => return Ok(JsonValue::Object(fields)),
+                found => {
+                    let expected = "',' or '}'";
+                    return Err(JsonError::Unexpected { found, expected, at });
+                }
+            }
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<JsonValue, JsonError> {
+    let mut parser = Parser { text, pos: 0, depth: 0 };
+    let value = parser.parse_value()?;
+    if parser.pos < text.len() {
+        return Err(JsonError::TrailingCharacters { at: parser.pos });
+    }
+    Ok(value)
+}
+
+/*
+    SongUserProfile <-> JSON
+
+        {"days_active":3,"disliked_songs":[],"liked_songs":["Help!"],
+         "listens":42,"username":"alice"}
+
+    Unknown fields are ignored, so files written by a newer version with
+    extra fields can still be read.
+*/
+
+fn field<'v>(
+    fields: &'v BTreeMap<String, JsonValue>,
+    name: &'static str,
+) -> Result<&'v JsonValue, JsonError> {
+    fields.get(name).ok_or(JsonError::MissingField(name))
+}
+
+fn count_field(
+    fields: &BTreeMap<String, JsonValue>,
+    name: &'static str,
+) -> Result<usize, JsonError> {
+    match field(fields, name)? {
+        &JsonValue::Number(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+        _ => Err(JsonError::WrongType {
+            field: name,
+            expected: "a whole number",
+        }),
+    }
+}
+
+fn songs_field(
+    fields: &BTreeMap<String, JsonValue>,
+    name: &'static str,
+) -> Result<Vec<SongName>, JsonError> {
+    let wrong =
+        JsonError::WrongType { field: name, expected: "a list of strings" };
+    match field(fields, name)? {
+        JsonValue::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                JsonValue::String(s) => Ok(SongName::new(s.as_str())?),
+                _ => Err(wrong.clone()),
+            })
+            .collect(),
+        _ => Err(wrong),
+    }
+}
+
+fn song_list(songs: &[SongName]) -> JsonValue {
+    JsonValue::Array(
+        songs
+            .iter()
+            .map(|s| JsonValue::String(s.as_str().to_string()))
+            .collect(),
+    )
+}
+
+impl SongUserProfile {
+    pub fn to_json(&self) -> String {
+        let mut fields = BTreeMap::new();
+        let mut set = |key: &str, value| fields.insert(key.to_string(), value);
+        set("username", JsonValue::String(self.username().to_string()));
+        set("liked_songs", song_list(self.liked_songs()));
+        set("disliked_songs", song_list(self.disliked_songs()));
+        set("listens", JsonValue::Number(self.total_listens() as f64));
+        set("days_active", JsonValue::Number(self.days_active() as f64));
+        JsonValue::Object(fields).to_string()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, JsonError> {
+        use self::JsonError::WrongType;
+
+        let fields = match parse(text)? {
+            JsonValue::Object(fields) => fields,
+            _ => {
+                return Err(WrongType { field: "(top)", expected: "an object" })
+            }
+        };
+        let username = match field(&fields, "username")? {
+            JsonValue::String(s) => s.as_str(),
+            _ => {
+                return Err(WrongType {
+                    field: "username",
+                    expected: "a string",
+                })
+            }
+        };
+        let mut profile = SongUserProfile::new(username);
+        for song in songs_field(&fields, "liked_songs")? {
+            profile.like(song);
+        }
+        for song in songs_field(&fields, "disliked_songs")? {
+            profile.dislike(song);
+        }
+        profile.add_listens(count_field(&fields, "listens")?);
+        profile.set_days_active(count_field(&fields, "days_active")?);
+        Ok(profile)
+    }
+
+    // from_json on a file; errors say which file and which step failed
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self, LectureError> {
+        let path = path.as_ref();
+        let text = read_to_string_from(path)
+            .with_context(|| format!("reading {}", path.display()))?;
+        Ok(SongUserProfile::from_json(&text)
+            .with_context(|| format!("parsing {}", path.display()))?)
+    }
+}
+
+#[test]
+fn test_parse_json_values() {
+    let value =
+        parse(r#" {"a": [1, -2.5, true, null], "b": {"c": "d"}} "#).unwrap();
+    let mut inner = BTreeMap::new();
+    inner.insert(String::from("c"), JsonValue::String(String::from("d")));
+    let mut outer = BTreeMap::new();
+    outer.insert(
+        String::from("a"),
+        JsonValue::Array(vec![
+            JsonValue::Number(1.0),
+            JsonValue::Number(-2.5),
+            JsonValue::Bool(true),
+            JsonValue::Null,
+        ]),
+    );
+    outer.insert(String::from("b"), JsonValue::Object(inner));
+    assert_eq!(value, JsonValue::Object(outer));
+    assert_eq!(parse("[]"), Ok(JsonValue::Array(vec![])));
+
+    // Escapes, including a surrogate pair, survive a round trip
+    let s = parse(r#""tab\there \"q\" é 🎵""#).unwrap();
+    assert_eq!(s, JsonValue::String(String::from("tab\there \"q\" é 🎵")));
+    assert_eq!(parse(&s.to_string()), Ok(s));
+}
+
+#[test]
+fn test_parse_json_errors() {
+    assert_eq!(parse("[1, 2"), Err(JsonError::UnexpectedEnd));
+    assert_eq!(
+        parse(r#"{"a" 1}"#),
+        Err(JsonError::Unexpected { found: '1', expected: "':'", at: 5 })
+    );
+    assert_eq!(
+        parse("[1 2]").unwrap_err().to_string(),
+        "expected ',' or ']' at byte 3, found '2'"
+    );
+    assert_eq!(parse("1 2"), Err(JsonError::TrailingCharacters { at: 2 }));
+    assert_eq!(parse("-"), Err(JsonError::InvalidNumber { at: 0 }));
+    assert_eq!(parse(r#""\q""#), Err(JsonError::InvalidEscape { at: 1 }));
+    assert!(parse("tru").is_err());
+    assert_eq!(parse(r#""\u+041""#), Err(JsonError::InvalidEscape { at: 3 }));
+    assert_eq!(parse(r#""\u0041""#), Ok(JsonValue::String(String::from("A"))));
+}
+
+#[test]
+fn test_parse_json_too_deep() {
+    // Valid JSON, but deep enough to overflow the stack if we let it
+    let deep = "[".repeat(200_000) + &"]".repeat(200_000);
+    assert_eq!(parse(&deep), Err(JsonError::TooDeep { at: MAX_DEPTH }));
+    let deep = r#"{"a":"#.repeat(200_000);
+    assert!(matches!(parse(&deep), Err(JsonError::TooDeep { .. })));
+
+    // Right at the limit is fine
+    let ok = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+    assert!(parse(&ok).is_ok());
+}
+
+#[test]
+fn test_json_non_finite_numbers() {
+    let values = JsonValue::Array(vec![
+        JsonValue::Number(f64::NAN),
+        JsonValue::Number(f64::INFINITY),
+        JsonValue::Number(1.5),
+    ]);
+    assert_eq!(values.to_string(), "[null,null,1.5]");
+    assert!(parse(&values.to_string()).is_ok());
+}
+
+#[test]
+fn test_profile_json_round_trip() {
+    use iterators::song;
+
+    let mut profile = SongUserProfile::new("alice \"the fan\"");
+    profile.like(song("Help!"));
+    profile.like(song("Ob-La-Di, Ob-La-Da"));
+    profile.dislike(song("Revolution 9"));
+    profile.add_listens(42);
+    profile.set_days_active(3);
+
+    let json = profile.to_json();
+    assert_eq!(
+        json,
+        r#"{"days_active":3,"disliked_songs":["Revolution 9"],"#.to_string()
+            + r#""liked_songs":["Help!","Ob-La-Di, Ob-La-Da"],"listens":42,"#
+            + r#""username":"alice \"the fan\""}"#
+    );
+    let back = SongUserProfile::from_json(&json).unwrap();
+    assert_eq!(back.to_json(), json);
+    assert_eq!(back.liked_songs(), profile.liked_songs());
+}
+
+#[test]
+fn test_profile_from_json_errors() {
+    let missing = r#"{"username": "bob", "liked_songs": []}"#;
+    assert_eq!(
+        SongUserProfile::from_json(missing).unwrap_err(),
+        JsonError::MissingField("disliked_songs")
+    );
+    let negative = r#"{"username": "bob", "liked_songs": [],
+        "disliked_songs": [], "listens": -1, "days_active": 0}"#;
+    assert_eq!(
+        SongUserProfile::from_json(negative).unwrap_err().to_string(),
+        "field `listens` should be a whole number"
+    );
+    let bad_song = r#"{"username": "bob", "liked_songs": [""]}"#;
+    assert_eq!(
+        SongUserProfile::from_json(bad_song).unwrap_err(),
+        JsonError::InvalidSongName(SongNameError::Empty)
+    );
+    assert!(SongUserProfile::from_json("[]").is_err());
+}
+
+#[test]
+fn test_profile_load_json() {
+    use error::report;
+    use utility_traits::{temp_path, TempFile};
+
+    let alice = SongUserProfile::new("alice");
+    let good =
+        TempFile::new(temp_path("load_alice.json"), &alice.to_json()).unwrap();
+    assert_eq!(
+        SongUserProfile::load_json(good.path()).unwrap().username(),
+        "alice"
+    );
+
+    let bad =
+        TempFile::new(temp_path("load_bad.json"), "{\"username\": \"bob\"}")
+            .unwrap();
+    let err = SongUserProfile::load_json(bad.path()).unwrap_err();
+    assert_eq!(
+        report(&err),
+        format!(
+            "parsing {}: invalid value: missing field `liked_songs`",
+            bad.path().display()
+        )
+    );
+    let source = err.without_context().source().unwrap();
+    assert_eq!(
+        source.downcast_ref::<JsonError>(),
+        Some(&JsonError::MissingField("liked_songs"))
+    );
+
+    let missing = SongUserProfile::load_json(temp_path("load_nobody.json"));
+    assert!(missing.unwrap_err().to_string().starts_with("reading "));
+}
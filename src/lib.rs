@@ -0,0 +1,81 @@
+/*
+    CIS198 Lecture 7: Closures and Iterators
+
+    The lecture code, as a library. Each module is one topic; the
+    binary (src/bin/lecture7.rs) is only a front end that lists the
+    examples and runs them by name.
+
+    Most programs using it want the same handful of names -- the
+    extension traits, the song types, the error type -- from half a
+    dozen modules. The prelude collects those, so one line brings them
+    all in:
+
+        use lecture7::prelude::*;
+
+    (std does the same: std::prelude is imported into every module
+    automatically, ours has to be asked for.) Extension traits are the
+    main reason to have one: a trait's methods are only callable where
+    the trait is in scope, and it's easy to forget which module
+    .quoted() or .context() lives in.
+*/
+
+pub mod adapters;
+#[cfg(test)]
+mod alloc_counter;
+pub mod closures;
+pub mod config;
+pub mod consumers;
+pub mod data_structures;
+pub mod encoding;
+pub mod error;
+pub mod generators;
+pub mod iterators;
+pub mod json;
+pub mod num;
+pub mod parsing;
+mod rng;
+pub mod songs;
+pub mod utility_traits;
+
+pub mod prelude {
+    // Extension traits
+    pub use adapters::MyIteratorExt;
+    pub use error::ErrContext;
+    pub use utility_traits::DisplayExt;
+
+    // Conversions and validation
+    pub use encoding::{FromBytes, ToBytes};
+    pub use utility_traits::{Validate, Validated};
+
+    // The song types
+    pub use iterators::{SongName, SongNameError, SongUserProfile};
+    pub use songs::{Playlist, SongArena, SongId, SongList, SongSlice, UserId};
+
+    // Errors
+    pub use error::{report, Contextual, LectureError};
+}
+
+#[test]
+fn test_prelude() {
+    use prelude::*;
+
+    // Every name here comes from the one import above
+    let mut profile = SongUserProfile::new("alice");
+    let name: SongName = "Help!".parse().unwrap();
+    profile.like(name.clone());
+    let playlist = Playlist::from(vec![name]);
+    let bytes = playlist.to_bytes();
+    assert_eq!(Playlist::from_bytes(&bytes).unwrap(), playlist);
+
+    let sums: Vec<u32> = (1..5).running_sum().collect();
+    assert_eq!(sums, vec![1, 3, 6, 10]);
+    assert_eq!(profile.username().quoted().to_string(), "\"alice\"");
+
+    fn like(name: &str) -> Result<SongName, LectureError> {
+        Ok(SongName::new(name).context("liking a song")?)
+    }
+    assert_eq!(
+        report(&like("").unwrap_err()),
+        "liking a song: invalid value: song name is empty"
+    );
+}
@@ -1,6 +0,0 @@
-pub mod closures;
-pub mod iterators;
-
-fn main() {
-    println!("CIS198 Lecture 7: Closures and Iterators");
-}
@@ -0,0 +1,80 @@
+/*
+    A tiny numeric trait
+
+    Functions like iterators::sum_squares_lt only need a handful of
+    operations: zero, one, +, *, and <. Rust's integer and float types
+    don't share a standard trait for "is a number", so we define one
+    (the num-traits crate does this properly).
+
+    The checked_* methods return None instead of overflowing. Floats
+    don't overflow in the same way -- they go to infinity -- so for them
+    "overflow" means the result is no longer finite.
+
+    A macro writes the nearly identical impls for each type.
+*/
+
+use std::ops::{Add, Mul};
+
+pub trait Num:
+    Copy + PartialOrd + Add<Output = Self> + Mul<Output = Self>
+{
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! impl_num_int {
+    ($($t:ty),*) => {
+        $(
+            impl Num for $t {
+                fn zero() -> Self {
+                    0
+                }
+                fn one() -> Self {
+                    1
+                }
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    <$t>::checked_add(self, other)
+                }
+                fn checked_mul(self, other: Self) -> Option<Self> {
+                    <$t>::checked_mul(self, other)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_num_float {
+    ($($t:ty),*) => {
+        $(
+            impl Num for $t {
+                fn zero() -> Self {
+                    0.0
+                }
+                fn one() -> Self {
+                    1.0
+                }
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    Some(self + other).filter(|x| x.is_finite())
+                }
+                fn checked_mul(self, other: Self) -> Option<Self> {
+                    Some(self * other).filter(|x| x.is_finite())
+                }
+            }
+        )*
+    };
+}
+
+impl_num_int!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+impl_num_float!(f32, f64);
+
+#[test]
+fn test_num_checked() {
+    assert_eq!(Num::checked_add(250u8, 5), Some(255));
+    assert_eq!(Num::checked_add(250u8, 6), None);
+    assert_eq!(Num::checked_mul(i128::MAX, 2), None);
+    assert_eq!(Num::checked_mul(2.0f64, 3.0), Some(6.0));
+    assert_eq!(Num::checked_mul(f64::MAX, 2.0), None);
+    assert_eq!(<u64 as Num>::zero() + <u64 as Num>::one(), 1);
+}
@@ -0,0 +1,708 @@
+/*
+    Parsing with iterators
+
+    Parsers are a natural fit for iterators: read a bit of input, produce
+    one item, repeat. The caller decides how much of the input to consume,
+    and all of our adapters and consumers work on the output.
+*/
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::iter;
+use std::path::Path;
+
+use consumers::count_frequencies;
+use error::{ErrContext, LectureError};
+
+/*
+    CSV rows
+
+    CsvRows wraps anything that implements BufRead (a file, stdin, or just
+    a &[u8] in tests) and yields one row per line. Reading can fail, and so
+    can parsing, so each item is a Result.
+
+    Supported quoting (a small subset of RFC 4180):
+    - a field wrapped in double quotes may contain commas: "a, b"
+    - inside quotes, a doubled quote is a literal quote: "say ""hi"""
+    - quoted fields can't span multiple lines
+    Blank lines are skipped.
+*/
+
+#[derive(Debug)]
+pub enum CsvError {
+    Io(io::Error),
+    // A quoted field with no closing quote
+    UnterminatedQuote { line: usize },
+    // Something other than a comma right after a closing quote
+    UnexpectedChar { line: usize, ch: char },
+    // The deserialize_row closure rejected the row
+    Deserialize { line: usize, message: String },
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::Io(e) => write!(f, "I/O error: {}", e),
+            CsvError::UnterminatedQuote { line } => {
+                write!(f, "line {}: unterminated quoted field", line)
+            }
+            CsvError::UnexpectedChar { line, ch } => {
+                write!(
+                    f,
+                    "line {}: unexpected {:?} after closing quote",
+                    line, ch
+                )
+            }
+            CsvError::Deserialize { line, message } => {
+                write!(f, "line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl Error for CsvError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CsvError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CsvError {
+    fn from(e: io::Error) -> Self {
+        CsvError::Io(e)
+    }
+}
+
+// Split one line into fields. `line_no` is only used for error messages.
+fn parse_csv_line(line: &str, line_no: usize) -> Result<Vec<String>, CsvError> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    None => {
+                        return Err(CsvError::UnterminatedQuote {
+                            line: line_no,
+                        })
+                    }
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                }
+            }
+            match chars.next() {
+                None => {
+                    fields.push(field);
+                    return Ok(fields);
+                }
+                Some(',') => fields.push(field),
+                Some(ch) => {
+                    return Err(CsvError::UnexpectedChar { line: line_no, ch })
+                }
+            }
+        } else {
+            // Unquoted: everything up to the next comma
+            let mut saw_comma = false;
+            for c in chars.by_ref() {
+                if c == ',' {
+                    saw_comma = true;
+                    break;
+                }
+                field.push(c);
+            }
+            fields.push(field);
+            if !saw_comma {
+                return Ok(fields);
+            }
+        }
+    }
+}
+
+pub struct CsvRows<R> {
+    reader: R,
+    line_no: usize,
+    buf: String,
+}
+
+impl<R: BufRead> CsvRows<R> {
+    pub fn new(reader: R) -> Self {
+        CsvRows { reader, line_no: 0, buf: String::new() }
+    }
+
+    // Turn each row into a T using a closure. If the closure returns
+    // Err(message), that becomes a CsvError::Deserialize carrying the line
+    // number. Parse errors pass through unchanged.
+    pub fn deserialize_row<T, F>(
+        self,
+        mut f: F,
+    ) -> impl Iterator<Item = Result<T, CsvError>>
+    where
+        F: FnMut(&[String]) -> Result<T, String>,
+    {
+        let mut rows = self;
+        iter::from_fn(move || {
+            let row = rows.next()?;
+            // line_no is now the line we just read
+            let line = rows.line_no;
+            Some(row.and_then(|row| {
+                f(&row)
+                    .map_err(|message| CsvError::Deserialize { line, message })
+            }))
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for CsvRows<R> {
+    type Item = Result<Vec<String>, CsvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => self.line_no += 1,
+                // A line that isn't UTF-8 has still been read past, so it
+                // counts: later errors must name the right line
+                Err(e) => {
+                    self.line_no += 1;
+                    return Some(Err(CsvError::Io(e)));
+                }
+            }
+            let line = self.buf.trim_end_matches(&['\n', '\r'][..]);
+            if !line.is_empty() {
+                return Some(parse_csv_line(line, self.line_no));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_csv_rows() {
+    let input = "name,age\nalice,30\n\n\"smith, bob\",41\r\n";
+    let rows: Vec<Vec<String>> =
+        CsvRows::new(input.as_bytes()).map(Result::unwrap).collect();
+    assert_eq!(
+        rows,
+        vec![
+            vec!["name", "age"],
+            vec!["alice", "30"],
+            vec!["smith, bob", "41"],
+        ]
+    );
+}
+
+#[test]
+fn test_csv_rows_after_io_error() {
+    let input: &[u8] = b"a,b\n\xff,1\n\"open,2\n";
+    let mut rows = CsvRows::new(input);
+    assert_eq!(rows.next().unwrap().unwrap(), vec!["a", "b"]);
+    match rows.next() {
+        Some(Err(CsvError::Io(e))) => {
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData)
+        }
+        other => panic!("unexpected: {:?}", other),
+    }
+    match rows.next() {
+        Some(Err(CsvError::UnterminatedQuote { line: 3 })) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+    assert!(rows.next().is_none());
+}
+
+#[test]
+fn test_csv_quoting() {
+    let row = parse_csv_line(r#""say ""hi""",,x,"#, 1).unwrap();
+    assert_eq!(row, vec![r#"say "hi""#, "", "x", ""]);
+    assert_eq!(parse_csv_line("", 1).unwrap(), vec![""]);
+
+    match parse_csv_line(r#"a,"open"#, 7) {
+        Err(CsvError::UnterminatedQuote { line: 7 }) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+    match parse_csv_line(r#""a"b"#, 2) {
+        Err(CsvError::UnexpectedChar { line: 2, ch: 'b' }) => {}
+        other => panic!("unexpected: {:?}", other),
+    }
+}
+
+#[test]
+fn test_csv_deserialize_row() {
+    #[derive(Debug, PartialEq)]
+    struct Person {
+        name: String,
+        age: u32,
+    }
+    let input = "alice,30\n\nbob,old\ncarol,25";
+    let people: Vec<Result<Person, CsvError>> = CsvRows::new(input.as_bytes())
+        .deserialize_row(|fields| match fields {
+            [name, age] => Ok(Person {
+                name: name.clone(),
+                age: age.parse().map_err(|e| format!("bad age: {}", e))?,
+            }),
+            _ => Err(format!("expected 2 fields, got {}", fields.len())),
+        })
+        .collect();
+    assert_eq!(people[0].as_ref().unwrap().age, 30);
+    assert_eq!(
+        people[1].as_ref().unwrap_err().to_string(),
+        "line 3: bad age: invalid digit found in string"
+    );
+    assert_eq!(people[2].as_ref().unwrap().name, "carol");
+}
+
+// Every row of a CSV file, stopping at the first bad one. The error
+// names the file; CsvError already has the line.
+pub fn read_csv<P: AsRef<Path>>(
+    path: P,
+) -> Result<Vec<Vec<String>>, LectureError> {
+    let path = path.as_ref();
+    let file = File::open(path)
+        .with_context(|| format!("opening {}", path.display()))?;
+    Ok(CsvRows::new(BufReader::new(file))
+        .collect::<Result<_, _>>()
+        .with_context(|| path.display().to_string())?)
+}
+
+#[test]
+fn test_read_csv() {
+    use error::report;
+    use utility_traits::{temp_path, TempFile};
+
+    let good = TempFile::new(temp_path("read_good.csv"), "a,b\n1,2\n").unwrap();
+    assert_eq!(
+        read_csv(good.path()).unwrap(),
+        vec![vec!["a", "b"], vec!["1", "2"]]
+    );
+
+    let bad = TempFile::new(temp_path("read_bad.csv"), "a,b\n\"1,2\n").unwrap();
+    let err = read_csv(bad.path()).unwrap_err();
+    assert_eq!(
+        report(&err),
+        format!(
+            "{}: invalid value: line 2: unterminated quoted field",
+            bad.path().display()
+        )
+    );
+
+    let missing = read_csv(temp_path("read_missing.csv")).unwrap_err();
+    assert!(missing.to_string().starts_with("opening "));
+    assert!(matches!(missing.without_context(), LectureError::Io(_)));
+}
+
+/*
+    Tokenizer
+
+    Tokens splits a string on a set of delimiter characters, skipping
+    empty tokens (like split_whitespace). Each token is a slice of the
+    original input, so the struct has a lifetime parameter 'a: the tokens
+    borrow from the input, and can't outlive it.
+
+    Optionally an escape character lets a delimiter appear inside a token
+    (with a backslash as the escape, a\ b c is the two tokens "a b" and
+    "c"). Only those tokens need a new String; every other token is still
+    borrowed. Cow ("clone on write") expresses exactly this:
+    Cow::Borrowed(&'a str) or Cow::Owned(String), decided per token.
+*/
+
+pub struct Tokens<'a> {
+    rest: &'a str,
+    delims: &'a [char],
+    escape: Option<char>,
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(input: &'a str, delims: &'a [char]) -> Self {
+        Tokens { rest: input, delims, escape: None }
+    }
+
+    pub fn with_escape(mut self, escape: char) -> Self {
+        self.escape = Some(escape);
+        self
+    }
+
+    fn unescape(&self, token: &str) -> String {
+        let mut result = String::with_capacity(token.len());
+        let mut chars = token.chars();
+        while let Some(c) = chars.next() {
+            if Some(c) == self.escape {
+                // A trailing escape character is kept as-is
+                result.push(chars.next().unwrap_or(c));
+            } else {
+                result.push(c);
+            }
+        }
+        result
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let delims = self.delims;
+        self.rest = self.rest.trim_start_matches(|c| delims.contains(&c));
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        let mut end = self.rest.len();
+        let mut escaped = false;
+        let mut chars = self.rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if Some(c) == self.escape {
+                escaped = true;
+                chars.next();
+            } else if delims.contains(&c) {
+                end = i;
+                break;
+            }
+        }
+
+        let (token, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        if escaped {
+            Some(Cow::Owned(self.unescape(token)))
+        } else {
+            Some(Cow::Borrowed(token))
+        }
+    }
+}
+
+#[test]
+fn test_tokens() {
+    let input = String::from("  let x =\t42;; ");
+    let toks: Vec<Cow<str>> = Tokens::new(&input, &[' ', '\t', ';']).collect();
+    assert_eq!(toks, vec!["let", "x", "=", "42"]);
+    // No allocation: every token points into `input`
+    assert!(toks.iter().all(|t| matches!(t, Cow::Borrowed(_))));
+
+    assert_eq!(Tokens::new("", &[',']).count(), 0);
+    assert_eq!(Tokens::new(",,,", &[',']).count(), 0);
+    assert_eq!(Tokens::new("héllo wörld", &[' ']).count(), 2);
+}
+
+#[test]
+fn test_tokens_escape() {
+    let toks: Vec<Cow<str>> =
+        Tokens::new(r"a\ b c d\\ e\", &[' ']).with_escape('\\').collect();
+    assert_eq!(toks, vec!["a b", "c", r"d\", "e\\"]);
+    assert!(matches!(toks[0], Cow::Owned(_)));
+    assert!(matches!(toks[1], Cow::Borrowed(_)));
+}
+
+/*
+    key = value files
+
+    parse_kv reads the simple format config files often use:
+
+        # comment
+        name = lecture7
+        greeting = "hello, \"world\""
+
+    It's the Tokenizer's trick again, one level up. Keys and values are
+    slices of the input (&'a str / Cow::Borrowed), so parsing a whole
+    file allocates nothing -- except for a quoted value containing an
+    escape (\" or \\), which has to be rebuilt without the backslashes
+    and comes back as Cow::Owned. A quoted value without escapes is still
+    borrowed: it's just the slice between the quotes.
+
+    Each line becomes one Result, so the caller decides what a bad line
+    means: skip it, or .collect() into a Result and stop at the first.
+    Blank lines and # comments produce nothing.
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    MissingEquals,
+    EmptyKey,
+    UnterminatedQuote,
+    InvalidEscape(char),
+    TrailingCharacters,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match self.kind {
+            ParseErrorKind::MissingEquals => write!(f, "expected key = value"),
+            ParseErrorKind::EmptyKey => write!(f, "missing key before '='"),
+            ParseErrorKind::UnterminatedQuote => {
+                write!(f, "quoted value has no closing quote")
+            }
+            ParseErrorKind::InvalidEscape(c) => {
+                write!(f, "unknown escape \\{} in quoted value", c)
+            }
+            ParseErrorKind::TrailingCharacters => {
+                write!(f, "unexpected text after closing quote")
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+// The inside of a quoted value, from just after the opening quote
+fn parse_quoted(rest: &str) -> Result<Cow<'_, str>, ParseErrorKind> {
+    let mut owned: Option<String> = None;
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                if !rest[i + 1..].trim().is_empty() {
+                    return Err(ParseErrorKind::TrailingCharacters);
+                }
+                return Ok(match owned {
+                    Some(s) => Cow::Owned(s),
+                    None => Cow::Borrowed(&rest[..i]),
+                });
+            }
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some((_, c @ '"')) | Some((_, c @ '\\')) => c,
+                    Some((_, c)) => {
+                        return Err(ParseErrorKind::InvalidEscape(c))
+                    }
+                    None => return Err(ParseErrorKind::UnterminatedQuote),
+                };
+                // First escape: copy everything before it, then keep going
+                owned
+                    .get_or_insert_with(|| rest[..i].to_string())
+                    .push(escaped);
+            }
+            c => {
+                if let Some(s) = owned.as_mut() {
+                    s.push(c);
+                }
+            }
+        }
+    }
+    Err(ParseErrorKind::UnterminatedQuote)
+}
+
+fn parse_kv_line(line: &str) -> Result<(&str, Cow<'_, str>), ParseErrorKind> {
+    let eq = line.find('=').ok_or(ParseErrorKind::MissingEquals)?;
+    let key = line[..eq].trim();
+    if key.is_empty() {
+        return Err(ParseErrorKind::EmptyKey);
+    }
+    let value = line[eq + 1..].trim();
+    match value.strip_prefix('"') {
+        Some(quoted) => Ok((key, parse_quoted(quoted)?)),
+        None => Ok((key, Cow::Borrowed(value))),
+    }
+}
+
+pub fn parse_kv<'a>(
+    input: &'a str,
+) -> impl Iterator<Item = Result<(&'a str, Cow<'a, str>), ParseError>> + 'a {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(n, line)| {
+            parse_kv_line(line).map_err(|kind| ParseError { line: n, kind })
+        })
+}
+
+#[test]
+fn test_parse_kv_borrows() {
+    let text = "# settings\n\
+                name = lecture7\n\
+                \n  volume=11  \n\
+                motto = \"a, b = c\"\n\
+                empty =\n";
+    let pairs: Vec<(&str, Cow<str>)> =
+        parse_kv(text).collect::<Result<_, _>>().unwrap();
+    assert_eq!(
+        pairs,
+        vec![
+            ("name", Cow::Borrowed("lecture7")),
+            ("volume", Cow::Borrowed("11")),
+            ("motto", Cow::Borrowed("a, b = c")),
+            ("empty", Cow::Borrowed("")),
+        ]
+    );
+    // Nothing was copied
+    assert!(pairs.iter().all(|(_, v)| matches!(v, Cow::Borrowed(_))));
+}
+
+#[test]
+fn test_parse_kv_unescapes() {
+    let text = r#"greeting = "hello, \"world\"" "#.to_string()
+        + "\n"
+        + r#"path = "C:\\songs""#;
+    let pairs: Vec<_> = parse_kv(&text).map(Result::unwrap).collect();
+    assert_eq!(pairs[0].1, r#"hello, "world""#);
+    assert_eq!(pairs[1].1, r"C:\songs");
+    assert!(pairs.iter().all(|(_, v)| matches!(v, Cow::Owned(_))));
+}
+
+#[test]
+fn test_parse_kv_errors() {
+    let text = "ok = 1\njust words\n = 2\nq = \"open\nq = \"a\" b\nq = \"\\n\"";
+    let errors: Vec<ParseError> =
+        parse_kv(text).filter_map(Result::err).collect();
+    let kinds: Vec<(usize, ParseErrorKind)> =
+        errors.iter().map(|e| (e.line, e.kind)).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (2, ParseErrorKind::MissingEquals),
+            (3, ParseErrorKind::EmptyKey),
+            (4, ParseErrorKind::UnterminatedQuote),
+            (5, ParseErrorKind::TrailingCharacters),
+            (6, ParseErrorKind::InvalidEscape('n')),
+        ]
+    );
+    assert_eq!(errors[0].to_string(), "line 2: expected key = value");
+    assert_eq!(
+        errors[4].to_string(),
+        "line 6: unknown escape \\n in quoted value"
+    );
+}
+
+#[test]
+fn test_parse_kv_into_config() {
+    use config::Config;
+
+    // Cow<str> is Into<String>: borrowed values are copied into the
+    // Config, an owned (unescaped) one is moved in as-is
+    let mut config = Config::default();
+    for pair in parse_kv("theme = dark\nmotto = \"say \\\"hi\\\"\"") {
+        let (key, value) = pair.unwrap();
+        config.set(key, value);
+    }
+    assert_eq!(config.get("theme"), Some("dark"));
+    assert_eq!(config.get("motto"), Some("say \"hi\""));
+}
+
+/*
+    Word frequencies
+
+    An end-to-end pipeline using pieces from the other modules:
+    split into words -> normalize -> count (consumers::count_frequencies)
+    -> sort.
+
+    Words are maximal runs of alphanumeric characters, lowercased, so
+    "Don't" is the two words "don" and "t". Most common words come first;
+    ties are broken alphabetically so the output is deterministic. The
+    sort compares through references with a comparator: a sort key would
+    have to own its word, cloning every String just to compare it.
+*/
+
+pub fn word_frequencies(text: &str) -> impl Iterator<Item = (String, usize)> {
+    let words = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase);
+    let mut freqs: Vec<(String, usize)> =
+        count_frequencies(words).into_iter().collect();
+    freqs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    freqs.into_iter()
+}
+
+#[test]
+fn test_word_frequencies() {
+    let text = "The cat and the hat. THE END -- and that's that!";
+    let freqs: Vec<(String, usize)> = word_frequencies(text).collect();
+    assert_eq!(freqs[0], ("the".to_string(), 3));
+    assert_eq!(freqs[1], ("and".to_string(), 2));
+    assert_eq!(freqs[2], ("that".to_string(), 2));
+    assert_eq!(freqs.len(), 7);
+    assert_eq!(freqs.iter().map(|(_, c)| c).sum::<usize>(), 11);
+
+    assert_eq!(word_frequencies("").count(), 0);
+    assert_eq!(word_frequencies(" ...!? ").count(), 0);
+}
+
+/*
+    Searching lines (a tiny grep)
+
+    Like copy_increasing_iter1 in iterators.rs, the returned iterator
+    borrows its input, so the return type needs a lifetime: + 'a says
+    "this iterator is only valid as long as haystack (and pattern) are".
+    The lines it yields are slices of the haystack, not copies.
+
+    search_lines_by takes the matching rule as a closure, which makes the
+    plain and case-insensitive versions one-liners.
+*/
+
+pub fn search_lines_by<'a, F>(
+    haystack: &'a str,
+    is_match: F,
+) -> impl Iterator<Item = (usize, &'a str)> + 'a
+where
+    F: Fn(&str) -> bool + 'a,
+{
+    // Line numbers start at 1, like grep -n
+    haystack
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(move |(_, line)| is_match(line))
+}
+
+pub fn search_lines<'a>(
+    haystack: &'a str,
+    pattern: &'a str,
+) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+    search_lines_by(haystack, move |line| line.contains(pattern))
+}
+
+pub fn search_lines_case_insensitive<'a>(
+    haystack: &'a str,
+    pattern: &str,
+) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+    // Lowercase the pattern once, up front; the closure owns the result
+    let pattern = pattern.to_lowercase();
+    search_lines_by(haystack, move |line| {
+        line.to_lowercase().contains(&pattern)
+    })
+}
+
+#[test]
+fn test_search_lines() {
+    let poem = "Roses are red\nviolets are blue\nRust is fast\nand so are you";
+    let hits: Vec<(usize, &str)> = search_lines(poem, "are").collect();
+    assert_eq!(
+        hits,
+        vec![
+            (1, "Roses are red"),
+            (2, "violets are blue"),
+            (4, "and so are you")
+        ]
+    );
+    assert_eq!(search_lines(poem, "rust").count(), 0);
+    assert_eq!(search_lines("", "x").count(), 0);
+}
+
+#[test]
+fn test_search_lines_case_insensitive() {
+    let poem = "Roses are red\nviolets are blue\nRust is fast\nand so are you";
+    let hits: Vec<usize> =
+        search_lines_case_insensitive(poem, "RUST").map(|(n, _)| n).collect();
+    assert_eq!(hits, vec![3]);
+
+    // Any predicate works
+    let long: Vec<usize> =
+        search_lines_by(poem, |l| l.len() > 13).map(|(n, _)| n).collect();
+    assert_eq!(long, vec![2, 4]);
+}
@@ -0,0 +1,55 @@
+/*
+    A tiny deterministic pseudo-random number generator
+
+    The crate has no dependencies, so rather than pulling in `rand` we use
+    SplitMix64: a few lines of arithmetic with good enough statistical
+    quality for sampling and shuffling in examples and tests.
+
+    NOT suitable for anything security related.
+*/
+
+#[derive(Clone, Debug)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in 0..n (n must be positive). Uses rejection sampling to
+    // avoid the bias of a plain `% n`.
+    pub(crate) fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "Rng::below: n must be positive");
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_rng_deterministic() {
+    let a: Vec<u64> = {
+        let mut rng = Rng::new(42);
+        (0..5).map(|_| rng.next_u64()).collect()
+    };
+    let mut rng = Rng::new(42);
+    let b: Vec<u64> = (0..5).map(|_| rng.next_u64()).collect();
+    assert_eq!(a, b);
+
+    let mut rng = Rng::new(7);
+    assert!((0..1000).all(|_| rng.below(10) < 10));
+}
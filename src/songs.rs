@@ -0,0 +1,1830 @@
+/*
+    More of the song domain
+
+    iterators.rs introduced SongName and SongUserProfile to show how to
+    write an iterator for your own type. This module builds the rest of a
+    small music library around them, mostly as a place to implement the
+    standard traits on types that aren't toys.
+*/
+
+use std::borrow::{self, Cow};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Deref, Index, IndexMut, Range};
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use std::slice;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use iterators::{SongName, SongNameError, SongUserProfile};
+use utility_traits::{
+    hash_str, normalize_whitespace, Seconds, Validate, Validated,
+};
+
+/*
+    Playlist and operator overloading
+
+    Operators in Rust are traits: a[i] is sugar for *a.index(i) (or
+    *a.index_mut(i) when assigned to), so any type implementing
+    Index/IndexMut can be indexed.
+
+    The Idx type parameter means a type can be indexed by several kinds
+    of thing. Like Vec, Playlist supports playlist[2] (one SongName) and
+    playlist[1..3] (a slice of them). As with Vec, out-of-range indexing
+    panics; get/get_mut are the checked versions returning Option.
+
+    Each song can have a duration (in Seconds, see utility_traits), kept
+    in a Vec parallel to `songs`. It's None when the length isn't known.
+*/
+
+#[derive(Debug, Default)]
+pub struct Playlist {
+    songs: Vec<SongName>,
+    // Always the same length as songs
+    durations: Vec<Option<Seconds>>,
+    // See "Playlist owners" below
+    owner: Option<Weak<RefCell<SongUserProfile>>>,
+}
+
+impl Playlist {
+    pub fn new() -> Self {
+        Playlist::default()
+    }
+
+    pub fn push(&mut self, song: SongName) {
+        self.songs.push(song);
+        self.durations.push(None);
+    }
+
+    pub fn push_timed(&mut self, song: SongName, duration: Seconds) {
+        self.songs.push(song);
+        self.durations.push(Some(duration));
+    }
+
+    pub fn duration_of(&self, index: usize) -> Option<Seconds> {
+        self.durations.get(index).copied().flatten()
+    }
+
+    // Songs of unknown length count as zero
+    pub fn total_duration(&self) -> Seconds {
+        self.durations.iter().flatten().copied().sum()
+    }
+
+    pub fn len(&self) -> usize {
+        self.songs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.songs.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&SongName> {
+        self.songs.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut SongName> {
+        self.songs.get_mut(index)
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, SongName> {
+        self.songs.iter()
+    }
+}
+
+impl Index<usize> for Playlist {
+    type Output = SongName;
+
+    fn index(&self, index: usize) -> &SongName {
+        &self.songs[index]
+    }
+}
+
+impl IndexMut<usize> for Playlist {
+    fn index_mut(&mut self, index: usize) -> &mut SongName {
+        &mut self.songs[index]
+    }
+}
+
+impl Index<Range<usize>> for Playlist {
+    type Output = [SongName];
+
+    fn index(&self, range: Range<usize>) -> &[SongName] {
+        &self.songs[range]
+    }
+}
+
+#[cfg(test)]
+fn sample_playlist() -> Playlist {
+    use iterators::song;
+
+    let mut p = Playlist::new();
+    for name in &["Help!", "Yesterday", "Let It Be", "Something"] {
+        p.push(song(name));
+    }
+    p
+}
+
+#[test]
+fn test_playlist_index() {
+    use iterators::song;
+
+    let mut p = sample_playlist();
+    assert_eq!(p[1], song("Yesterday"));
+    assert_eq!(&p[1..3], &[song("Yesterday"), song("Let It Be")]);
+    assert!(p[0..0].is_empty());
+
+    p[0] = song("Twist and Shout");
+    assert_eq!(p.get(0), Some(&song("Twist and Shout")));
+    assert_eq!(p.get(4), None);
+
+    *p.get_mut(3).unwrap() = song("Girl");
+    assert_eq!(p.iter().last(), Some(&song("Girl")));
+    assert!(p.get_mut(10).is_none());
+}
+
+#[test]
+#[should_panic]
+fn test_playlist_index_out_of_range() {
+    let p = sample_playlist();
+    let _ = &p[4];
+}
+
+#[test]
+fn test_playlist_durations() {
+    use iterators::song;
+    use utility_traits::Minutes;
+
+    let mut p = Playlist::new();
+    p.push_timed(song("Hey Jude"), Seconds(431));
+    p.push(song("Mystery Track"));
+    p.push_timed(song("Her Majesty"), Seconds(23));
+    p.push_timed(song("Revolution 9"), Seconds::try_from(Minutes(8)).unwrap());
+
+    assert_eq!(p.duration_of(0), Some(Seconds(431)));
+    assert_eq!(p.duration_of(1), None);
+    assert_eq!(p.duration_of(3), Some(Seconds(480)));
+    assert_eq!(p.duration_of(9), None);
+    assert_eq!(p.total_duration(), Seconds(934));
+    assert_eq!(p.total_duration().to_string(), "15:34");
+}
+
+/*
+    Typestate builder
+
+    An export needs a destination path and a format; whether to include
+    disliked songs is optional. A normal builder would check for the
+    required fields in build() and return a Result. A typestate builder
+    makes forgetting one a compile error instead.
+
+    ExportBuilder has two type parameters recording whether the path and
+    the format have been set. Each is one of two empty marker types,
+    Unset or Set. Setting a field consumes the builder and returns one
+    with that parameter changed to Set, and build() is only defined for
+    ExportBuilder<Set, Set>:
+
+        ExportBuilder::new().path("out.csv").build()
+        // error: no method named `build` found for
+        //        `ExportBuilder<Set, Unset>`
+
+    The markers are never stored -- a struct must use its type
+    parameters, and PhantomData<(P, F)> "uses" them at zero size. All of
+    this disappears at compile time: every ExportBuilder is the same
+    Options and bool at runtime.
+*/
+
+pub struct Unset;
+pub struct Set;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    // One song per line
+    Text,
+    // status,name rows
+    Csv,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportRequest {
+    pub path: PathBuf,
+    pub format: ExportFormat,
+    pub include_disliked: bool,
+}
+
+pub struct ExportBuilder<P, F> {
+    path: Option<PathBuf>,
+    format: Option<ExportFormat>,
+    include_disliked: bool,
+    _state: PhantomData<(P, F)>,
+}
+
+impl ExportBuilder<Unset, Unset> {
+    pub fn new() -> Self {
+        ExportBuilder {
+            path: None,
+            format: None,
+            include_disliked: false,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl Default for ExportBuilder<Unset, Unset> {
+    fn default() -> Self {
+        ExportBuilder::new()
+    }
+}
+
+impl<P, F> ExportBuilder<P, F> {
+    pub fn path<Q: AsRef<Path>>(self, path: Q) -> ExportBuilder<Set, F> {
+        ExportBuilder {
+            path: Some(path.as_ref().to_path_buf()),
+            format: self.format,
+            include_disliked: self.include_disliked,
+            _state: PhantomData,
+        }
+    }
+
+    pub fn format(self, format: ExportFormat) -> ExportBuilder<P, Set> {
+        ExportBuilder {
+            path: self.path,
+            format: Some(format),
+            include_disliked: self.include_disliked,
+            _state: PhantomData,
+        }
+    }
+
+    // Optional, so it doesn't change the state
+    pub fn include_disliked(mut self, yes: bool) -> Self {
+        self.include_disliked = yes;
+        self
+    }
+}
+
+impl ExportBuilder<Set, Set> {
+    pub fn build(self) -> ExportRequest {
+        // The type says both were set, so these can't fail
+        ExportRequest {
+            path: self.path.unwrap(),
+            format: self.format.unwrap(),
+            include_disliked: self.include_disliked,
+        }
+    }
+}
+
+impl ExportRequest {
+    // Only validated profiles get exported (see Validated below)
+    pub fn render(&self, profile: &Validated<SongUserProfile>) -> String {
+        let mut rows: Vec<(&str, &SongName)> =
+            profile.liked_songs().iter().map(|s| ("liked", s)).collect();
+        if self.include_disliked {
+            rows.extend(
+                profile.disliked_songs().iter().map(|s| ("disliked", s)),
+            );
+        }
+        let mut out = String::new();
+        for (status, song) in rows {
+            match self.format {
+                ExportFormat::Text => out.push_str(song.as_str()),
+                ExportFormat::Csv => {
+                    out.push_str(status);
+                    out.push(',');
+                    out.push_str(&csv_quote(song.as_str()));
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+// Quote a CSV field if it needs it (the inverse of parsing::CsvRows)
+fn csv_quote(field: &str) -> String {
+    if field.contains(',') || field.contains('"') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[test]
+fn test_export_builder() {
+    // Required fields in either order
+    let a =
+        ExportBuilder::new().path("out.csv").format(ExportFormat::Csv).build();
+    let b = ExportBuilder::new()
+        .format(ExportFormat::Csv)
+        .include_disliked(true)
+        .path(PathBuf::from("out.csv"))
+        .build();
+    assert_eq!(a.path, b.path);
+    assert!(!a.include_disliked && b.include_disliked);
+
+    // The markers take no space
+    assert_eq!(
+        std::mem::size_of::<ExportBuilder<Set, Set>>(),
+        std::mem::size_of::<ExportBuilder<Unset, Unset>>()
+    );
+}
+
+#[test]
+fn test_export_render() {
+    use iterators::song;
+    use parsing::CsvRows;
+
+    let mut profile = SongUserProfile::new("alice");
+    profile.like(song("Help!"));
+    profile.like(song("Love, Love, Love"));
+    profile.dislike(song("Revolution 9"));
+    let profile = profile.validate().unwrap();
+
+    let text =
+        ExportBuilder::new().path("x").format(ExportFormat::Text).build();
+    assert_eq!(text.render(&profile), "Help!\nLove, Love, Love\n");
+
+    let csv = ExportBuilder::new()
+        .path("x")
+        .format(ExportFormat::Csv)
+        .include_disliked(true)
+        .build();
+    let rendered = csv.render(&profile);
+    // Round trip through our own CSV reader
+    let rows: Vec<Vec<String>> =
+        CsvRows::new(rendered.as_bytes()).collect::<Result<_, _>>().unwrap();
+    assert_eq!(rows[1], vec!["liked", "Love, Love, Love"]);
+    assert_eq!(rows[2], vec!["disliked", "Revolution 9"]);
+}
+
+/*
+    Validating profiles
+
+    A profile is only worth exporting (or saving, see encoding.rs) if it
+    has a username and doesn't both like and dislike the same song. APIs that
+    depend on that take &Validated<SongUserProfile> rather than
+    &SongUserProfile, so they never have to check again themselves.
+*/
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProfileError {
+    EmptyUsername,
+    LikedAndDisliked(SongName),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfileError::EmptyUsername => write!(f, "profile has no username"),
+            ProfileError::LikedAndDisliked(song) => {
+                write!(f, "\"{}\" is both liked and disliked", song)
+            }
+        }
+    }
+}
+
+impl Error for ProfileError {}
+
+impl Validate for SongUserProfile {
+    type Error = ProfileError;
+
+    fn check(&self) -> Result<(), ProfileError> {
+        if self.username().trim().is_empty() {
+            return Err(ProfileError::EmptyUsername);
+        }
+        match self
+            .liked_songs()
+            .iter()
+            .find(|s| self.disliked_songs().contains(s))
+        {
+            Some(song) => Err(ProfileError::LikedAndDisliked(song.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_validate_profile() {
+    use iterators::song;
+
+    let mut anonymous = SongUserProfile::default();
+    anonymous.like(song("Help!"));
+    assert_eq!(anonymous.validate().err(), Some(ProfileError::EmptyUsername));
+
+    let mut torn = SongUserProfile::new("bob");
+    torn.like(song("Help!"));
+    torn.dislike(song("Help!"));
+    let err = torn.validate().unwrap_err();
+    assert_eq!(err.to_string(), "\"Help!\" is both liked and disliked");
+
+    let fine = SongUserProfile::new("carol").validate().unwrap();
+    // Read access through Deref
+    assert_eq!(fine.username(), "carol");
+}
+
+/*
+    A shared, live profile: Rc<RefCell<..>>
+
+    SongIterator borrows the profile (&'a SongUserProfile), so while a
+    playback iterator exists, nothing can like a new song -- that would
+    need &mut. Sometimes that's exactly what we want to allow: the
+    player keeps going while the user likes songs, and the player should
+    pick them up.
+
+    SharedProfile is a cheap-to-clone handle: Rc gives every clone shared
+    ownership of one profile, and RefCell lets any of them mutate it
+    through a shared reference. The rules are still enforced, just at
+    runtime: a borrow_mut() while any other borrow is alive panics. Every
+    method here borrows only for its own duration and never returns a
+    reference into the profile, so that can't happen through this API.
+
+    snapshot() clones the current state, for code that wants a plain
+    SongUserProfile to look at without holding a borrow.
+*/
+
+#[derive(Clone)]
+pub struct SharedProfile {
+    inner: Rc<RefCell<SongUserProfile>>,
+}
+
+impl SharedProfile {
+    pub fn new(profile: SongUserProfile) -> Self {
+        SharedProfile { inner: Rc::new(RefCell::new(profile)) }
+    }
+
+    pub fn like(&self, song: SongName) {
+        self.inner.borrow_mut().like(song);
+    }
+
+    pub fn record_listen(&self) {
+        self.inner.borrow().record_listen();
+    }
+
+    pub fn snapshot(&self) -> SongUserProfile {
+        self.inner.borrow().clone()
+    }
+
+    // Plays liked songs in order, including ones liked after it started
+    pub fn playback(&self) -> LivePlayback {
+        LivePlayback { profile: self.clone(), next_index: 0 }
+    }
+}
+
+pub struct LivePlayback {
+    profile: SharedProfile,
+    next_index: usize,
+}
+
+impl Iterator for LivePlayback {
+    type Item = SongName;
+
+    fn next(&mut self) -> Option<SongName> {
+        let profile = self.profile.inner.borrow();
+        let song = profile.liked_songs().get(self.next_index)?.clone();
+        self.next_index += 1;
+        Some(song)
+    }
+}
+
+#[test]
+fn test_shared_profile_live_updates() {
+    use iterators::song;
+
+    let shared = SharedProfile::new(SongUserProfile::new("alice"));
+    shared.like(song("Help!"));
+
+    let mut player = shared.playback();
+    assert_eq!(player.next(), Some(song("Help!")));
+    shared.record_listen();
+    // Caught up with the profile...
+    assert_eq!(player.next(), None);
+
+    // ...until another handle likes something
+    let other_handle = shared.clone();
+    other_handle.like(song("Yesterday"));
+    assert_eq!(player.next(), Some(song("Yesterday")));
+    other_handle.record_listen();
+
+    let snap = shared.snapshot();
+    assert_eq!(snap.total_listens(), 2);
+    assert_eq!(snap.liked_songs().len(), 2);
+}
+
+#[test]
+fn test_shared_profile_snapshot_is_detached() {
+    use iterators::song;
+
+    let shared = SharedProfile::new(SongUserProfile::new("bob"));
+    let before = shared.snapshot();
+    shared.like(song("Help!"));
+    assert!(before.liked_songs().is_empty());
+    assert_eq!(Rc::strong_count(&shared.inner), 1);
+    let _player = shared.playback();
+    assert_eq!(Rc::strong_count(&shared.inner), 2);
+}
+
+/*
+    A thread-safe profile: Arc<Mutex<..>> and an atomic counter
+
+    SharedProfile can't cross threads: Rc's count isn't updated
+    atomically, and RefCell's borrow flag isn't either, so neither type
+    is Send/Sync and the compiler rejects moving a SharedProfile into
+    thread::spawn. The thread-safe equivalents are Arc (atomic refcount)
+    and Mutex (a lock instead of a borrow flag).
+
+    Listens are recorded far more often than anything else, and a lock
+    for every play would make the threads queue up. So the listen count
+    lives outside the Mutex in an AtomicUsize: fetch_add is a single
+    atomic instruction, no lock needed. Relaxed ordering is enough: we
+    only need the count to be right, not to order it against other
+    memory operations. The atomic count is folded into the profile when
+    taking a snapshot.
+*/
+
+#[derive(Clone)]
+pub struct ConcurrentProfile {
+    profile: Arc<Mutex<SongUserProfile>>,
+    pending_listens: Arc<AtomicUsize>,
+}
+
+impl ConcurrentProfile {
+    pub fn new(profile: SongUserProfile) -> Self {
+        ConcurrentProfile {
+            profile: Arc::new(Mutex::new(profile)),
+            pending_listens: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn like(&self, song: SongName) {
+        self.profile.lock().unwrap().like(song);
+    }
+
+    pub fn record_listen(&self) {
+        self.pending_listens.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn total_listens(&self) -> usize {
+        let profile = self.profile.lock().unwrap();
+        profile.total_listens() + self.pending_listens.load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self) -> SongUserProfile {
+        let profile = self.profile.lock().unwrap().clone();
+        profile.add_listens(self.pending_listens.load(Ordering::Relaxed));
+        profile
+    }
+}
+
+#[test]
+fn test_concurrent_profile() {
+    use iterators::song;
+    use std::thread;
+
+    let shared = ConcurrentProfile::new(SongUserProfile::new("alice"));
+    let handles: Vec<_> = (0..8)
+        .map(|t| {
+            let profile = shared.clone();
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    profile.record_listen();
+                }
+                // Everyone likes song 0; each thread also likes its own
+                profile.like(song("Song 0"));
+                profile.like(song(&format!("Song {}", t + 1)));
+            })
+        })
+        .collect();
+    for h in handles {
+        h.join().unwrap();
+    }
+
+    assert_eq!(shared.total_listens(), 8000);
+    let snap = shared.snapshot();
+    assert_eq!(snap.total_listens(), 8000);
+    assert_eq!(snap.liked_songs().len(), 9);
+}
+
+/*
+    Playlist owners: Weak back-references
+
+    A playlist made by a user should know who made it. If it held a
+    SharedProfile (a strong Rc), then as long as the playlist existed the
+    profile couldn't be freed -- and if the profile ever held its
+    playlists too, that would be a cycle that's never freed at all, since
+    each side keeps the other's count above zero.
+
+    So the playlist holds a Weak instead. A Weak doesn't keep the profile
+    alive; it can only be upgrade()d back into an Rc, which gives None
+    once the profile has been dropped. owner() does exactly that.
+
+    The owner is bookkeeping, not content: two playlists with the same
+    songs are equal whoever made them, so PartialEq is written by hand to
+    ignore it (Weak doesn't implement PartialEq anyway).
+*/
+
+// Only the songs count: durations are ignored so Eq/Hash match
+// [SongName] -- see "Playlists as map keys" at the end of this file
+impl PartialEq for Playlist {
+    fn eq(&self, other: &Playlist) -> bool {
+        self.songs == other.songs
+    }
+}
+
+impl Eq for Playlist {}
+
+impl Playlist {
+    pub fn owner(&self) -> Option<SharedProfile> {
+        let inner = self.owner.as_ref()?.upgrade()?;
+        Some(SharedProfile { inner })
+    }
+}
+
+impl SharedProfile {
+    pub fn new_playlist(&self) -> Playlist {
+        Playlist { owner: Some(Rc::downgrade(&self.inner)), ..Playlist::new() }
+    }
+}
+
+#[test]
+fn test_playlist_eq_ignores_durations() {
+    use iterators::song;
+
+    let mut timed = Playlist::new();
+    timed.push_timed(song("Help!"), Seconds(139));
+    timed.push_timed(song("Yesterday"), Seconds(125));
+    let mut untimed = Playlist::new();
+    untimed.push(song("Help!"));
+    untimed.push(song("Yesterday"));
+    let mut retimed = Playlist::new();
+    retimed.push_timed(song("Help!"), Seconds(1));
+    retimed.push(song("Yesterday"));
+    assert_eq!(timed, untimed);
+    assert_eq!(timed, retimed);
+
+    // The songs and their order still matter
+    let mut reversed = Playlist::new();
+    reversed.push(song("Yesterday"));
+    reversed.push(song("Help!"));
+    assert_ne!(untimed, reversed);
+}
+
+#[test]
+fn test_playlist_owner() {
+    use iterators::song;
+
+    let alice = SharedProfile::new(SongUserProfile::new("alice"));
+    let mut mix = alice.new_playlist();
+    mix.push(song("Help!"));
+
+    // The owner can be reached (and updated) from the playlist
+    mix.owner().unwrap().like(song("Help!"));
+    assert_eq!(alice.snapshot().liked_songs().len(), 1);
+    assert_eq!(Rc::weak_count(&alice.inner), 1);
+
+    // Equal to an unowned playlist with the same songs
+    let mut plain = Playlist::new();
+    plain.push(song("Help!"));
+    assert_eq!(mix, plain);
+    assert!(plain.owner().is_none());
+
+    // The playlist doesn't keep alice alive
+    let copy = mix.clone();
+    drop(alice);
+    assert!(mix.owner().is_none());
+    assert!(copy.owner().is_none());
+}
+
+/*
+    Playlist's Clone forwards clone_from to each field, like
+    SongUserProfile's (see the Clone section at the end of iterators.rs).
+*/
+
+impl Clone for Playlist {
+    fn clone(&self) -> Self {
+        Playlist {
+            songs: self.songs.clone(),
+            durations: self.durations.clone(),
+            owner: self.owner.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.songs.clone_from(&source.songs);
+        self.durations.clone_from(&source.durations);
+        self.owner.clone_from(&source.owner);
+    }
+}
+
+#[test]
+fn test_playlist_clone_from() {
+    use alloc_counter::count_allocations;
+    use iterators::song;
+
+    let source = sample_playlist();
+    let (mut copy, clone_allocs) = count_allocations(|| source.clone());
+    // Two Vecs plus a String per song
+    assert_eq!(clone_allocs, 2 + source.len());
+
+    copy[0] = song("Twist and Shout");
+    let ((), allocs) = count_allocations(|| copy.clone_from(&source));
+    assert_eq!(allocs, 0);
+    assert_eq!(copy, source);
+}
+
+/*
+    Cow over a slice
+
+    Song names from imports often have stray spaces: "  Let  It Be ".
+    clean_song_names tidies a whole list with normalize_whitespace, but
+    most lists are clean already, and cloning every name just to find
+    nothing changed would be a waste.
+
+    Cow isn't only for str: Cow<'a, [SongName]> is either a borrowed
+    &'a [SongName] or an owned Vec<SongName> ([T]'s ToOwned type is
+    Vec<T>). So we scan first, return the caller's slice untouched if
+    every name is clean, and only build a new Vec -- starting with a copy
+    of the clean prefix -- once a name actually needs fixing.
+*/
+
+pub fn clean_song_names(names: &[SongName]) -> Cow<'_, [SongName]> {
+    let needs_fixing = |name: &SongName| {
+        matches!(normalize_whitespace(name.as_str()), Cow::Owned(_))
+    };
+    let first_dirty = match names.iter().position(needs_fixing) {
+        Some(i) => i,
+        None => return Cow::Borrowed(names),
+    };
+    let mut cleaned = names[..first_dirty].to_vec();
+    for name in &names[first_dirty..] {
+        cleaned.push(match normalize_whitespace(name.as_str()) {
+            Cow::Borrowed(_) => name.clone(),
+            // Trimming and collapsing spaces can't make a valid name
+            // invalid: it's still non-empty, shorter, and has no new chars
+            Cow::Owned(fixed) => SongName::new(fixed).unwrap(),
+        });
+    }
+    Cow::Owned(cleaned)
+}
+
+#[test]
+fn test_clean_song_names_borrows_when_clean() {
+    use iterators::song;
+
+    let clean = [song("Help!"), song("Let It Be")];
+    let result = clean_song_names(&clean);
+    assert!(matches!(result, Cow::Borrowed(_)));
+    assert_eq!(&*result, &clean[..]);
+    assert!(matches!(clean_song_names(&[]), Cow::Borrowed(_)));
+}
+
+#[test]
+fn test_clean_song_names_fixes() {
+    use iterators::song;
+
+    let messy = [song("Help!"), song("  Let  It Be "), song("Yesterday ")];
+    let result = clean_song_names(&messy);
+    assert!(matches!(result, Cow::Owned(_)));
+    assert_eq!(
+        result.into_owned(),
+        vec![song("Help!"), song("Let It Be"), song("Yesterday")]
+    );
+}
+
+/*
+    Borrow vs AsRef as bounds
+
+    Two ways to let callers search a playlist by name without building a
+    SongName first:
+
+    find_by_name_borrow<Q>(&Q) where SongName: Borrow<Q>
+        Compares each song, borrowed as a Q, with the argument. Q can be
+        SongName itself (everything borrows as itself) or str (SongName:
+        Borrow<str>). Nothing else: &String won't compile, because
+        SongName isn't Borrow<String>.
+
+    find_by_name_asref(impl AsRef<str>)
+        Takes anything that can show a &str: &str, String, &String,
+        SongName, &SongName, Cow<str>... and compares strings.
+
+    So AsRef accepts more argument types. Borrow's advantage is the
+    guarantee behind it (equal as Q means equal as SongName) which is
+    what a hash-based lookup needs -- for a linear search like this
+    either is fine, and AsRef is friendlier.
+*/
+
+impl Playlist {
+    // Borrow isn't imported into this module: with it in scope,
+    // .borrow() on an Rc<RefCell<..>> (SharedProfile) becomes ambiguous
+    pub fn find_by_name_borrow<Q>(&self, name: &Q) -> Option<usize>
+    where
+        SongName: borrow::Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        self.songs
+            .iter()
+            .position(|song| borrow::Borrow::<Q>::borrow(song) == name)
+    }
+
+    pub fn find_by_name_asref<S: AsRef<str>>(&self, name: S) -> Option<usize> {
+        let name = name.as_ref();
+        self.songs.iter().position(|song| song.as_str() == name)
+    }
+}
+
+#[test]
+fn test_find_by_name_borrow() {
+    use iterators::song;
+
+    let p = sample_playlist();
+    // Q = str
+    assert_eq!(p.find_by_name_borrow("Let It Be"), Some(2));
+    // Q = SongName
+    assert_eq!(p.find_by_name_borrow(&song("Help!")), Some(0));
+    // &String deref-coerces to &str only if we ask for it:
+    let owned = String::from("Something");
+    assert_eq!(p.find_by_name_borrow(owned.as_str()), Some(3));
+    // p.find_by_name_borrow(&owned) doesn't compile:
+    // SongName: Borrow<String> is not satisfied
+    assert_eq!(p.find_by_name_borrow("Girl"), None);
+}
+
+#[test]
+fn test_find_by_name_asref() {
+    use iterators::song;
+
+    let p = sample_playlist();
+    let owned = String::from("Something");
+    assert_eq!(p.find_by_name_asref("Yesterday"), Some(1));
+    assert_eq!(p.find_by_name_asref(&owned), Some(3));
+    assert_eq!(p.find_by_name_asref(owned), Some(3));
+    let help = song("Help!");
+    assert_eq!(p.find_by_name_asref(&help), Some(0));
+    assert_eq!(p.find_by_name_asref(help), Some(0));
+    assert_eq!(p.find_by_name_asref(Cow::Borrowed("Let It Be")), Some(2));
+    assert_eq!(p.find_by_name_asref("help!"), None);
+}
+
+/*
+    A borrowed/owned pair of our own: SongSlice and SongList
+
+    str and String are the model: str is an unsized slice type that only
+    exists behind a pointer (&str, Box<str>), String owns one, and the
+    two are tied together by String: Borrow<str> and str: ToOwned<Owned =
+    String>. That pairing is what Cow<str> is built on.
+
+    SongSlice is the same trick over [SongName]. A struct whose last (here
+    only) field is unsized is itself unsized, so SongSlice can only be
+    used as &SongSlice. #[repr(transparent)] guarantees it's laid out
+    exactly like [SongName], which is what makes the pointer cast in
+    SongSlice::new sound -- std builds &Path from &OsStr the same way.
+
+    With ToOwned and Borrow in place, Cow<SongSlice> works: clean_songs
+    below hands back the caller's list untouched, or a new SongList.
+*/
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct SongSlice([SongName]);
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SongList(Vec<SongName>);
+
+impl SongSlice {
+    pub fn new(songs: &[SongName]) -> &SongSlice {
+        // Same layout (repr(transparent)), same lifetime
+        unsafe { &*(songs as *const [SongName] as *const SongSlice) }
+    }
+
+    pub fn as_slice(&self) -> &[SongName] {
+        &self.0
+    }
+
+    pub fn titles(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(SongName::as_str)
+    }
+}
+
+// Everything a slice can do (len, iter, contains, [i]...)
+impl Deref for SongSlice {
+    type Target = [SongName];
+
+    fn deref(&self) -> &[SongName] {
+        &self.0
+    }
+}
+
+impl ToOwned for SongSlice {
+    type Owned = SongList;
+
+    fn to_owned(&self) -> SongList {
+        SongList(self.0.to_vec())
+    }
+}
+
+impl SongList {
+    pub fn new() -> Self {
+        SongList::default()
+    }
+
+    pub fn push(&mut self, song: SongName) {
+        self.0.push(song);
+    }
+
+    pub fn into_vec(self) -> Vec<SongName> {
+        self.0
+    }
+}
+
+impl From<Vec<SongName>> for SongList {
+    fn from(songs: Vec<SongName>) -> Self {
+        SongList(songs)
+    }
+}
+
+// Like String -> str: a &SongList can be used wherever a &SongSlice is
+// expected, and picks up all of SongSlice's (and so [SongName]'s) methods
+impl Deref for SongList {
+    type Target = SongSlice;
+
+    fn deref(&self) -> &SongSlice {
+        SongSlice::new(&self.0)
+    }
+}
+
+// Consistent with Hash/Eq: both derive from the same [SongName]
+impl borrow::Borrow<SongSlice> for SongList {
+    fn borrow(&self) -> &SongSlice {
+        self
+    }
+}
+
+pub fn clean_songs(songs: &SongSlice) -> Cow<'_, SongSlice> {
+    match clean_song_names(songs.as_slice()) {
+        Cow::Borrowed(same) => Cow::Borrowed(SongSlice::new(same)),
+        Cow::Owned(cleaned) => Cow::Owned(SongList(cleaned)),
+    }
+}
+
+#[test]
+fn test_song_slice_and_list() {
+    use iterators::song;
+
+    let mut list = SongList::new();
+    list.push(song("Help!"));
+    list.push(song("Yesterday"));
+    // Deref twice: SongList -> SongSlice -> [SongName]
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[1], song("Yesterday"));
+    assert_eq!(list.titles().collect::<Vec<_>>(), vec!["Help!", "Yesterday"]);
+
+    let slice: &SongSlice = &list;
+    assert_eq!(slice.to_owned(), list);
+
+    // Owned keys, borrowed lookups
+    let mut seen = HashSet::new();
+    seen.insert(list.clone());
+    let names = [song("Help!"), song("Yesterday")];
+    assert!(seen.contains(SongSlice::new(&names)));
+    assert!(!seen.contains(SongSlice::new(&names[..1])));
+    assert_eq!(list.into_vec(), names.to_vec());
+}
+
+#[test]
+fn test_clean_songs_cow() {
+    use iterators::song;
+
+    let clean = SongList::from(vec![song("Help!"), song("Let It Be")]);
+    assert!(matches!(clean_songs(&clean), Cow::Borrowed(_)));
+
+    let messy = [song("Help!"), song(" Let  It Be")];
+    let cleaned = clean_songs(SongSlice::new(&messy));
+    assert!(matches!(cleaned, Cow::Owned(_)));
+    assert_eq!(cleaned.into_owned(), clean);
+}
+
+/*
+    Typed ids
+
+    A user id and a song id are both just numbers, and as bare u64s
+    nothing stops code from passing one where the other belongs --
+    arena.get(user_id) would compile and quietly return the wrong song.
+    Wrapping each in its own newtype makes that a type error, at no cost
+    at runtime: a UserId is a u64 in memory.
+
+    Conversions are deliberately explicit. There's no From<u64>, so a
+    plain number never becomes an id through a stray .into(); it takes
+    from_raw (for ids loaded from storage) and raw() to go back.
+
+    Display writes the kind as well as the number ("user:42"), and
+    FromStr expects the same form, so ids that travel through text (logs,
+    URLs, config files) can't be mixed up either.
+
+    The two types are identical apart from name and prefix, so a macro
+    writes them, like unit_ops! does for Seconds and friends.
+
+    That mix-ups don't compile is the whole point, so it's tested: the
+    doc comments on UserId and SongId hold compile_fail doctests, which
+    `cargo test` runs and which pass only if the compiler rejects them.
+    Stable rustdoc doesn't check the error code after compile_fail, so
+    a typo would "pass" too: each one is a single line away from a
+    doctest that does compile, with the same imports and constructors.
+*/
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdParseError {
+    pub kind: &'static str,
+    pub input: String,
+}
+
+impl fmt::Display for IdParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} is not a {} id (expected {}:<number>)",
+            self.input, self.kind, self.kind
+        )
+    }
+}
+
+impl Error for IdParseError {}
+
+macro_rules! id_type {
+    ($(#[$attr:meta])* $name:ident, $kind:expr) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(u64);
+
+        impl $name {
+            pub fn from_raw(raw: u64) -> Self {
+                $name(raw)
+            }
+
+            pub fn raw(self) -> u64 {
+                self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}:{}", $kind, self.0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = IdParseError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let err = || IdParseError { kind: $kind, input: s.to_string() };
+                let digits = s
+                    .strip_prefix($kind)
+                    .and_then(|rest| rest.strip_prefix(':'))
+                    .ok_or_else(err)?;
+                // u64's parser would accept a leading '+'
+                if !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(err());
+                }
+                digits.parse().map($name).map_err(|_| err())
+            }
+        }
+    };
+}
+
+id_type!(
+    /// A user's id.
+    ///
+    /// ```
+    /// use lecture7::songs::{SongId, UserId};
+    /// let user: UserId = UserId::from_raw(1);
+    /// assert_eq!(user.raw(), SongId::from_raw(1).raw());
+    /// ```
+    ///
+    /// It isn't a SongId, though:
+    ///
+    /// ```compile_fail,E0308
+    /// use lecture7::songs::{SongId, UserId};
+    /// let song: SongId = UserId::from_raw(1);
+    /// ```
+    ///
+    /// and the two can't even be compared:
+    ///
+    /// ```compile_fail,E0308
+    /// use lecture7::songs::{SongId, UserId};
+    /// assert!(UserId::from_raw(1) != SongId::from_raw(1));
+    /// ```
+    UserId,
+    "user"
+);
+id_type!(
+    /// A song's id, as handed out by a SongArena.
+    ///
+    /// ```
+    /// use lecture7::iterators::SongName;
+    /// use lecture7::songs::SongArena;
+    /// let mut arena = SongArena::new();
+    /// let id = arena.intern(SongName::new("Help!").unwrap());
+    /// assert_eq!(arena.get(id).as_str(), "Help!");
+    /// ```
+    ///
+    /// A UserId can't be used to look a song up:
+    ///
+    /// ```compile_fail,E0308
+    /// use lecture7::songs::{SongArena, UserId};
+    /// let arena = SongArena::new();
+    /// arena.get(UserId::from_raw(0));
+    /// ```
+    ///
+    /// and a bare number doesn't turn into one by accident:
+    ///
+    /// ```compile_fail,E0277
+    /// use lecture7::songs::SongId;
+    /// let id: SongId = 1u64.into();
+    /// ```
+    SongId,
+    "song"
+);
+
+#[test]
+fn test_id_display_and_parse() {
+    let user = UserId::from_raw(42);
+    assert_eq!(user.to_string(), "user:42");
+    assert_eq!("user:42".parse(), Ok(user));
+    assert_eq!("song:7".parse::<SongId>().map(SongId::raw), Ok(7));
+    let max = format!("song:{}", u64::MAX);
+    assert_eq!(max.parse(), Ok(SongId::from_raw(u64::MAX)));
+
+    // Each type only accepts its own prefix
+    let err = "song:7".parse::<UserId>().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "\"song:7\" is not a user id (expected user:<number>)"
+    );
+    for bad in &[
+        "user:",
+        "user:+1",
+        "user:-1",
+        "user: 1",
+        "42",
+        "user:99999999999999999999",
+    ] {
+        assert!(bad.parse::<UserId>().is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_ids_are_distinct_types() {
+    use std::collections::BTreeSet;
+
+    // Ordered and hashable like the numbers inside
+    let ids: BTreeSet<SongId> =
+        [3, 1, 2, 1].iter().map(|&n| SongId::from_raw(n)).collect();
+    let raw: Vec<u64> = ids.iter().map(|id| id.raw()).collect();
+    assert_eq!(raw, vec![1, 2, 3]);
+    assert!(UserId::from_raw(1) < UserId::from_raw(2));
+
+    // What doesn't compile is checked by the compile_fail doctests on
+    // UserId and SongId
+}
+
+/*
+    An arena of song names
+
+    Every SongUserProfile keeps its own SongName for each song it likes,
+    so a million users liking the same hit means a million copies of its
+    name, and play_songs() clones the String again for every song it
+    plays.
+
+    A SongArena stores each distinct name once, in one Vec. Anything that
+    needs to refer to a song keeps a SongId instead: a Copy index into
+    that Vec, eight bytes, no allocation. Looking a name up is just
+    indexing, so iterating a profile's songs hands out &SongName
+    borrowed from the arena -- no clones at all.
+
+    intern() also needs to go the other way, from a name to its id. A
+    HashMap<SongName, SongId> would hold a second copy of every name, so
+    like TextStore in utility_traits, the map is keyed by the name's hash
+    and the (rare) colliding ids are compared against `names`. The
+    SongName passed to intern() is moved into the Vec as it is; the
+    arena never copies the text.
+
+    The catch is the usual one for handles: a SongId only means something
+    to the arena that issued it. get() panics on an id that's out of
+    range, like slice indexing.
+*/
+
+#[derive(Debug, Default)]
+pub struct SongArena {
+    names: Vec<SongName>,
+    // Ids by the hash of their name; see above
+    by_hash: HashMap<u64, Vec<SongId>>,
+}
+
+impl SongArena {
+    pub fn new() -> Self {
+        SongArena::default()
+    }
+
+    // The same name always gets the same id
+    pub fn intern(&mut self, name: SongName) -> SongId {
+        let hash = hash_str(name.as_str());
+        if let Some(id) = self.find_hashed(name.as_str(), hash) {
+            return id;
+        }
+        let id = SongId(self.names.len() as u64);
+        self.names.push(name);
+        self.by_hash.entry(hash).or_default().push(id);
+        id
+    }
+
+    fn find_hashed(&self, name: &str, hash: u64) -> Option<SongId> {
+        let ids = self.by_hash.get(&hash)?;
+        ids.iter().copied().find(|&id| self.get(id).as_str() == name)
+    }
+
+    pub fn find(&self, name: &str) -> Option<SongId> {
+        self.find_hashed(name, hash_str(name))
+    }
+
+    pub fn get(&self, id: SongId) -> &SongName {
+        &self.names[id.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (SongId, &SongName)> {
+        self.names.iter().enumerate().map(|(i, name)| (SongId(i as u64), name))
+    }
+}
+
+impl Index<SongId> for SongArena {
+    type Output = SongName;
+
+    fn index(&self, id: SongId) -> &SongName {
+        self.get(id)
+    }
+}
+
+/*
+    A profile that stores ids
+
+    The id-based counterpart of SongUserProfile's song lists. It's built
+    from an ordinary profile (interning its songs), and everything that
+    reads song names takes the arena to resolve ids against. Each one
+    also carries the UserId it's stored under.
+
+    Why not switch SongUserProfile itself over to ids? A SongId is only
+    meaningful next to its arena, so every SongUserProfile::new, like()
+    and liked_songs() would need one passed in (or a shared
+    Rc<RefCell<SongArena>>, and then liked_songs() can't hand out a plain
+    &[SongName]). That would rewrite the lecture's own example, whose
+    point is play_songs() yielding owned SongNames via .cloned(), along
+    with JSON, the binary encoding and everything else built on it. So
+    SongUserProfile stays the simple owning version, and CompactProfile
+    is what to use when there are many profiles to store.
+*/
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompactProfile {
+    id: UserId,
+    username: String,
+    liked: Vec<SongId>,
+    disliked: Vec<SongId>,
+}
+
+impl CompactProfile {
+    pub fn from_profile(
+        id: UserId,
+        profile: &SongUserProfile,
+        arena: &mut SongArena,
+    ) -> Self {
+        let mut intern_all = |songs: &[SongName]| {
+            songs.iter().map(|s| arena.intern(s.clone())).collect()
+        };
+        CompactProfile {
+            id,
+            username: profile.username().to_string(),
+            liked: intern_all(profile.liked_songs()),
+            disliked: intern_all(profile.disliked_songs()),
+        }
+    }
+
+    pub fn id(&self) -> UserId {
+        self.id
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn liked_ids(&self) -> &[SongId] {
+        &self.liked
+    }
+
+    pub fn disliked_ids(&self) -> &[SongId] {
+        &self.disliked
+    }
+
+    pub fn like(&mut self, id: SongId) {
+        if !self.liked.contains(&id) {
+            self.liked.push(id);
+        }
+    }
+
+    // Like SongUserProfile::play_songs, but borrowing instead of cloning
+    pub fn play_songs<'a>(
+        &'a self,
+        arena: &'a SongArena,
+    ) -> impl Iterator<Item = &'a SongName> + 'a {
+        self.liked.iter().map(move |&id| arena.get(id))
+    }
+}
+
+#[test]
+fn test_song_arena() {
+    use iterators::song;
+
+    let mut arena = SongArena::new();
+    let help = arena.intern(song("Help!"));
+    let yesterday = arena.intern(song("Yesterday"));
+    assert_eq!(arena.intern(song("Help!")), help);
+    assert_ne!(help, yesterday);
+    assert_eq!(arena.len(), 2);
+
+    assert_eq!(arena.get(yesterday), &song("Yesterday"));
+    assert_eq!(arena[help].as_str(), "Help!");
+    assert_eq!(arena.find("Yesterday"), Some(yesterday));
+    assert_eq!(arena.find("Girl"), None);
+    let all: Vec<_> =
+        arena.iter().map(|(id, name)| (id, name.as_str())).collect();
+    assert_eq!(all, vec![(help, "Help!"), (yesterday, "Yesterday")]);
+}
+
+#[test]
+fn test_song_arena_stores_names_once() {
+    use iterators::song;
+
+    // The arena keeps the very String it was given; nothing is copied
+    let mut arena = SongArena::new();
+    let name = song("Here Comes the Sun");
+    let text = name.as_str().as_ptr();
+    let id = arena.intern(name);
+    assert_eq!(arena[id].as_str().as_ptr(), text);
+
+    // Interning it again finds the original and drops the duplicate
+    assert_eq!(arena.intern(song("Here Comes the Sun")), id);
+    assert_eq!(arena.len(), 1);
+    assert_eq!(arena[id].as_str().as_ptr(), text);
+}
+
+#[test]
+fn test_compact_profiles_share_names() {
+    use alloc_counter::count_allocations;
+    use iterators::song;
+
+    let mut arena = SongArena::new();
+    let mut alice = SongUserProfile::new("alice");
+    alice.like(song("Help!"));
+    alice.like(song("Yesterday"));
+    let mut bob = SongUserProfile::new("bob");
+    bob.like(song("Yesterday"));
+    bob.dislike(song("Help!"));
+
+    let alice =
+        CompactProfile::from_profile(UserId::from_raw(1), &alice, &mut arena);
+    let mut bob =
+        CompactProfile::from_profile(UserId::from_raw(2), &bob, &mut arena);
+    assert_eq!(bob.id().to_string(), "user:2");
+    // Three mentions of two songs: each name stored once
+    assert_eq!(arena.len(), 2);
+    assert_eq!(alice.liked_ids()[1], bob.liked_ids()[0]);
+    assert_eq!(bob.disliked_ids(), &alice.liked_ids()[..1]);
+
+    bob.like(arena.intern(song("Let It Be")));
+    let (played, allocs) = count_allocations(|| bob.play_songs(&arena).count());
+    assert_eq!(played, 2);
+    assert_eq!(allocs, 0);
+    let names: Vec<&str> = bob.play_songs(&arena).map(|s| s.as_str()).collect();
+    assert_eq!(names, vec!["Yesterday", "Let It Be"]);
+}
+
+/*
+    Editing song lists in place with AsMut
+
+    normalize_in_place tidies names the way clean_song_names does, but
+    overwrites them where they are instead of returning a copy. It only
+    needs to replace elements, not add or remove them, so it asks for
+    AsMut<[SongName]>: a Vec, an array, a &mut of either, a SongList or
+    a Playlist all qualify.
+
+    dedup_songs removes entries, so a slice isn't enough; it needs
+    AsMut<Vec<SongName>>. SongList can offer that. Playlist deliberately
+    doesn't: its durations Vec must stay the same length as its songs,
+    and a caller holding the bare Vec could break that.
+*/
+
+impl AsMut<[SongName]> for SongList {
+    fn as_mut(&mut self) -> &mut [SongName] {
+        &mut self.0
+    }
+}
+
+impl AsMut<Vec<SongName>> for SongList {
+    fn as_mut(&mut self) -> &mut Vec<SongName> {
+        &mut self.0
+    }
+}
+
+impl AsMut<[SongName]> for Playlist {
+    fn as_mut(&mut self) -> &mut [SongName] {
+        &mut self.songs
+    }
+}
+
+// Returns how many names were changed
+pub fn normalize_in_place<T: AsMut<[SongName]>>(mut target: T) -> usize {
+    let mut changed = 0;
+    for name in target.as_mut() {
+        if let Cow::Owned(fixed) = normalize_whitespace(name.as_str()) {
+            // Still valid, see clean_song_names
+            *name = SongName::new(fixed).unwrap();
+            changed += 1;
+        }
+    }
+    changed
+}
+
+// Keeps the first occurrence of each name; returns how many were removed
+pub fn dedup_songs<T: AsMut<Vec<SongName>>>(mut target: T) -> usize {
+    let songs = target.as_mut();
+    let before = songs.len();
+    let mut seen = HashSet::new();
+    songs.retain(|song| seen.insert(song.clone()));
+    before - songs.len()
+}
+
+#[test]
+fn test_normalize_in_place() {
+    use iterators::song;
+
+    let mut names = vec![song(" Help!"), song("Let  It Be"), song("Girl")];
+    assert_eq!(normalize_in_place(&mut names), 2);
+    assert_eq!(names, vec![song("Help!"), song("Let It Be"), song("Girl")]);
+    assert_eq!(normalize_in_place(&mut names[..]), 0);
+
+    let mut array = [song("Yesterday  ")];
+    normalize_in_place(&mut array);
+    assert_eq!(array[0].as_str(), "Yesterday");
+
+    let mut p = Playlist::new();
+    p.push_timed(song("Something "), Seconds(182));
+    normalize_in_place(&mut p);
+    assert_eq!(p[0].as_str(), "Something");
+    assert_eq!(p.duration_of(0), Some(Seconds(182)));
+}
+
+#[test]
+fn test_dedup_songs() {
+    use iterators::song;
+
+    let mut list = SongList::from(vec![
+        song("Help!"),
+        song("Girl"),
+        song("Help!"),
+        song("Girl"),
+        song("Yesterday"),
+    ]);
+    assert_eq!(dedup_songs(&mut list), 2);
+    assert_eq!(
+        list.titles().collect::<Vec<_>>(),
+        vec!["Help!", "Girl", "Yesterday"]
+    );
+    assert_eq!(normalize_in_place(&mut list), 0);
+
+    let mut plain = vec![song("a"), song("a")];
+    assert_eq!(dedup_songs(&mut plain), 1);
+}
+
+/*
+    Building a Playlist from other collections
+
+    From<Vec<SongName>> can't fail: the names are already valid, and the
+    Vec is reused as the playlist's own storage.
+
+    Strings are a different story. Every SongName goes through
+    validation (see iterators.rs), so turning a Vec<String> or a
+    &[&str] into a Playlist can fail, and those conversions are TryFrom
+    rather than From. (A From that panicked on a bad name would compile,
+    but From promises the conversion always works.) TryFrom<&str> reads
+    a comma-separated list: "Help!, Yesterday, Let It Be".
+
+    The error says which entry was bad as well as why.
+*/
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlaylistEntryError {
+    pub index: usize,
+    pub error: SongNameError,
+}
+
+impl fmt::Display for PlaylistEntryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "song {}: {}", self.index + 1, self.error)
+    }
+}
+
+impl Error for PlaylistEntryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+// The shared part of the fallible conversions
+fn playlist_from_names<I>(names: I) -> Result<Playlist, PlaylistEntryError>
+where
+    I: IntoIterator,
+    I::Item: Into<String>,
+{
+    let mut playlist = Playlist::new();
+    for (index, name) in names.into_iter().enumerate() {
+        let song = SongName::new(name)
+            .map_err(|error| PlaylistEntryError { index, error })?;
+        playlist.push(song);
+    }
+    Ok(playlist)
+}
+
+impl From<Vec<SongName>> for Playlist {
+    fn from(songs: Vec<SongName>) -> Self {
+        let durations = vec![None; songs.len()];
+        Playlist { songs, durations, owner: None }
+    }
+}
+
+impl TryFrom<Vec<String>> for Playlist {
+    type Error = PlaylistEntryError;
+
+    fn try_from(names: Vec<String>) -> Result<Self, Self::Error> {
+        playlist_from_names(names)
+    }
+}
+
+impl<'a, 'b> TryFrom<&'a [&'b str]> for Playlist {
+    type Error = PlaylistEntryError;
+
+    fn try_from(names: &'a [&'b str]) -> Result<Self, Self::Error> {
+        playlist_from_names(names.iter().cloned())
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Playlist {
+    type Error = PlaylistEntryError;
+
+    // An empty string is an empty playlist, not one empty name
+    fn try_from(list: &'a str) -> Result<Self, Self::Error> {
+        if list.trim().is_empty() {
+            return Ok(Playlist::new());
+        }
+        playlist_from_names(list.split(',').map(str::trim))
+    }
+}
+
+#[test]
+fn test_playlist_from_collections() {
+    use iterators::song;
+    use std::convert::TryInto;
+
+    let expected = sample_playlist();
+    let songs = vec![song("Help!"), song("Yesterday")];
+    let p = Playlist::from(songs.clone());
+    assert_eq!(p.len(), 2);
+    assert_eq!(p.duration_of(1), None);
+
+    let names: Vec<String> = expected.iter().map(|s| s.to_string()).collect();
+    assert_eq!(Playlist::try_from(names), Ok(expected.clone()));
+    let strs: &[&str] = &["Help!", "Yesterday"];
+    assert_eq!(Playlist::try_from(strs), Ok(p.clone()));
+    assert_eq!(
+        Playlist::try_from("Help!, Yesterday, Let It Be,Something"),
+        Ok(expected)
+    );
+    assert_eq!(Playlist::try_from("  "), Ok(Playlist::new()));
+
+    // ? and .try_into() work like with any TryFrom
+    fn parse(list: &str) -> Result<usize, PlaylistEntryError> {
+        let p: Playlist = list.try_into()?;
+        Ok(p.len())
+    }
+    assert_eq!(parse("Help!"), Ok(1));
+}
+
+#[test]
+fn test_playlist_from_bad_names() {
+    let err = Playlist::try_from("Help!, , Yesterday").unwrap_err();
+    assert_eq!(
+        err,
+        PlaylistEntryError { index: 1, error: SongNameError::Empty }
+    );
+    assert_eq!(err.to_string(), "song 2: song name is empty");
+
+    let names = vec![String::from("ok"), String::from("tab\there")];
+    assert!(matches!(
+        Playlist::try_from(names),
+        Err(PlaylistEntryError {
+            index: 1,
+            error: SongNameError::ControlChar { .. }
+        })
+    ));
+}
+
+/*
+    Applying an update without cloning for nothing
+
+    A ProfileUpdate is a batch of changes: songs to like and dislike,
+    listens to add, a new days-active count. Very often -- a client
+    re-sending state the server already has -- none of it changes
+    anything.
+
+    merge_profiles returns Cow<SongUserProfile> so that case costs
+    nothing: Borrowed(base) when the update is a no-op, and only
+    otherwise an Owned clone with the changes applied. (Cow needs
+    SongUserProfile: ToOwned, which std provides for every Clone type.)
+*/
+
+#[derive(Clone, Debug, Default)]
+pub struct ProfileUpdate {
+    pub like: Vec<SongName>,
+    pub dislike: Vec<SongName>,
+    pub add_listens: usize,
+    pub days_active: Option<usize>,
+}
+
+impl ProfileUpdate {
+    fn changes(&self, profile: &SongUserProfile) -> bool {
+        self.like.iter().any(|s| !profile.liked_songs().contains(s))
+            || self
+                .dislike
+                .iter()
+                .any(|s| !profile.disliked_songs().contains(s))
+            || self.add_listens > 0
+            || self.days_active.is_some_and(|d| d != profile.days_active())
+    }
+}
+
+pub fn merge_profiles<'a>(
+    base: &'a SongUserProfile,
+    update: &ProfileUpdate,
+) -> Cow<'a, SongUserProfile> {
+    if !update.changes(base) {
+        return Cow::Borrowed(base);
+    }
+    let mut merged = base.clone();
+    for song in &update.like {
+        merged.like(song.clone());
+    }
+    for song in &update.dislike {
+        merged.dislike(song.clone());
+    }
+    merged.add_listens(update.add_listens);
+    if let Some(days) = update.days_active {
+        merged.set_days_active(days);
+    }
+    Cow::Owned(merged)
+}
+
+#[test]
+fn test_merge_profiles_noop_borrows() {
+    use alloc_counter::count_allocations;
+    use iterators::song;
+
+    let mut base = SongUserProfile::new("alice");
+    base.like(song("Help!"));
+    base.set_days_active(4);
+
+    let resent = ProfileUpdate {
+        like: vec![song("Help!")],
+        days_active: Some(4),
+        ..ProfileUpdate::default()
+    };
+    let (merged, allocs) = count_allocations(|| merge_profiles(&base, &resent));
+    assert!(matches!(merged, Cow::Borrowed(_)));
+    assert_eq!(allocs, 0);
+    assert!(matches!(
+        merge_profiles(&base, &ProfileUpdate::default()),
+        Cow::Borrowed(_)
+    ));
+}
+
+#[test]
+fn test_merge_profiles_applies_changes() {
+    use iterators::song;
+
+    let mut base = SongUserProfile::new("alice");
+    base.like(song("Help!"));
+    let update = ProfileUpdate {
+        like: vec![song("Help!"), song("Girl")],
+        dislike: vec![song("Revolution 9")],
+        add_listens: 3,
+        days_active: Some(1),
+    };
+    let merged = merge_profiles(&base, &update);
+    assert!(matches!(merged, Cow::Owned(_)));
+    assert_eq!(merged.liked_songs(), &[song("Help!"), song("Girl")]);
+    assert_eq!(merged.disliked_songs(), &[song("Revolution 9")]);
+    assert_eq!(merged.total_listens(), 3);
+    assert_eq!(merged.days_active(), 1);
+    // The base is untouched
+    assert_eq!(base.liked_songs().len(), 1);
+    assert_eq!(base.total_listens(), 0);
+
+    let listens_only = ProfileUpdate { add_listens: 1, ..Default::default() };
+    assert!(matches!(merge_profiles(&base, &listens_only), Cow::Owned(_)));
+}
+
+/*
+    Playlists as map keys
+
+    Playlist: Borrow<[SongName]> lets a HashMap<Playlist, _> be searched
+    with a plain &[SongName], the way a HashMap<String, _> is searched
+    with a &str. Borrow comes with a promise, though (see "When is
+    Borrow<str> correct?" in utility_traits): a playlist and its song
+    slice must hash the same and compare the same, or lookups silently
+    miss.
+
+    So Playlist's Eq and Hash look at the songs and nothing else.
+    Durations are metadata about the songs -- a playlist whose lengths
+    have been filled in is still the same playlist -- and the owner
+    never counted. Hash hashes exactly the slice, so the two agree.
+
+    AsRef<[SongName]> comes along for the same reason it does on String:
+    functions taking impl AsRef<[SongName]> can now accept a Playlist.
+*/
+
+impl Hash for Playlist {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.songs.hash(state);
+    }
+}
+
+impl borrow::Borrow<[SongName]> for Playlist {
+    fn borrow(&self) -> &[SongName] {
+        &self.songs
+    }
+}
+
+impl AsRef<[SongName]> for Playlist {
+    fn as_ref(&self) -> &[SongName] {
+        &self.songs
+    }
+}
+
+#[test]
+fn test_playlist_as_map_key() {
+    use iterators::song;
+
+    let mut plays: HashMap<Playlist, u32> = HashMap::new();
+    let p = sample_playlist();
+    plays.insert(p.clone(), 3);
+
+    // Look up with a slice, no Playlist needed
+    let songs = [
+        song("Help!"),
+        song("Yesterday"),
+        song("Let It Be"),
+        song("Something"),
+    ];
+    assert_eq!(plays.get(&songs[..]), Some(&3));
+    assert_eq!(plays.get(&songs[..2]), None);
+
+    // Durations don't change which entry a playlist finds
+    let mut timed = Playlist::new();
+    for s in songs.iter().cloned() {
+        timed.push_timed(s, Seconds(120));
+    }
+    assert_eq!(timed, p);
+    *plays.get_mut(&timed).unwrap() += 1;
+    assert_eq!(plays[&songs[..]], 4);
+}
+
+#[test]
+fn test_playlist_hash_matches_slice() {
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash_of<T: Hash + ?Sized>(value: &T) -> u64 {
+        let mut h = DefaultHasher::new();
+        value.hash(&mut h);
+        h.finish()
+    }
+    let p = sample_playlist();
+    let slice: &[SongName] = p.as_ref();
+    assert_eq!(hash_of(&p), hash_of(slice));
+    assert_eq!(slice.len(), 4);
+}
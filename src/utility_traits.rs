@@ -0,0 +1,2593 @@
+/*
+    Utility traits
+
+    A handful of small std traits show up in almost every signature:
+    AsRef, Borrow, From/Into, Deref, Drop, Display... Each one is only a
+    method or two, but together they decide how pleasant an API is to
+    call.
+*/
+
+use std::any::{Any, TypeId};
+use std::borrow::{Borrow, Cow};
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::iter::Sum;
+use std::marker::PhantomData;
+use std::ops::{Add, AddAssign, Deref, DerefMut, Mul, Neg, Sub};
+use std::path::{self, Component, Path, PathBuf};
+use std::ptr::NonNull;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/*
+    AsRef: cheap reference-to-reference conversion
+
+    std::fs::File::open doesn't take a &Path, it takes any P: AsRef<Path>.
+    &str, String, &Path, PathBuf, OsString... all implement AsRef<Path>,
+    so callers can pass whichever they have:
+
+        open("notes.txt")
+        open(String::from("notes.txt"))
+        open(Path::new("notes.txt"))
+
+    Inside the function, path.as_ref() gives a &Path. Nothing is copied
+    or allocated -- each of those types already contains path data.
+
+    The functions below forward straight to a non-generic helper. Generic
+    functions are compiled once per type they're called with, so keeping
+    the generic part to a single .as_ref() line keeps that copy small.
+*/
+
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<File> {
+    File::open(path.as_ref())
+}
+
+pub fn read_to_string_from<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    fn inner(path: &Path) -> io::Result<String> {
+        let mut s = String::new();
+        open(path)?.read_to_string(&mut s)?;
+        Ok(s)
+    }
+    inner(path.as_ref())
+}
+
+// Creates the file, or truncates it if it exists
+pub fn write_string_to<P: AsRef<Path>>(
+    path: P,
+    contents: &str,
+) -> io::Result<()> {
+    fn inner(path: &Path, contents: &str) -> io::Result<()> {
+        File::create(path)?.write_all(contents.as_bytes())
+    }
+    inner(path.as_ref(), contents)
+}
+
+// Creates the file if needed, and adds the trailing newline for you
+pub fn append_line_to<P: AsRef<Path>>(path: P, line: &str) -> io::Result<()> {
+    fn inner(path: &Path, line: &str) -> io::Result<()> {
+        let mut file =
+            OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", line)
+    }
+    inner(path.as_ref(), line)
+}
+
+// A fresh path in the system temp directory. Tests run in parallel, so
+// the name includes the process id and the test's own name.
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    use std::{env, process};
+    env::temp_dir().join(format!("lecture7-{}-{}", process::id(), name))
+}
+
+#[test]
+fn test_file_helpers() {
+    use std::fs;
+    use std::path::PathBuf;
+
+    let path: PathBuf = temp_path("file_helpers.txt");
+    // PathBuf, &PathBuf, &Path, &str and String all work
+    write_string_to(&path, "first\n").unwrap();
+    append_line_to(path.as_path(), "second").unwrap();
+    let as_string: String = path.to_str().unwrap().to_string();
+    append_line_to(&as_string, "third").unwrap();
+    assert_eq!(
+        read_to_string_from(as_string.as_str()).unwrap(),
+        "first\nsecond\nthird\n"
+    );
+
+    // write_string_to truncates
+    write_string_to(path.clone(), "replaced").unwrap();
+    assert_eq!(read_to_string_from(&path).unwrap(), "replaced");
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_open_missing_file() {
+    let path = temp_path("does_not_exist.txt");
+    let err = open(&path).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert!(read_to_string_from(&path).is_err());
+}
+
+/*
+    Cow: clone-on-write
+
+    Cow<'a, str> is either Borrowed(&'a str) or Owned(String). A function
+    that *might* need to change its input can return Cow: if the input is
+    already fine, hand back a borrow of it for free, and only allocate a
+    new String when something actually changes. Either way the caller
+    gets something that Derefs to &str.
+
+    Most inputs to a normalizing function are usually normalized already,
+    so this saves an allocation on the common path.
+*/
+
+// Trims the ends and turns every run of whitespace into a single space
+pub fn normalize_whitespace(s: &str) -> Cow<'_, str> {
+    let mut prev_space = true; // so leading whitespace counts as a run
+    let is_normalized = s.chars().all(|c| {
+        let ok = if c.is_whitespace() { c == ' ' && !prev_space } else { true };
+        prev_space = c.is_whitespace();
+        ok
+    }) && !s.ends_with(' ');
+    if is_normalized {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    for word in s.split_whitespace() {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+    Cow::Owned(out)
+}
+
+pub fn to_ascii_lowercase_cow(s: &str) -> Cow<'_, str> {
+    if s.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(s.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+#[test]
+fn test_normalize_whitespace() {
+    let already = normalize_whitespace("one two three");
+    assert_eq!(already, "one two three");
+    assert!(matches!(already, Cow::Borrowed(_)));
+    assert!(matches!(normalize_whitespace(""), Cow::Borrowed(_)));
+
+    for &(input, expected) in &[
+        ("  one two", "one two"),
+        ("one two ", "one two"),
+        ("one  two", "one two"),
+        ("one\ttwo\nthree", "one two three"),
+        ("  \t ", ""),
+    ] {
+        let result = normalize_whitespace(input);
+        assert_eq!(result, expected);
+        assert!(matches!(result, Cow::Owned(_)), "{:?} should allocate", input);
+    }
+}
+
+#[test]
+fn test_to_ascii_lowercase_cow() {
+    let lower = to_ascii_lowercase_cow("already lower, ünïcode ok");
+    assert!(matches!(lower, Cow::Borrowed(_)));
+
+    let changed = to_ascii_lowercase_cow("Hello WORLD");
+    assert_eq!(changed, "hello world");
+    assert!(matches!(changed, Cow::Owned(_)));
+
+    // Only ASCII letters are changed
+    assert_eq!(to_ascii_lowercase_cow("ÜBER"), "Über");
+}
+
+/*
+    Cow<Path>
+
+    Cow works for any borrowed/owned pair linked by ToOwned, not just
+    str/String: Path's owned form is PathBuf, so Cow<Path> is "a &Path,
+    or a PathBuf if we had to build a new one".
+
+    normalize_path tidies a path without touching the filesystem:
+    - "." components are dropped: ./a/./b -> a/b
+    - repeated separators are collapsed: a//b -> a/b
+    - a trailing separator is dropped: a/b/ -> a/b
+    ".." is left alone. a/../b isn't necessarily b if a is a symlink, and
+    only the filesystem knows that (see fs::canonicalize).
+
+    Path::components() already does the parsing; we only rebuild the
+    path from its components if a quick scan finds something to fix.
+*/
+
+pub fn normalize_path(p: &Path) -> Cow<'_, Path> {
+    // Split on separators: an empty segment (other than the one before a
+    // leading /) means a doubled or trailing separator
+    let bytes = p.as_os_str().as_encoded_bytes();
+    let needs_fixing = bytes.len() > 1
+        && bytes
+            .split(|&b| path::is_separator(b as char))
+            .enumerate()
+            .any(|(i, seg)| seg == b"." || (seg.is_empty() && i > 0));
+    if !needs_fixing {
+        return Cow::Borrowed(p);
+    }
+    let normalized: PathBuf =
+        p.components().filter(|&c| c != Component::CurDir).collect();
+    if normalized.as_os_str().is_empty() {
+        // "./" or "././" -- don't turn the current directory into ""
+        Cow::Owned(PathBuf::from("."))
+    } else {
+        Cow::Owned(normalized)
+    }
+}
+
+#[test]
+fn test_normalize_path() {
+    for &p in &["a/b", "/usr/lib", "/", "../x", "a/../b", ".", ""] {
+        let result = normalize_path(Path::new(p));
+        assert!(matches!(result, Cow::Borrowed(_)), "{:?} should borrow", p);
+        assert_eq!(result, Path::new(p));
+    }
+
+    for &(p, expected) in &[
+        ("./a", "a"),
+        ("a/./b/.", "a/b"),
+        ("a//b", "a/b"),
+        ("/usr//local///bin/", "/usr/local/bin"),
+        ("./", "."),
+        ("/./", "/"),
+    ] {
+        let result = normalize_path(Path::new(p));
+        assert!(matches!(result, Cow::Owned(_)), "{:?} should allocate", p);
+        assert_eq!(result, Path::new(expected));
+    }
+}
+
+/*
+    Drop: cleaning up a real resource
+
+    Drop::drop runs automatically when a value goes out of scope -- at the
+    end of its block, on early return, on `?`, or while unwinding from a
+    panic. That makes it the place to release resources: TempFile deletes
+    its file no matter how the owning function exits.
+
+    drop() can't return an error (there's nobody to return it to), so
+    failures are passed to an error handler instead. It defaults to
+    printing to stderr; tests swap in a closure that records the error.
+*/
+
+type ErrorHandler = Box<dyn FnMut(&Path, io::Error)>;
+
+pub struct TempFile {
+    path: PathBuf,
+    on_error: ErrorHandler,
+}
+
+impl TempFile {
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        contents: &str,
+    ) -> io::Result<TempFile> {
+        let path = path.as_ref().to_path_buf();
+        write_string_to(&path, contents)?;
+        Ok(TempFile {
+            path,
+            on_error: Box::new(|path, e| {
+                eprintln!("warning: couldn't remove {}: {}", path.display(), e)
+            }),
+        })
+    }
+
+    pub fn with_error_handler<F>(mut self, on_error: F) -> Self
+    where
+        F: FnMut(&Path, io::Error) + 'static,
+    {
+        self.on_error = Box::new(on_error);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Replaces the file's contents
+    pub fn write(&mut self, contents: &str) -> io::Result<()> {
+        write_string_to(&self.path, contents)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            (self.on_error)(&self.path, e);
+        }
+    }
+}
+
+#[test]
+fn test_temp_file_removed_on_drop() {
+    let path = temp_path("temp_file.txt");
+    {
+        let mut tmp = TempFile::new(&path, "hello").unwrap();
+        assert_eq!(read_to_string_from(tmp.path()).unwrap(), "hello");
+        tmp.write("goodbye").unwrap();
+        assert_eq!(read_to_string_from(&path).unwrap(), "goodbye");
+    }
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_temp_file_error_handler() {
+    let errors = Rc::new(RefCell::new(Vec::new()));
+    let path = temp_path("temp_file_error.txt");
+    let tmp = TempFile::new(&path, "").unwrap().with_error_handler({
+        let errors = Rc::clone(&errors);
+        move |_, e| errors.borrow_mut().push(e.kind())
+    });
+    // Delete it out from under the guard
+    fs::remove_file(&path).unwrap();
+    drop(tmp);
+    assert_eq!(*RefCell::borrow(&errors), vec![io::ErrorKind::NotFound]);
+}
+
+/*
+    Timing a scope
+
+    A ScopedTimer notes the time when it's created and reports how long
+    it lived when it's dropped. Since drop happens at the end of the
+    enclosing block, timing a block is one line at the top of it:
+
+        {
+            let _t = ScopedTimer::new();
+            ... work ...
+        } // prints the elapsed time here
+
+    (Note `_t`, not `_`: `let _ = ...` drops the value immediately.)
+
+    Where the result goes is up to the caller: with_sink takes any
+    FnMut(Duration), e.g. a closure pushing onto a Vec of samples.
+*/
+
+pub struct ScopedTimer<F: FnMut(Duration)> {
+    start: Instant,
+    sink: F,
+}
+
+fn report_to_stderr(elapsed: Duration) {
+    eprintln!("elapsed: {:?}", elapsed);
+}
+
+impl ScopedTimer<fn(Duration)> {
+    pub fn new() -> Self {
+        ScopedTimer::with_sink(report_to_stderr)
+    }
+}
+
+impl Default for ScopedTimer<fn(Duration)> {
+    fn default() -> Self {
+        ScopedTimer::new()
+    }
+}
+
+impl<F: FnMut(Duration)> ScopedTimer<F> {
+    pub fn with_sink(sink: F) -> Self {
+        ScopedTimer { start: Instant::now(), sink }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl<F: FnMut(Duration)> Drop for ScopedTimer<F> {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        (self.sink)(elapsed);
+    }
+}
+
+#[test]
+fn test_scoped_timer() {
+    use std::thread;
+
+    let mut samples = Vec::new();
+    for _ in 0..3 {
+        let _t = ScopedTimer::with_sink(|d| samples.push(d));
+        thread::sleep(Duration::from_millis(2));
+    }
+    assert_eq!(samples.len(), 3);
+    assert!(samples.iter().all(|&d| d >= Duration::from_millis(2)));
+
+    // Reports even when the scope is left by a panic
+    let mut reported = false;
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _t = ScopedTimer::with_sink(|_| reported = true);
+        panic!("oops");
+    }));
+    assert!(result.is_err());
+    assert!(reported);
+}
+
+/*
+    A resource pool: Drop + Deref + interior mutability
+
+    Some values are expensive to create (connections, big buffers) and
+    cheap to reuse. A Pool keeps idle ones around: checkout() hands one
+    out (creating it only if none are idle), and when the caller is done
+    it goes back into the pool for the next checkout.
+
+    "When the caller is done" is exactly what Drop is for. checkout()
+    returns a PooledGuard that owns the value; dropping the guard puts
+    the value back. The caller never calls a release function, so it
+    can't forget to -- or release twice.
+
+    - Deref/DerefMut let the guard be used as if it were the value:
+      guard.push(1) on a PooledGuard<Vec<i32>> calls Vec::push.
+    - The guard borrows the pool (&'a Pool<T>), so the pool can't be
+      dropped while values are checked out.
+    - Several guards can be out at once, all holding &Pool, yet each
+      drop has to modify the idle list. RefCell/Cell allow mutation
+      through a shared reference (checked at runtime for RefCell).
+      Borrows are never held across a call to user code, so they can't
+      conflict.
+*/
+
+type Factory<T> = Box<dyn Fn() -> T>;
+type Reset<T> = Box<dyn Fn(&mut T)>;
+
+pub struct Pool<T> {
+    idle: RefCell<Vec<T>>,
+    factory: Factory<T>,
+    // Runs on each value as it comes back, e.g. Vec::clear
+    reset: Option<Reset<T>>,
+    // Idle values beyond this are dropped instead of kept
+    max_idle: usize,
+    created: Cell<usize>,
+    checked_out: Cell<usize>,
+}
+
+impl<T> Pool<T> {
+    pub fn new<F>(factory: F) -> Self
+    where
+        F: Fn() -> T + 'static,
+    {
+        Pool {
+            idle: RefCell::new(Vec::new()),
+            factory: Box::new(factory),
+            reset: None,
+            max_idle: usize::MAX,
+            created: Cell::new(0),
+            checked_out: Cell::new(0),
+        }
+    }
+
+    pub fn with_reset<R>(mut self, reset: R) -> Self
+    where
+        R: Fn(&mut T) + 'static,
+    {
+        self.reset = Some(Box::new(reset));
+        self
+    }
+
+    pub fn with_max_idle(mut self, max_idle: usize) -> Self {
+        self.max_idle = max_idle;
+        self
+    }
+
+    // Creates `n` values up front so the first checkouts are cheap
+    pub fn prefill(&self, n: usize) {
+        for _ in 0..n {
+            let value = self.create();
+            self.put_back(value);
+        }
+    }
+
+    pub fn checkout(&self) -> PooledGuard<'_, T> {
+        let value = match self.take_idle() {
+            Some(value) => value,
+            None => self.create(),
+        };
+        self.hand_out(value)
+    }
+
+    // Like checkout, but never creates a new value
+    pub fn try_checkout(&self) -> Option<PooledGuard<'_, T>> {
+        self.take_idle().map(|value| self.hand_out(value))
+    }
+
+    pub fn idle(&self) -> usize {
+        self.idle.borrow().len()
+    }
+
+    pub fn checked_out(&self) -> usize {
+        self.checked_out.get()
+    }
+
+    // Total values ever made by the factory
+    pub fn created(&self) -> usize {
+        self.created.get()
+    }
+
+    fn create(&self) -> T {
+        self.created.set(self.created.get() + 1);
+        (self.factory)()
+    }
+
+    // Separate from checkout so that the RefCell borrow ends before the
+    // factory runs
+    fn take_idle(&self) -> Option<T> {
+        self.idle.borrow_mut().pop()
+    }
+
+    fn hand_out(&self, value: T) -> PooledGuard<'_, T> {
+        self.checked_out.set(self.checked_out.get() + 1);
+        PooledGuard { pool: self, value: Some(value) }
+    }
+
+    fn put_back(&self, mut value: T) {
+        if let Some(reset) = &self.reset {
+            reset(&mut value);
+        }
+        let mut idle = self.idle.borrow_mut();
+        if idle.len() < self.max_idle {
+            idle.push(value);
+        }
+    }
+}
+
+pub struct PooledGuard<'a, T> {
+    pool: &'a Pool<T>,
+    // Always Some until drop() or detach() takes it
+    value: Option<T>,
+}
+
+impl<'a, T> PooledGuard<'a, T> {
+    // Keeps the value for good instead of returning it to the pool
+    pub fn detach(mut self) -> T {
+        self.pool.checked_out.set(self.pool.checked_out.get() - 1);
+        self.value.take().unwrap()
+    }
+}
+
+impl<'a, T> Deref for PooledGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.as_ref().unwrap()
+    }
+}
+
+impl<'a, T> DerefMut for PooledGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.as_mut().unwrap()
+    }
+}
+
+impl<'a, T> Drop for PooledGuard<'a, T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            self.pool.checked_out.set(self.pool.checked_out.get() - 1);
+            self.pool.put_back(value);
+        }
+    }
+}
+
+#[test]
+fn test_pool_reuses_values() {
+    let pool = Pool::new(|| Vec::<u8>::with_capacity(1024));
+    {
+        let mut buf = pool.checkout();
+        // Deref(Mut) to Vec<u8>
+        buf.extend_from_slice(b"hello");
+        assert_eq!(buf.len(), 5);
+        assert_eq!(pool.checked_out(), 1);
+    }
+    assert_eq!((pool.idle(), pool.checked_out()), (1, 0));
+
+    // Reused, not re-created -- and not reset, since we didn't ask
+    let buf = pool.checkout();
+    assert_eq!(&buf[..], b"hello");
+    assert_eq!(pool.created(), 1);
+}
+
+#[test]
+fn test_pool_several_checkouts() {
+    let pool = Pool::new(String::new).with_reset(|s: &mut String| s.clear());
+    let mut a = pool.checkout();
+    let mut b = pool.checkout();
+    a.push_str("alpha");
+    b.push_str("beta");
+    assert_eq!((pool.created(), pool.checked_out()), (2, 2));
+    assert!(pool.try_checkout().is_none());
+
+    drop(a);
+    drop(b);
+    assert_eq!(pool.idle(), 2);
+    let c = pool.try_checkout().unwrap();
+    assert_eq!(*c, "");
+    assert_eq!(pool.created(), 2);
+}
+
+#[test]
+fn test_pool_max_idle_and_detach() {
+    let pool = Pool::new(|| 0u32).with_max_idle(1);
+    pool.prefill(3);
+    assert_eq!((pool.created(), pool.idle()), (3, 1));
+
+    let guards: Vec<_> = (0..3).map(|_| pool.checkout()).collect();
+    assert_eq!(pool.created(), 5);
+    drop(guards);
+    assert_eq!(pool.idle(), 1);
+
+    let mut g = pool.checkout();
+    *g = 42;
+    let kept = g.detach();
+    assert_eq!(kept, 42);
+    assert_eq!((pool.idle(), pool.checked_out()), (0, 0));
+}
+
+#[test]
+fn test_pool_returns_on_panic() {
+    use std::panic::{self, AssertUnwindSafe};
+    let pool = Pool::new(Vec::<i32>::new);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut v = pool.checkout();
+        v.push(1);
+        panic!("worker failed");
+    }));
+    assert!(result.is_err());
+    assert_eq!((pool.idle(), pool.checked_out()), (1, 0));
+}
+
+/*
+    Deref coercion, and a Box of our own
+
+    If T: Deref<Target = U>, then &T automatically converts to &U where
+    a &U is expected. The compiler inserts as many derefs as it needs:
+    &MyBox<String> -> &String -> &str. That's why a function taking &str
+    can be called with &String, &Box<String>, &Rc<String>, ...
+
+    MyBox is a minimal Box. It owns a heap allocation through a raw
+    pointer, so it needs all three traits to behave like a real owner:
+    - Deref / DerefMut: *b, b.method(), and coercion all reach the T
+    - Drop: free the allocation (and drop the T) when the MyBox goes away
+
+    The allocation is borrowed from Box itself: Box::into_raw gives up
+    ownership without freeing, and Box::from_raw takes it back so that
+    dropping the Box frees it. PhantomData<T> tells the compiler that
+    MyBox<T> owns a T (for drop checking), which a raw pointer alone
+    doesn't say.
+*/
+
+pub struct MyBox<T> {
+    ptr: NonNull<T>,
+    _owns: PhantomData<T>,
+}
+
+impl<T> MyBox<T> {
+    pub fn new(value: T) -> Self {
+        let raw = Box::into_raw(Box::new(value));
+        // Box never hands out a null pointer
+        MyBox { ptr: NonNull::new(raw).unwrap(), _owns: PhantomData }
+    }
+
+    pub fn into_inner(self) -> T {
+        let ptr = self.ptr.as_ptr();
+        // Don't run our Drop: ownership moves to the Box below
+        std::mem::forget(self);
+        // Safe: ptr came from Box::into_raw and hasn't been freed
+        *unsafe { Box::from_raw(ptr) }
+    }
+}
+
+impl<T> Deref for MyBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safe: the pointer is valid for as long as self is
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for MyBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safe: &mut self means nobody else can be using it
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for MyBox<T> {
+    fn drop(&mut self) {
+        // Safe: we own the allocation and this runs exactly once
+        unsafe { drop(Box::from_raw(self.ptr.as_ptr())) }
+    }
+}
+
+#[test]
+fn test_my_box_deref_coercion() {
+    fn shout(s: &str) -> String {
+        s.to_uppercase()
+    }
+
+    let mut b = MyBox::new(String::from("hello"));
+    // &MyBox<String> -> &String -> &str
+    assert_eq!(shout(&b), "HELLO");
+    // Method calls auto-deref too: str::len and String::push_str
+    assert_eq!(b.len(), 5);
+    b.push_str(", world");
+    assert_eq!(*b, "hello, world");
+    assert_eq!(b.into_inner(), "hello, world");
+}
+
+#[test]
+fn test_my_box_drops_contents() {
+    let tracker = Rc::new(());
+    let b = MyBox::new(Rc::clone(&tracker));
+    assert_eq!(Rc::strong_count(&tracker), 2);
+    drop(b);
+    assert_eq!(Rc::strong_count(&tracker), 1);
+
+    // into_inner moves the value out without dropping it
+    let rc = MyBox::new(Rc::clone(&tracker)).into_inner();
+    assert_eq!(Rc::strong_count(&tracker), 2);
+    drop(rc);
+    assert_eq!(Rc::strong_count(&tracker), 1);
+}
+
+/*
+    An Rc of our own
+
+    MyBox has one owner. MyRc has many: every clone() points at the same
+    heap allocation, which holds the value next to a count of how many
+    MyRcs point at it. Four traits make that work:
+    - Clone bumps the count instead of copying the T
+    - Drop decrements it, and the last MyRc out frees the allocation
+    - Deref gives shared (&T) access -- never &mut, since other MyRcs
+      may be reading the same T
+    - Cell, for the count: clone() and drop() only get &self / one
+      handle, but all handles must see the same count. Cell allows
+      mutation through a shared reference, and since MyRc isn't Send
+      (the raw pointer sees to that), no other thread can race on it.
+*/
+
+struct RcBox<T> {
+    count: Cell<usize>,
+    value: T,
+}
+
+pub struct MyRc<T> {
+    ptr: NonNull<RcBox<T>>,
+    _owns: PhantomData<RcBox<T>>,
+}
+
+impl<T> MyRc<T> {
+    pub fn new(value: T) -> Self {
+        let raw = Box::into_raw(Box::new(RcBox { count: Cell::new(1), value }));
+        MyRc { ptr: NonNull::new(raw).unwrap(), _owns: PhantomData }
+    }
+
+    fn inner(&self) -> &RcBox<T> {
+        // Safe: the allocation lives until the last MyRc is dropped,
+        // and self is one of them
+        unsafe { self.ptr.as_ref() }
+    }
+
+    pub fn count(this: &Self) -> usize {
+        this.inner().count.get()
+    }
+
+    // Do two MyRcs share one allocation? (== compares the values)
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.ptr == other.ptr
+    }
+}
+
+impl<T> Clone for MyRc<T> {
+    fn clone(&self) -> Self {
+        let count = &self.inner().count;
+        count.set(count.get() + 1);
+        MyRc { ptr: self.ptr, _owns: PhantomData }
+    }
+}
+
+impl<T> Deref for MyRc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner().value
+    }
+}
+
+impl<T> Drop for MyRc<T> {
+    fn drop(&mut self) {
+        let count = &self.inner().count;
+        count.set(count.get() - 1);
+        if count.get() == 0 {
+            // Safe: we were the last handle, so nobody can see it anymore
+            unsafe { drop(Box::from_raw(self.ptr.as_ptr())) }
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyRc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for MyRc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+#[test]
+fn test_my_rc_count() {
+    let a = MyRc::new(String::from("shared"));
+    assert_eq!(MyRc::count(&a), 1);
+    let b = a.clone();
+    let c = MyRc::clone(&b);
+    assert_eq!(MyRc::count(&a), 3);
+    drop(b);
+    assert_eq!(MyRc::count(&c), 2);
+
+    // Aliasing: one String, not three
+    assert!(MyRc::ptr_eq(&a, &c));
+    assert_eq!(a.as_ptr(), c.as_ptr());
+    let other = MyRc::new(String::from("shared"));
+    assert_eq!(a, other);
+    assert!(!MyRc::ptr_eq(&a, &other));
+    assert_eq!(a.len(), 6);
+}
+
+#[test]
+fn test_my_rc_frees_once() {
+    let tracker = DropTracker::new();
+    let a = MyRc::new(tracker.track("song"));
+    let b = a.clone();
+    drop(a);
+    assert!(tracker.dropped().is_empty());
+    assert_eq!(b.label(), "song");
+    drop(b);
+    assert_eq!(tracker.dropped(), vec!["song"]);
+
+    // Interior mutability composes: MyRc<RefCell<T>> is shared *and*
+    // mutable, like Rc<RefCell<T>>
+    let list = MyRc::new(RefCell::new(Vec::new()));
+    let handle = list.clone();
+    handle.borrow_mut().push(1);
+    list.borrow_mut().push(2);
+    assert_eq!(*RefCell::borrow(&list), vec![1, 2]);
+}
+
+/*
+    Borrowed vs owned wrappers
+
+    Wrapping a string in a newtype (to give it its own methods or trait
+    impls) normally hides all of str's methods. Deref<Target = str> brings
+    them back, and deref coercion lets the wrapper be passed anywhere a
+    &str is expected.
+
+    StrWrapper borrows its string, so it can only hand out &str: Deref
+    only. StringWrapper owns its String, so it can also hand out &mut str
+    (DerefMut) -- enough for in-place edits like make_ascii_uppercase,
+    though not for anything that changes the length. For that it has
+    its own push_str.
+*/
+
+pub struct StrWrapper<'a>(pub &'a str);
+
+impl<'a> Deref for StrWrapper<'a> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.0
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StringWrapper(String);
+
+impl StringWrapper {
+    pub fn push_str(&mut self, s: &str) {
+        self.0.push_str(s);
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for StringWrapper {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl DerefMut for StringWrapper {
+    fn deref_mut(&mut self) -> &mut str {
+        &mut self.0
+    }
+}
+
+impl From<String> for StringWrapper {
+    fn from(s: String) -> Self {
+        StringWrapper(s)
+    }
+}
+
+impl<'a> From<&'a str> for StringWrapper {
+    fn from(s: &'a str) -> Self {
+        StringWrapper(s.to_string())
+    }
+}
+
+impl fmt::Display for StringWrapper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Plain &str functions, callable with either wrapper through coercion
+pub fn count_words(s: &str) -> usize {
+    s.split_whitespace().count()
+}
+
+pub fn shout_in_place(s: &mut str) {
+    s.make_ascii_uppercase();
+}
+
+#[test]
+fn test_str_wrapper() {
+    let text = String::from("borrowed words here");
+    let w = StrWrapper(&text);
+    assert_eq!(count_words(&w), 3);
+    // str methods straight through Deref
+    assert!(w.starts_with("borrowed"));
+    assert_eq!(w.len(), text.len());
+}
+
+#[test]
+fn test_string_wrapper() {
+    let mut w = StringWrapper::from("owned");
+    w.push_str(" words");
+    assert_eq!(count_words(&w), 2);
+
+    // &mut StringWrapper -> &mut str
+    shout_in_place(&mut w);
+    assert_eq!(w.to_string(), "OWNED WORDS");
+    assert_eq!(format!("[{}]", w), "[OWNED WORDS]");
+
+    let from_string: StringWrapper = String::from("x").into();
+    assert_eq!(from_string.into_string(), "x");
+}
+
+/*
+    AsMut: the mutable counterpart of AsRef
+
+    A function taking T: AsRef<str> can read from anything string-like;
+    T: AsMut<str> can write into anything that will hand out a &mut str
+    -- String, Box<str>, str itself, and StringWrapper below. That's
+    enough for shout(), which only rewrites bytes in place.
+
+    Anything that changes the length needs the String, so exclaim() asks
+    for AsMut<String>. std doesn't implement that for String itself (a
+    &mut String already *is* one), which makes it a bound for wrapper
+    types that own a String and are willing to expose it.
+
+    Unlike DerefMut, AsMut never kicks in implicitly: the function asks
+    for it, the caller sees exactly what can be changed.
+*/
+
+impl AsMut<str> for StringWrapper {
+    fn as_mut(&mut self) -> &mut str {
+        &mut self.0
+    }
+}
+
+impl AsMut<String> for StringWrapper {
+    fn as_mut(&mut self) -> &mut String {
+        &mut self.0
+    }
+}
+
+pub fn shout<T: AsMut<str> + ?Sized>(target: &mut T) {
+    target.as_mut().make_ascii_uppercase();
+}
+
+pub fn exclaim<T: AsMut<String> + ?Sized>(target: &mut T) {
+    let s = target.as_mut();
+    if !s.ends_with('!') {
+        s.push('!');
+    }
+}
+
+#[test]
+fn test_as_mut_helpers() {
+    let mut owned = String::from("hey");
+    shout(&mut owned);
+    assert_eq!(owned, "HEY");
+
+    let mut boxed: Box<str> = "you".into();
+    shout(&mut boxed);
+    assert_eq!(&*boxed, "YOU");
+
+    let mut w = StringWrapper::from("get back");
+    shout(&mut w);
+    exclaim(&mut w);
+    exclaim(&mut w);
+    assert_eq!(w.to_string(), "GET BACK!");
+}
+
+/*
+    Encoding an invariant: NonEmptyVec
+
+    slice.first() and .last() return Option because the slice might be
+    empty, and every caller has to deal with the None. If emptiness is
+    impossible by construction, the type can say so:
+
+    - the fields are private, so the only ways to build a NonEmptyVec are
+      new(head, tail), which always has a head, and try_from(Vec), which
+      refuses an empty Vec
+    - there's no method that removes elements
+    so first() and last() can return &T directly.
+
+    Deref<Target = [T]> gives back every read-only slice method (len,
+    iter, contains, indexing...). Not DerefMut: through &mut [T] you can't
+    change the length, so it would be safe, but we keep the type
+    read-only to keep the example small. Inherent methods win over
+    methods found through Deref, so v.first() calls ours, not
+    <[T]>::first.
+*/
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NonEmptyVec<T> {
+    items: Vec<T>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmptyVecError;
+
+impl fmt::Display for EmptyVecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("expected at least one element")
+    }
+}
+
+impl Error for EmptyVecError {}
+
+impl<T> NonEmptyVec<T> {
+    pub fn new(head: T, tail: Vec<T>) -> Self {
+        let mut items = Vec::with_capacity(tail.len() + 1);
+        items.push(head);
+        items.extend(tail);
+        NonEmptyVec { items }
+    }
+
+    pub fn first(&self) -> &T {
+        &self.items[0]
+    }
+
+    pub fn last(&self) -> &T {
+        &self.items[self.items.len() - 1]
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.items.push(item);
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+}
+
+impl<T> TryFrom<Vec<T>> for NonEmptyVec<T> {
+    type Error = EmptyVecError;
+
+    fn try_from(items: Vec<T>) -> Result<Self, Self::Error> {
+        if items.is_empty() {
+            Err(EmptyVecError)
+        } else {
+            Ok(NonEmptyVec { items })
+        }
+    }
+}
+
+impl<T> Deref for NonEmptyVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+#[test]
+fn test_non_empty_vec() {
+    let mut v = NonEmptyVec::new(3, vec![1, 4]);
+    assert_eq!((*v.first(), *v.last()), (3, 4));
+    v.push(1);
+    assert_eq!(*v.last(), 1);
+
+    // Slice methods through Deref
+    assert_eq!(v.len(), 4);
+    assert_eq!(v.iter().max(), Some(&4));
+    assert_eq!(&v[1..3], &[1, 4]);
+
+    let single = NonEmptyVec::new("only", Vec::new());
+    assert_eq!(single.first(), single.last());
+}
+
+#[test]
+fn test_non_empty_vec_try_from() {
+    assert_eq!(NonEmptyVec::<u8>::try_from(Vec::new()), Err(EmptyVecError));
+    let v = NonEmptyVec::try_from(vec!['a', 'b']).unwrap();
+    assert_eq!(*v.last(), 'b');
+    assert_eq!(v.into_vec(), vec!['a', 'b']);
+}
+
+/*
+    From, Into and TryFrom: a bounded integer
+
+    Percent only holds 0..=100. Going *out* of Percent can't fail, so
+    u32 implements From<Percent> -- and Into<u32> for Percent comes with
+    it for free through std's blanket impl (always implement From, never
+    Into directly). Going *in* can fail, so that's TryFrom<u32>, with an
+    error that remembers the rejected value.
+
+    Arithmetic saturates instead of failing: 90% + 20% is 100%, and
+    10% - 20% is 0%. Every Percent that exists is therefore valid.
+*/
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Percent(u8);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PercentOutOfRange(pub u32);
+
+impl fmt::Display for PercentOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is not a percentage (0 to 100)", self.0)
+    }
+}
+
+impl Error for PercentOutOfRange {}
+
+impl Percent {
+    pub const MAX: Percent = Percent(100);
+
+    // Share of `total` that `part` makes up, rounded down
+    pub fn of(part: u32, total: u32) -> Option<Percent> {
+        if total == 0 || part > total {
+            return None;
+        }
+        let p = u64::from(part) * 100 / u64::from(total);
+        Some(Percent(p as u8))
+    }
+}
+
+impl TryFrom<u32> for Percent {
+    type Error = PercentOutOfRange;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        if value <= 100 {
+            Ok(Percent(value as u8))
+        } else {
+            Err(PercentOutOfRange(value))
+        }
+    }
+}
+
+impl From<Percent> for u32 {
+    fn from(p: Percent) -> u32 {
+        u32::from(p.0)
+    }
+}
+
+impl Add for Percent {
+    type Output = Percent;
+
+    fn add(self, other: Percent) -> Percent {
+        // Both are <= 100, so the u8 sum can't overflow
+        Percent((self.0 + other.0).min(100))
+    }
+}
+
+impl Sub for Percent {
+    type Output = Percent;
+
+    fn sub(self, other: Percent) -> Percent {
+        Percent(self.0.saturating_sub(other.0))
+    }
+}
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+#[test]
+fn test_percent_conversions() {
+    let p = Percent::try_from(42).unwrap();
+    assert_eq!(p.to_string(), "42%");
+    let raw: u32 = p.into();
+    assert_eq!(raw, 42);
+
+    assert_eq!(Percent::try_from(100), Ok(Percent::MAX));
+    let err = Percent::try_from(101).unwrap_err();
+    assert_eq!(err, PercentOutOfRange(101));
+    assert_eq!(err.to_string(), "101 is not a percentage (0 to 100)");
+
+    assert_eq!(Percent::of(1, 3), Percent::try_from(33).ok());
+    assert_eq!(Percent::of(4, 3), None);
+    assert_eq!(Percent::of(0, 0), None);
+}
+
+#[test]
+fn test_percent_saturating_arithmetic() {
+    let pct = |n| Percent::try_from(n).unwrap();
+    assert_eq!(pct(90) + pct(20), Percent::MAX);
+    assert_eq!(pct(40) + pct(20), pct(60));
+    assert_eq!(pct(10) - pct(20), Percent::default());
+    assert_eq!(pct(100) + pct(100) - pct(50), pct(50));
+}
+
+/*
+    Borrow: looking up String keys with a &str
+
+    With a HashMap<String, V>, it would be silly to have to allocate a
+    String just to look something up: map.get("key") should work. So
+    std's get isn't get(&self, k: &K), it's
+
+        fn get<Q: ?Sized>(&self, k: &Q) -> Option<&V>
+        where K: Borrow<Q>, Q: Hash + Eq
+
+    "Any Q that a K can be borrowed as." String: Borrow<str>, so Q = str
+    works. (?Sized because str has no fixed size -- we only ever handle
+    it behind a reference.)
+
+    Borrow promises more than AsRef: x.borrow() must hash and compare
+    exactly like x does. That's what makes the lookup correct -- the
+    &str is hashed to find the bucket the String was put in.
+
+    The map itself is as simple as possible: a Vec of buckets, each a
+    Vec of (key, value) pairs. A key's bucket is its hash mod the number
+    of buckets, and the bucket count doubles once there are more entries
+    than buckets, to keep the buckets short.
+*/
+
+pub struct HashMap<K, V> {
+    buckets: Vec<Vec<(K, V)>>,
+    len: usize,
+}
+
+const INITIAL_BUCKETS: usize = 8;
+
+fn bucket_index<Q: Hash + ?Sized>(key: &Q, num_buckets: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % num_buckets as u64) as usize
+}
+
+impl<K: Hash + Eq, V> HashMap<K, V> {
+    pub fn new() -> Self {
+        HashMap {
+            buckets: (0..INITIAL_BUCKETS).map(|_| Vec::new()).collect(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Returns the old value if the key was already present
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(old) = self.get_mut(&key) {
+            return Some(std::mem::replace(old, value));
+        }
+        if self.len >= self.buckets.len() {
+            self.grow();
+        }
+        let i = bucket_index(&key, self.buckets.len());
+        self.buckets[i].push((key, value));
+        self.len += 1;
+        None
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let bucket = &self.buckets[bucket_index(key, self.buckets.len())];
+        bucket.iter().find(|(k, _)| k.borrow() == key).map(|(_, v)| v)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let i = bucket_index(key, self.buckets.len());
+        self.buckets[i]
+            .iter_mut()
+            .find(|(k, _)| k.borrow() == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let i = bucket_index(key, self.buckets.len());
+        let bucket = &mut self.buckets[i];
+        let pos = bucket.iter().position(|(k, _)| k.borrow() == key)?;
+        self.len -= 1;
+        Some(bucket.swap_remove(pos).1)
+    }
+
+    // Every entry's bucket depends on the bucket count, so they all get
+    // redistributed
+    fn grow(&mut self) {
+        let new_count = self.buckets.len() * 2;
+        let mut buckets: Vec<Vec<(K, V)>> =
+            (0..new_count).map(|_| Vec::new()).collect();
+        for (k, v) in self.buckets.drain(..).flatten() {
+            buckets[bucket_index(&k, new_count)].push((k, v));
+        }
+        self.buckets = buckets;
+    }
+}
+
+impl<K: Hash + Eq, V> Default for HashMap<K, V> {
+    fn default() -> Self {
+        HashMap::new()
+    }
+}
+
+#[test]
+fn test_hash_map_borrowed_lookup() {
+    let mut ages: HashMap<String, u32> = HashMap::new();
+    assert_eq!(ages.insert("alice".to_string(), 30), None);
+    ages.insert("bob".to_string(), 25);
+
+    // &str lookups on String keys: no allocation
+    assert_eq!(ages.get("alice"), Some(&30));
+    assert_eq!(ages.get("carol"), None);
+    assert!(ages.contains_key("bob"));
+    // A &String works too (Q = String)
+    assert_eq!(ages.get(&"bob".to_string()), Some(&25));
+
+    *ages.get_mut("bob").unwrap() += 1;
+    assert_eq!(ages.insert("bob".to_string(), 40), Some(26));
+    assert_eq!(ages.remove("alice"), Some(30));
+    assert_eq!(ages.remove("alice"), None);
+    assert_eq!(ages.len(), 1);
+}
+
+#[test]
+fn test_hash_map_grows() {
+    // Vec<T>: Borrow<[T]>, so slices work as lookup keys too
+    let mut map: HashMap<Vec<u32>, usize> = HashMap::new();
+    for i in 0..1000 {
+        map.insert(vec![i, i * 2], i as usize);
+    }
+    assert_eq!(map.len(), 1000);
+    assert!(map.buckets.len() >= 1000);
+    assert!((0..1000).all(|i| map.get(&[i, i * 2][..]) == Some(&(i as usize))));
+    assert_eq!(map.get(&[1, 3][..]), None);
+}
+
+/*
+    A bounded cache with Borrow lookups
+
+    The HashMap above is the whole point of Borrow: keys are owned
+    (String), lookups can be borrowed (&str). Cache builds on it and adds
+    what a cache needs on top of a map:
+    - a capacity: once it's full, inserting a new key evicts the oldest
+      entry (first in, first out -- the VecDeque remembers the order)
+    - hit and miss counters, to tell whether the cache is earning its
+      memory
+
+    get takes &self, because looking something up shouldn't need
+    exclusive access to the cache, but it still has to bump a counter.
+    Those counters are Cells, like the listen count on SongUserProfile.
+*/
+
+pub struct Cache<K: Hash + Eq, V> {
+    map: HashMap<K, V>,
+    // Keys in insertion order, oldest at the front
+    order: VecDeque<K>,
+    capacity: usize,
+    hits: Cell<usize>,
+    misses: Cell<usize>,
+}
+
+impl<K: Hash + Eq + Clone, V> Cache<K, V> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "a cache needs room for at least one entry");
+        Cache {
+            map: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let found = self.map.get(key);
+        let counter = if found.is_some() { &self.hits } else { &self.misses };
+        counter.set(counter.get() + 1);
+        found
+    }
+
+    // Returns the evicted entry's key, if one had to go
+    pub fn insert(&mut self, key: K, value: V) -> Option<K> {
+        if self.map.insert(key.clone(), value).is_some() {
+            // Replacing a value doesn't make the key any newer
+            return None;
+        }
+        self.order.push_back(key);
+        if self.order.len() <= self.capacity {
+            return None;
+        }
+        let oldest = self.order.pop_front()?;
+        self.map.remove(&oldest);
+        Some(oldest)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits.get()
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses.get()
+    }
+}
+
+#[test]
+fn test_cache_borrow_lookups() {
+    let mut lyrics: Cache<String, &str> = Cache::with_capacity(10);
+    lyrics.insert(String::from("Help!"), "Help! I need somebody");
+    // &str looks up a String key; so do &String and String::as_str
+    assert_eq!(lyrics.get("Help!"), Some(&"Help! I need somebody"));
+    let owned = String::from("Help!");
+    assert!(lyrics.get(&owned).is_some());
+    assert_eq!(lyrics.get("Girl"), None);
+    assert_eq!((lyrics.hits(), lyrics.misses()), (2, 1));
+}
+
+#[test]
+fn test_cache_capacity() {
+    let mut cache = Cache::with_capacity(2);
+    assert_eq!(cache.insert(String::from("a"), 1), None);
+    assert_eq!(cache.insert(String::from("b"), 2), None);
+    // Replacing doesn't evict
+    assert_eq!(cache.insert(String::from("a"), 10), None);
+    assert_eq!(cache.get("a"), Some(&10));
+    // Full: "a" is the oldest entry
+    assert_eq!(cache.insert(String::from("c"), 3), Some(String::from("a")));
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get("a"), None);
+    assert_eq!(cache.get("c"), Some(&3));
+    assert_eq!((cache.hits(), cache.misses()), (2, 1));
+}
+
+/*
+    String interning
+
+    Many profiles like the same songs, so the same names get stored over
+    and over. An Interner keeps one copy of each distinct string and
+    hands out a Symbol -- a small Copy id -- in its place. Symbols compare
+    and hash as fast as integers, and resolve() turns one back into text.
+
+    Each string is stored once, as an Rc<str>, shared between the Vec
+    that resolve() indexes and the map that intern() searches. That map
+    is the HashMap above with Rc<str> keys: Rc<str>: Borrow<str>, so we
+    can search it with the caller's &str directly. Interning a string
+    we've already seen doesn't allocate at all.
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+pub struct Interner {
+    ids: HashMap<Rc<str>, Symbol>,
+    strings: Vec<Rc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(&sym) = self.ids.get(s) {
+            return sym;
+        }
+        let sym = Symbol(self.strings.len() as u32);
+        let shared: Rc<str> = Rc::from(s);
+        self.strings.push(Rc::clone(&shared));
+        self.ids.insert(shared, sym);
+        sym
+    }
+
+    // Like intern, but never adds anything
+    pub fn lookup(&self, s: &str) -> Option<Symbol> {
+        self.ids.get(s).copied()
+    }
+
+    pub fn resolve(&self, sym: Symbol) -> &str {
+        &self.strings[sym.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[test]
+fn test_interner() {
+    let mut names = Interner::new();
+    let help = names.intern("Help!");
+    let yesterday = names.intern("Yesterday");
+    assert_ne!(help, yesterday);
+    assert_eq!(names.intern("Help!"), help);
+    assert_eq!(names.len(), 2);
+
+    assert_eq!(names.resolve(yesterday), "Yesterday");
+    assert_eq!(names.lookup("Yesterday"), Some(yesterday));
+    assert_eq!(names.lookup("Let It Be"), None);
+    assert_eq!(names.len(), 2);
+}
+
+#[test]
+fn test_interner_shares_storage() {
+    use iterators::song;
+
+    let mut names = Interner::new();
+    let liked = [song("Help!"), song("Yesterday")];
+    let also_liked = [song("Yesterday"), song("Help!"), song("Help!")];
+    let a: Vec<Symbol> =
+        liked.iter().map(|s| names.intern(s.as_str())).collect();
+    let b: Vec<Symbol> =
+        also_liked.iter().map(|s| names.intern(s.as_str())).collect();
+    assert_eq!(b, vec![a[1], a[0], a[0]]);
+
+    // One copy of each name: the Vec and the map share each Rc
+    assert_eq!(names.len(), 2);
+    assert!(names.strings.iter().all(|s| Rc::strong_count(s) == 2));
+}
+
+/*
+    A Cow-based text store
+
+    Interner copies every string it's given into an Rc<str>. When most of
+    the text already lives somewhere that outlives the store (a file read
+    into one big String, say), that's wasted work. TextStore<'a> takes a
+    Cow<'a, str> instead and keeps each kind the cheap way:
+    - Cow::Borrowed(&'a str): store the reference itself. No copy.
+    - Cow::Owned(String): copy the text onto the end of one internal
+      String (the arena) and remember its range. Many small allocations
+      become one growing buffer.
+    Either way an equal string that's already stored isn't stored again;
+    insert returns the existing TextId, and an owned duplicate is simply
+    dropped.
+
+    Looking for a duplicate needs a map from text to id, but the owned
+    text lives inside our own arena, and a struct can't hold references
+    into itself. So the map is keyed by the text's hash instead, with the
+    (rare) colliding ids compared one by one.
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextId(u32);
+
+enum StoredText<'a> {
+    Borrowed(&'a str),
+    // A byte range of the arena
+    Owned(usize, usize),
+}
+
+#[derive(Default)]
+pub struct TextStore<'a> {
+    entries: Vec<StoredText<'a>>,
+    arena: String,
+    by_hash: HashMap<u64, Vec<TextId>>,
+}
+
+pub(crate) fn hash_str(s: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    s.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<'a> TextStore<'a> {
+    pub fn new() -> Self {
+        TextStore::default()
+    }
+
+    pub fn insert(&mut self, text: Cow<'a, str>) -> TextId {
+        let hash = hash_str(&text);
+        if let Some(id) = self.find_hashed(&text, hash) {
+            return id;
+        }
+        let id = TextId(self.entries.len() as u32);
+        self.entries.push(match text {
+            Cow::Borrowed(s) => StoredText::Borrowed(s),
+            Cow::Owned(s) => {
+                let start = self.arena.len();
+                self.arena.push_str(&s);
+                StoredText::Owned(start, self.arena.len())
+            }
+        });
+        match self.by_hash.get_mut(&hash) {
+            Some(ids) => ids.push(id),
+            None => {
+                self.by_hash.insert(hash, vec![id]);
+            }
+        }
+        id
+    }
+
+    fn find_hashed(&self, text: &str, hash: u64) -> Option<TextId> {
+        let ids = self.by_hash.get(&hash)?;
+        ids.iter().copied().find(|&id| self.get(id) == text)
+    }
+
+    pub fn find(&self, text: &str) -> Option<TextId> {
+        self.find_hashed(text, hash_str(text))
+    }
+
+    pub fn get(&self, id: TextId) -> &str {
+        match self.entries[id.0 as usize] {
+            StoredText::Borrowed(s) => s,
+            StoredText::Owned(start, end) => &self.arena[start..end],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // How many bytes were actually copied into the store
+    pub fn owned_bytes(&self) -> usize {
+        self.arena.len()
+    }
+}
+
+#[test]
+fn test_text_store_borrows_and_owns() {
+    let source = String::from("Help! Yesterday");
+    let mut store = TextStore::new();
+    let help = store.insert(Cow::Borrowed(&source[..5]));
+    let yesterday = store.insert(Cow::Borrowed(&source[6..]));
+    assert_eq!(store.owned_bytes(), 0);
+    // The stored &str points into `source` itself
+    assert_eq!(store.get(help).as_ptr(), source.as_ptr());
+
+    let made_up = store.insert(Cow::Owned(format!("{} {}", "Let", "It Be")));
+    assert_eq!(store.get(made_up), "Let It Be");
+    assert_eq!(store.owned_bytes(), 9);
+    assert_eq!(store.get(yesterday), "Yesterday");
+    assert_eq!(store.find("Yesterday"), Some(yesterday));
+    assert_eq!(store.find("Girl"), None);
+}
+
+#[test]
+fn test_text_store_dedups() {
+    let source = "Help!";
+    let mut store = TextStore::new();
+    let owned = store.insert(Cow::Owned(String::from("Help!")));
+    // Equal text returns the same id, borrowed or owned
+    assert_eq!(store.insert(Cow::Borrowed(source)), owned);
+    assert_eq!(store.insert(Cow::Owned(String::from("Help!"))), owned);
+    assert_eq!(store.len(), 1);
+    assert_eq!(store.owned_bytes(), 5);
+
+    let words: Vec<String> =
+        "a b a c b a".split(' ').map(String::from).collect();
+    let ids: Vec<TextId> =
+        words.iter().map(|w| store.insert(Cow::Borrowed(w))).collect();
+    assert_eq!(ids[0], ids[2]);
+    assert_eq!(ids[1], ids[4]);
+    assert_eq!(store.len(), 4);
+}
+
+/*
+    When is Borrow<str> correct?
+
+    Borrow's promise (x.borrow() hashes and compares like x) means a key
+    type can only implement Borrow<str> if its Hash and Eq look at that
+    str and nothing else.
+
+    UserKey stores which realm (login provider) a user came from, but
+    usernames are unique across realms, so only the name identifies the
+    user. Hash and Eq are written by hand to ignore the realm, and then
+    Borrow<str> is correct: a map keyed by UserKey can be searched with
+    a plain &str.
+
+    If usernames were only unique per realm, Eq would have to include the
+    realm, and hashing the bare name would give a different hash from
+    hashing the key -- lookups by &str would silently miss. That's when
+    to use AsRef<str> instead: AsRef just means "can show you a &str",
+    with no promise about hashing or equality.
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Realm {
+    Local,
+    Google,
+    GitHub,
+}
+
+#[derive(Clone, Debug)]
+pub struct UserKey {
+    pub name: String,
+    pub realm: Realm,
+}
+
+impl PartialEq for UserKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for UserKey {}
+
+impl Hash for UserKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Must match str's Hash for Borrow<str> to be correct
+        self.name.hash(state);
+    }
+}
+
+impl Borrow<str> for UserKey {
+    fn borrow(&self) -> &str {
+        &self.name
+    }
+}
+
+impl AsRef<str> for UserKey {
+    fn as_ref(&self) -> &str {
+        &self.name
+    }
+}
+
+#[test]
+fn test_user_key_borrow_lookup() {
+    use std::collections::HashMap as StdHashMap;
+
+    let key = |name: &str, realm| UserKey { name: name.to_string(), realm };
+    let mut logins: StdHashMap<UserKey, u32> = StdHashMap::new();
+    logins.insert(key("alice", Realm::Google), 3);
+    logins.insert(key("bob", Realm::Local), 1);
+    assert_eq!(logins.get("alice"), Some(&3));
+    assert_eq!(logins.get("carol"), None);
+
+    // Same name, other realm: the same user
+    logins.insert(key("alice", Realm::GitHub), 4);
+    assert_eq!(logins.len(), 2);
+    assert_eq!(logins.get("alice"), Some(&4));
+
+    // Works with our HashMap too
+    let mut ours = HashMap::new();
+    ours.insert(key("bob", Realm::Local), "admin");
+    assert_eq!(ours.get("bob"), Some(&"admin"));
+}
+
+// The wrong way: Eq and Hash include the realm, but Borrow<str> is
+// implemented anyway
+#[cfg(test)]
+#[derive(PartialEq, Eq, Hash)]
+struct RealmUserKey {
+    name: String,
+    realm: Realm,
+}
+
+#[cfg(test)]
+impl Borrow<str> for RealmUserKey {
+    fn borrow(&self) -> &str {
+        &self.name
+    }
+}
+
+#[test]
+fn test_incorrect_borrow_misses() {
+    // Our HashMap, because its DefaultHasher::new() hashes the same way
+    // on every run (std's HashMap is randomly seeded)
+    let mut logins = HashMap::new();
+    let names = ["alice", "bob", "carol", "dave"];
+    for name in &names {
+        logins.insert(
+            RealmUserKey { name: name.to_string(), realm: Realm::Local },
+            1,
+        );
+    }
+    // The derived Hash also hashes the realm, so a bare name usually
+    // hashes to a different bucket: the entries are there, but most
+    // can't be found by name
+    assert_eq!(logins.len(), 4);
+    let found =
+        names.iter().filter(|&&name| logins.get(name).is_some()).count();
+    assert!(found < names.len());
+}
+
+/*
+    Operator overloading: a 2D vector
+
+    Each operator is a trait in std::ops: a + b calls Add::add(a, b),
+    -a calls Neg::neg(a), a += b calls AddAssign::add_assign(&mut a, b).
+    Vec2 is Copy, so the operators can take it by value without moving
+    anything the caller still wants.
+
+    Mul's right-hand side is a type parameter, so "vector * scalar" is
+    impl Mul<f64> for Vec2. "scalar * vector" is a different impl, on
+    f64 -- allowed because Vec2 is our type.
+
+    Sum lets .sum() work on an iterator of Vec2s. It's written on top of
+    Add, starting from the zero vector.
+
+    Floating point arithmetic rounds, so == on computed values is
+    fragile; approx_eq compares with a tolerance instead.
+*/
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+
+    pub fn new(x: f64, y: f64) -> Self {
+        Vec2 { x, y }
+    }
+
+    pub fn dot(self, other: Vec2) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn approx_eq(self, other: Vec2, epsilon: f64) -> bool {
+        (self.x - other.x).abs() <= epsilon
+            && (self.y - other.y).abs() <= epsilon
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl Mul<f64> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, k: f64) -> Vec2 {
+        Vec2::new(self.x * k, self.y * k)
+    }
+}
+
+impl Mul<Vec2> for f64 {
+    type Output = Vec2;
+
+    fn mul(self, v: Vec2) -> Vec2 {
+        v * self
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Vec2) {
+        *self = *self + other;
+    }
+}
+
+impl Sum for Vec2 {
+    fn sum<I: Iterator<Item = Vec2>>(iter: I) -> Vec2 {
+        iter.fold(Vec2::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Vec2> for Vec2 {
+    fn sum<I: Iterator<Item = &'a Vec2>>(iter: I) -> Vec2 {
+        iter.copied().sum()
+    }
+}
+
+#[test]
+fn test_vec2_ops() {
+    let a = Vec2::new(1.0, 2.0);
+    let b = Vec2::new(3.0, -1.0);
+    assert_eq!(a + b, Vec2::new(4.0, 1.0));
+    assert_eq!(a - b, Vec2::new(-2.0, 3.0));
+    assert_eq!(-a, Vec2::new(-1.0, -2.0));
+    assert_eq!(a * 2.0, 2.0 * a);
+    assert_eq!(Vec2::new(3.0, 4.0).length(), 5.0);
+
+    let mut pos = Vec2::ZERO;
+    for _ in 0..10 {
+        pos += Vec2::new(0.1, 0.2);
+    }
+    // Rounding: ten 0.1s don't add up to exactly 1.0
+    assert_ne!(pos, Vec2::new(1.0, 2.0));
+    assert!(pos.approx_eq(Vec2::new(1.0, 2.0), 1e-9));
+}
+
+#[test]
+fn test_vec2_sum() {
+    // Centroid of a square's corners
+    let corners = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(2.0, 0.0),
+        Vec2::new(2.0, 2.0),
+        Vec2::new(0.0, 2.0),
+    ];
+    let centroid = corners.iter().sum::<Vec2>() * (1.0 / corners.len() as f64);
+    assert_eq!(centroid, Vec2::new(1.0, 1.0));
+    assert_eq!(Vec::<Vec2>::new().into_iter().sum::<Vec2>(), Vec2::ZERO);
+}
+
+/*
+    Units of measure
+
+    If durations are plain u64s, nothing stops you from adding seconds to
+    milliseconds. Give each unit its own newtype and that becomes a type
+    error: there's an Add for Seconds + Seconds, but none for
+    Seconds + Milliseconds, so
+
+        Seconds(1) + Milliseconds(500)
+
+    doesn't compile. To combine them you convert first, and the
+    conversions encode which direction is safe:
+    - TryFrom goes to a finer unit (Minutes -> Seconds -> Milliseconds).
+      There's no rounding, but all three hold a u64, so a big enough
+      Seconds has no Milliseconds to convert to: u64::MAX seconds is
+      u64::MAX * 1000 milliseconds. From promises it can't fail, so
+      these are TryFrom with checked_mul, and the error is
+      DurationOverflow. Milliseconds::try_from(Seconds(2)) is
+      Ok(Milliseconds(2000)).
+    - going to a coarser unit loses the remainder, so it's a named
+      method instead of From: Milliseconds(2500).whole_seconds() is
+      Seconds(2)
+
+    (std::time::Duration solves the same problem with one type that
+    stores nanoseconds; these exist to show the newtype pattern.)
+*/
+
+macro_rules! unit_ops {
+    ($($t:ident),*) => {
+        $(
+            impl Add for $t {
+                type Output = $t;
+
+                fn add(self, other: $t) -> $t {
+                    $t(self.0 + other.0)
+                }
+            }
+
+            impl Sub for $t {
+                type Output = $t;
+
+                fn sub(self, other: $t) -> $t {
+                    $t(self.0 - other.0)
+                }
+            }
+
+            impl AddAssign for $t {
+                fn add_assign(&mut self, other: $t) {
+                    self.0 += other.0;
+                }
+            }
+
+            impl Sum for $t {
+                fn sum<I: Iterator<Item = $t>>(iter: I) -> $t {
+                    $t(iter.map(|x| x.0).sum())
+                }
+            }
+        )*
+    };
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Milliseconds(pub u64);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Seconds(pub u64);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Minutes(pub u64);
+
+unit_ops!(Milliseconds, Seconds, Minutes);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DurationOverflow;
+
+impl fmt::Display for DurationOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("duration too long for the finer unit")
+    }
+}
+
+impl Error for DurationOverflow {}
+
+impl TryFrom<Seconds> for Milliseconds {
+    type Error = DurationOverflow;
+
+    fn try_from(s: Seconds) -> Result<Self, Self::Error> {
+        s.0.checked_mul(1000).map(Milliseconds).ok_or(DurationOverflow)
+    }
+}
+
+impl TryFrom<Minutes> for Seconds {
+    type Error = DurationOverflow;
+
+    fn try_from(m: Minutes) -> Result<Self, Self::Error> {
+        m.0.checked_mul(60).map(Seconds).ok_or(DurationOverflow)
+    }
+}
+
+impl TryFrom<Minutes> for Milliseconds {
+    type Error = DurationOverflow;
+
+    fn try_from(m: Minutes) -> Result<Self, Self::Error> {
+        Milliseconds::try_from(Seconds::try_from(m)?)
+    }
+}
+
+impl Milliseconds {
+    pub fn whole_seconds(self) -> Seconds {
+        Seconds(self.0 / 1000)
+    }
+}
+
+impl Seconds {
+    pub fn whole_minutes(self) -> Minutes {
+        Minutes(self.0 / 60)
+    }
+}
+
+// m:ss, the usual way to show a track length
+impl fmt::Display for Seconds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{:02}", self.0 / 60, self.0 % 60)
+    }
+}
+
+#[test]
+fn test_unit_conversions() {
+    assert_eq!(Milliseconds::try_from(Seconds(2)), Ok(Milliseconds(2000)));
+    assert_eq!(Seconds::try_from(Minutes(3)), Ok(Seconds(180)));
+    assert_eq!(Milliseconds::try_from(Minutes(1)), Ok(Milliseconds(60_000)));
+
+    // Coarser units drop the remainder
+    assert_eq!(Milliseconds(2999).whole_seconds(), Seconds(2));
+    assert_eq!(Seconds(119).whole_minutes(), Minutes(1));
+    assert_eq!(Seconds(185).to_string(), "3:05");
+}
+
+#[test]
+fn test_unit_conversion_overflow() {
+    assert_eq!(
+        Milliseconds::try_from(Seconds(u64::MAX / 10)),
+        Err(DurationOverflow)
+    );
+    assert_eq!(Seconds::try_from(Minutes(u64::MAX)), Err(DurationOverflow));
+    // Fits in Seconds, but not in Milliseconds
+    let m = Minutes(u64::MAX / 60);
+    assert!(Seconds::try_from(m).is_ok());
+    assert_eq!(Milliseconds::try_from(m), Err(DurationOverflow));
+
+    // The largest convertible value still converts exactly
+    let s = Seconds(u64::MAX / 1000);
+    assert_eq!(Milliseconds::try_from(s).unwrap().whole_seconds(), s);
+}
+
+#[test]
+fn test_unit_arithmetic() {
+    // Seconds(1) + Milliseconds(500) is a compile error; convert first
+    let total = Milliseconds::try_from(Seconds(1)).unwrap() + Milliseconds(500);
+    assert_eq!(total, Milliseconds(1500));
+
+    let mut t = Seconds(10);
+    t += Seconds(5);
+    assert_eq!(t - Seconds(3), Seconds(12));
+    let album: Seconds =
+        [Seconds(200), Seconds(185), Seconds::try_from(Minutes(4)).unwrap()]
+            .iter()
+            .copied()
+            .sum();
+    assert_eq!(album, Seconds(625));
+}
+
+/*
+    Validated<T>: a proof in the type
+
+    A function that needs valid input can check it itself, every time,
+    or it can take a type that can only hold valid values. Validated<T>
+    is that type: its field is private, and the only code that creates
+    one is Validate::validate, which runs the check first. So holding a
+    Validated<T> proves the check passed -- the compiler won't let a
+    caller skip it.
+
+    Deref gives read access to the T. There's no DerefMut: mutating the
+    value could break what was checked. To change it, take it back out
+    with into_inner() and validate again.
+*/
+
+pub trait Validate: Sized {
+    type Error;
+
+    fn check(&self) -> Result<(), Self::Error>;
+
+    fn validate(self) -> Result<Validated<Self>, Self::Error> {
+        self.check()?;
+        Ok(Validated(self))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Validated<T>(T);
+
+impl<T> Validated<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Validated<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[test]
+fn test_validated() {
+    #[derive(Clone, Copy)]
+    struct Even(u32);
+
+    impl Validate for Even {
+        type Error = u32;
+
+        fn check(&self) -> Result<(), u32> {
+            if self.0.is_multiple_of(2) {
+                Ok(())
+            } else {
+                Err(self.0)
+            }
+        }
+    }
+
+    fn half(n: &Validated<Even>) -> u32 {
+        let Even(value) = **n;
+        value / 2
+    }
+
+    assert_eq!(half(&Even(10).validate().unwrap()), 5);
+    assert_eq!(Even(7).validate().err(), Some(7));
+    assert_eq!(Even(4).validate().unwrap().into_inner().0, 4);
+}
+
+/*
+    Any: one value per type
+
+    A TypeMap stores at most one value of each type, looked up by the type
+    itself: map.insert(Volume(7)), then map.get::<Volume>(). Useful for
+    "extension data" that different parts of a program attach to a shared
+    object without knowing about each other.
+
+    Values of different types can't share a Vec or map directly, so they
+    go in as Box<dyn Any>. Any is implemented for every 'static type and
+    can do two things: report the value's TypeId, and downcast back to
+    the concrete type (returning None if it's the wrong one). The key is
+    TypeId::of::<T>(), so each value is stored under its own type and
+    the downcasts below can't actually fail.
+*/
+
+#[derive(Default)]
+pub struct TypeMap {
+    values: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl TypeMap {
+    pub fn new() -> Self {
+        TypeMap::default()
+    }
+
+    // Returns the previous value of this type, if any
+    pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast().ok())
+            .map(|old: Box<T>| *old)
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.values.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        let boxed = self.values.remove(&TypeId::of::<T>())?;
+        boxed.downcast().ok().map(|b: Box<T>| *b)
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+#[test]
+fn test_type_map() {
+    #[derive(Debug, PartialEq)]
+    struct Volume(u8);
+    #[derive(Debug, PartialEq)]
+    struct Theme(&'static str);
+
+    let mut ext = TypeMap::new();
+    assert_eq!(ext.insert(Volume(7)), None);
+    ext.insert(Theme("dark"));
+    ext.insert(vec![1, 2, 3]);
+    assert_eq!(ext.len(), 3);
+
+    assert_eq!(ext.get::<Volume>(), Some(&Volume(7)));
+    assert_eq!(ext.get::<Theme>(), Some(&Theme("dark")));
+    // Vec<i32> and Vec<u8> are different types
+    assert!(ext.contains::<Vec<i32>>());
+    assert_eq!(ext.get::<Vec<u8>>(), None);
+
+    ext.get_mut::<Vec<i32>>().unwrap().push(4);
+    assert_eq!(ext.get::<Vec<i32>>().map(|v| v.len()), Some(4));
+
+    assert_eq!(ext.insert(Volume(11)), Some(Volume(7)));
+    assert_eq!(ext.remove::<Theme>(), Some(Theme("dark")));
+    assert_eq!(ext.remove::<Theme>(), None);
+    assert_eq!(ext.len(), 2);
+}
+
+/*
+    Drop order, observed
+
+    Rust's drop order is fully specified:
+    - locals: reverse order of declaration (last in, first out)
+    - struct fields: declaration order, after the struct's own Drop
+      (if it has one) has run
+    - Vec/array elements: first to last
+    - temporaries: at the end of the statement
+    DropTracker makes this testable: each Tracked value records its
+    label in a shared log when it's dropped, and the test checks the log.
+
+    The log is an Rc<RefCell<Vec>> so every Tracked can push to it
+    without the tracker having to outlive them in any particular way.
+*/
+
+#[derive(Clone, Default)]
+pub struct DropTracker {
+    log: Rc<RefCell<Vec<String>>>,
+}
+
+pub struct Tracked {
+    label: String,
+    log: Rc<RefCell<Vec<String>>>,
+}
+
+impl DropTracker {
+    pub fn new() -> Self {
+        DropTracker::default()
+    }
+
+    pub fn track<S: Into<String>>(&self, label: S) -> Tracked {
+        Tracked { label: label.into(), log: Rc::clone(&self.log) }
+    }
+
+    // Labels in the order they were dropped
+    pub fn dropped(&self) -> Vec<String> {
+        RefCell::borrow(&self.log).clone()
+    }
+
+    pub fn clear(&self) {
+        self.log.borrow_mut().clear();
+    }
+}
+
+impl Tracked {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.log.borrow_mut().push(self.label.clone());
+    }
+}
+
+#[test]
+fn test_drop_order_locals_and_vecs() {
+    let tracker = DropTracker::new();
+    {
+        let _a = tracker.track("a");
+        let _b = tracker.track("b");
+        let _v: Vec<Tracked> =
+            ["v0", "v1"].iter().map(|l| tracker.track(*l)).collect();
+        // A temporary: dropped at the end of this statement
+        assert_eq!(tracker.track("temp").label(), "temp");
+        assert_eq!(tracker.dropped(), vec!["temp"]);
+    }
+    assert_eq!(tracker.dropped(), vec!["temp", "v0", "v1", "b", "a"]);
+
+    // Moving a value out of scope moves its drop too
+    tracker.clear();
+    let kept = {
+        let _x = tracker.track("x");
+        tracker.track("kept")
+    };
+    assert_eq!(tracker.dropped(), vec!["x"]);
+    drop(kept);
+    assert_eq!(tracker.dropped(), vec!["x", "kept"]);
+}
+
+#[test]
+fn test_drop_order_struct_fields() {
+    struct Outer {
+        log: DropTracker,
+        _first: Tracked,
+        _second: Tracked,
+    }
+
+    impl Drop for Outer {
+        fn drop(&mut self) {
+            // Runs before any field is dropped, so fields are still usable
+            self.log.log.borrow_mut().push("Outer".to_string());
+        }
+    }
+
+    let tracker = DropTracker::new();
+    let outer = Outer {
+        log: tracker.clone(),
+        // Initialization order doesn't matter, declaration order does
+        _second: tracker.track("second"),
+        _first: tracker.track("first"),
+    };
+    drop(outer);
+    assert_eq!(tracker.dropped(), vec!["Outer", "first", "second"]);
+}
+
+/*
+    An extension trait for Display
+
+    Like MyIteratorExt in adapters.rs: a trait with default methods and a
+    blanket impl for every T: Display, so once DisplayExt is imported,
+    anything printable gets these methods.
+
+    Each method returns a small wrapper that borrows the value and
+    implements Display itself, formatting lazily when it's printed. No
+    String is built unless the caller asks for one (.to_string()), and
+    because the wrappers are Display too, they chain:
+
+        println!("{}", title.truncated(20).padded(22).quoted());
+
+    Lengths are in chars, not bytes.
+*/
+
+pub trait DisplayExt: fmt::Display {
+    // "like this"
+    fn quoted(&self) -> Quoted<'_, Self> {
+        Quoted(self)
+    }
+
+    // Left-aligned, padded with spaces to at least `width` chars
+    fn padded(&self, width: usize) -> Padded<'_, Self> {
+        Padded(self, width)
+    }
+
+    // At most `max` chars, ending in "…" if anything was cut
+    fn truncated(&self, max: usize) -> Truncated<'_, Self> {
+        Truncated(self, max)
+    }
+}
+
+impl<T: fmt::Display + ?Sized> DisplayExt for T {}
+
+pub struct Quoted<'a, T: ?Sized>(&'a T);
+pub struct Padded<'a, T: ?Sized>(&'a T, usize);
+pub struct Truncated<'a, T: ?Sized>(&'a T, usize);
+
+impl<'a, T: fmt::Display + ?Sized> fmt::Display for Quoted<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\"", self.0)
+    }
+}
+
+impl<'a, T: fmt::Display + ?Sized> fmt::Display for Padded<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // {:<width$} on T itself would only work if T's Display impl
+        // respects width (many custom ones don't), so format it first
+        write!(f, "{:<width$}", self.0.to_string(), width = self.1)
+    }
+}
+
+impl<'a, T: fmt::Display + ?Sized> fmt::Display for Truncated<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = self.0.to_string();
+        if s.chars().count() <= self.1 {
+            return f.write_str(&s);
+        }
+        let kept: String = s.chars().take(self.1.saturating_sub(1)).collect();
+        write!(f, "{}", kept)?;
+        if self.1 > 0 {
+            f.write_str("…")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_display_ext() {
+    assert_eq!("hi".quoted().to_string(), "\"hi\"");
+    assert_eq!(42.padded(5).to_string(), "42   ");
+    assert_eq!("toolong".padded(3).to_string(), "toolong");
+    assert_eq!("Hello, world".truncated(6).to_string(), "Hello…");
+    assert_eq!("short".truncated(10).to_string(), "short");
+    assert_eq!("abc".truncated(0).to_string(), "");
+
+    // Works on our own Display types, and chains
+    let t = Seconds(185);
+    assert_eq!(format!("[{}]", t.padded(6)), "[3:05  ]");
+    let name = StringWrapper::from("Sgt. Pepper's Lonely Hearts Club Band");
+    assert_eq!(name.truncated(12).quoted().to_string(), "\"Sgt. Pepper…\"");
+    assert_eq!("ünï".truncated(2).padded(3).to_string(), "ü… ");
+}
+
+/*
+    Immutable<T>: read-only by construction
+
+    Deref without DerefMut. Method calls and &-coercion reach the T as
+    usual -- len(), iter(), contains(), &*value -- but there is no path
+    to a &mut T, so a caller holding an Immutable can read it fluently
+    and nothing else.
+
+    Getting the T back out is only offered when T: Clone: into_inner
+    gives the caller their own copy to do what they like with, which
+    they could have made anyway by cloning through Deref. A value that
+    can't be cloned (a unique handle, say) stays locked in.
+
+    SongUserProfile::liked() uses it to hand out a snapshot of the liked
+    songs. Edits have to go through like()/dislike(), which keep the
+    list deduplicated; a plain Vec would invite push() on a copy that
+    the profile never sees.
+*/
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Immutable<T>(T);
+
+impl<T> Immutable<T> {
+    pub fn new(value: T) -> Self {
+        Immutable(value)
+    }
+}
+
+impl<T: Clone> Immutable<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Immutable<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Immutable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[test]
+fn test_immutable() {
+    let words = Immutable::new(vec!["a", "b"]);
+    assert_eq!(words.len(), 2);
+    assert!(words.contains(&"b"));
+    let slice: &[&str] = &words;
+    assert_eq!(slice, ["a", "b"]);
+    // words.push("c") doesn't compile: cannot borrow data in
+    // dereference of `Immutable<Vec<&str>>` as mutable
+
+    let mut copy = words.into_inner();
+    copy.push("c");
+    assert_eq!(copy.len(), 3);
+
+    assert_eq!(Immutable::new(Percent::MAX).to_string(), "100%");
+}